@@ -0,0 +1,113 @@
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+// Axis aligned bounding box
+#[derive(Clone, Copy)]
+pub struct Aabb
+{
+    pub min : Vector,
+    pub max : Vector
+}
+
+impl Aabb
+{
+    pub fn new(min_corner : Vector, max_corner : Vector) -> Self
+    {
+        Aabb
+        {
+            min : min_corner,
+            max : max_corner
+        }
+    }
+
+    // Smallest box containing all given points
+    pub fn from_points(points : &[Vector]) -> Self
+    {
+        let mut result = Aabb::new(points[0], points[0]);
+
+        for point in points.iter().skip(1)
+        { result = result.grow(*point); }
+
+        return result;
+    }
+
+    pub fn grow(&self, point : Vector) -> Aabb
+    {
+        Aabb
+        {
+            min : Vector { x : self.min.x.min(point.x), y : self.min.y.min(point.y), z : self.min.z.min(point.z) },
+            max : Vector { x : self.max.x.max(point.x), y : self.max.y.max(point.y), z : self.max.z.max(point.z) }
+        }
+    }
+
+    pub fn union(&self, other : &Aabb) -> Aabb
+    { return self.grow(other.min).grow(other.max); }
+
+    pub fn centroid(&self) -> Vector
+    { return (self.min + self.max) * 0.5; }
+
+    // Checks whether the point lies inside the box enlarged by margin on every side
+    pub fn contains(&self, point : Vector, margin : f64) -> bool
+    {
+        point.x >= self.min.x - margin && point.x <= self.max.x + margin &&
+        point.y >= self.min.y - margin && point.y <= self.max.y + margin &&
+        point.z >= self.min.z - margin && point.z <= self.max.z + margin
+    }
+
+    pub fn corners(&self) -> [Vector; 8]
+    {
+        return std::array::from_fn(|i| Vector
+        {
+            x : if i & 1 == 0 { self.min.x } else { self.max.x },
+            y : if i & 2 == 0 { self.min.y } else { self.max.y },
+            z : if i & 4 == 0 { self.min.z } else { self.max.z }
+        });
+    }
+
+    // Box containing this box after transforming it by the matrix
+    pub fn transform(&self, matrix : &Matrix4) -> Aabb
+    {
+        let corners = self.corners().map(|corner| matrix.transform_point(corner));
+
+        return Aabb::from_points(&corners);
+    }
+
+    // Checks whether the ray enters the box before max_distance
+    pub fn hit(&self, ray : Ray, max_distance : f64) -> bool
+    { return self.clip(ray, max_distance).is_some(); }
+
+    // Part of the ray between its origin and max_distance that lies inside the box
+    pub fn clip(&self, ray : Ray, max_distance : f64) -> Option<(f64, f64)>
+    {
+        let origins = [ray.origin.x, ray.origin.y, ray.origin.z];
+        let dests = [ray.dest.x, ray.dest.y, ray.dest.z];
+        let mins = [self.min.x, self.min.y, self.min.z];
+        let maxs = [self.max.x, self.max.y, self.max.z];
+
+        let mut t_near = 0.0;
+        let mut t_far = max_distance;
+
+        for axis in 0..3
+        {
+            let inv_dest = 1.0 / dests[axis];
+            let mut t0 = (mins[axis] - origins[axis]) * inv_dest;
+            let mut t1 = (maxs[axis] - origins[axis]) * inv_dest;
+
+            if t0 > t1
+            { std::mem::swap(&mut t0, &mut t1); }
+
+            // Written so that NaNs from rays parallel to a slab keep the current interval
+            t_near = if t0 > t_near { t0 } else { t_near };
+            t_far = if t1 < t_far { t1 } else { t_far };
+
+            // Guard against rounding errors for flat boxes
+            t_far *= 1.0 + 1e-9;
+
+            if t_near > t_far
+            { return None; }
+        }
+
+        return Some((t_near, t_far));
+    }
+}
@@ -0,0 +1,603 @@
+use std::f64::consts::FRAC_1_PI;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::color::Color;
+use crate::lights::{emission_pdf, emitted, light_area_pdf, sample_emission};
+use crate::material::MatType;
+use crate::object::{Hit, Object};
+use crate::random;
+use crate::ray::Ray;
+use crate::render::{diffuse_brdf, refractive_brdf, specular_brdf};
+use crate::render_settings::RenderSettings;
+use crate::scene::Scene;
+use crate::vector::Vector;
+
+// Bidirectional path tracing after Veach's thesis and pbrt. Paths are traced in RGB and media are ignored,
+// medium boundaries are passed straight through.
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind
+{
+    Camera,
+    Light,
+    Surface
+}
+
+#[derive(Clone, Copy)]
+struct Vertex<'a>
+{
+    kind : VertexKind,
+    point : Vector,
+    // Geometric normal, the viewing direction for the camera
+    normal : Vector,
+    object : Option<&'a Object>,
+    // Where a ray hit the object, None for vertices sampled on a light or the camera
+    hit : Option<Hit>,
+    // Throughput of the subpath up to and including this vertex
+    beta : Color,
+    // Scatters specularly, so it cannot be connected to
+    delta : bool,
+    // Area densities of sampling this vertex from the previous and from the next vertex of its subpath
+    pdf_fwd : f64,
+    pdf_rev : f64
+}
+
+// Light tracing contributions, accumulated by all threads into the pixels the camera sees them through
+pub struct SplatBuffer
+{
+    width : usize,
+    values : Vec<AtomicU64>
+}
+
+impl SplatBuffer
+{
+    pub fn new(img_width : u32, img_height : u32) -> Self
+    {
+        let count = 3 * img_width as usize * img_height as usize;
+
+        SplatBuffer
+        {
+            width : img_width as usize,
+            values : (0..count).map(|_| AtomicU64::new(0.0_f64.to_bits())).collect()
+        }
+    }
+
+    fn add_value(value : &AtomicU64, amount : f64)
+    {
+        let mut current = value.load(Ordering::Relaxed);
+
+        loop
+        {
+            let updated = (f64::from_bits(current) + amount).to_bits();
+
+            match value.compare_exchange_weak(current, updated, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(actual) => current = actual
+            }
+        }
+    }
+
+    fn add(&self, x : usize, y : usize, color : Color)
+    {
+        let index = 3 * (y * self.width + x);
+
+        SplatBuffer::add_value(&self.values[index], color.r);
+        SplatBuffer::add_value(&self.values[index + 1], color.g);
+        SplatBuffer::add_value(&self.values[index + 2], color.b);
+    }
+
+    pub fn get(&self, x : usize, y : usize) -> Color
+    {
+        let index = 3 * (y * self.width + x);
+        let value = |i : usize| f64::from_bits(self.values[i].load(Ordering::Relaxed));
+
+        return Color::new_rgb(value(index), value(index + 1), value(index + 2));
+    }
+}
+
+fn direction(from : Vector, to : Vector) -> Vector
+{
+    let mut dir = to - from;
+
+    return *dir.normalize();
+}
+
+// Converts a solid angle density at from into an area density at to
+fn convert_density(pdf : f64, from : &Vertex, to : &Vertex) -> f64
+{
+    let w = to.point - from.point;
+    let sq_distance = w.dot_product(w);
+
+    if sq_distance <= 0.0
+    { return 0.0; }
+
+    let mut result = pdf / sq_distance;
+
+    if to.kind != VertexKind::Camera
+    { result = result * to.normal.dot_product(w).abs() / sq_distance.sqrt(); }
+
+    return result;
+}
+
+// Area density of the vertex as the start of a light subpath, including the choice of the light
+fn light_origin_pdf(scene : &Scene, vertex : &Vertex) -> f64
+{
+    return match vertex.object
+    {
+        Some(object) => light_area_pdf(scene, object),
+        None => 0.0
+    };
+}
+
+// Area density of next being sampled by emission from the light at vertex
+fn light_pdf_to(vertex : &Vertex, next : &Vertex) -> f64
+{
+    let pdf = match vertex.object
+    {
+        Some(object) => emission_pdf(object.body.as_ref(), vertex.normal, direction(vertex.point, next.point)),
+        None => 0.0
+    };
+
+    return convert_density(pdf, vertex, next);
+}
+
+// Diffuse reflection only happens between directions on the same side of the surface
+fn same_side(vertex : &Vertex, to_prev : Vector, to_next : Vector) -> bool
+{ return vertex.normal.dot_product(to_prev) * vertex.normal.dot_product(to_next) > 0.0; }
+
+fn is_diffuse(vertex : &Vertex) -> bool
+{
+    return match vertex.object
+    {
+        Some(object) => object.body.get_material().mat_type == MatType::Diffuse,
+        None => false
+    };
+}
+
+// BSDF of a surface vertex, or whether a light vertex emits towards next
+fn scatter(vertex : &Vertex, prev : Option<&Vertex>, next : Vector) -> Color
+{
+    let black = Color::new_rgb(0.0, 0.0, 0.0);
+    let to_next = direction(vertex.point, next);
+
+    match (vertex.kind, prev, vertex.object)
+    {
+        (VertexKind::Light, _, Some(object)) =>
+        {
+            if emitted(object.body.as_ref(), vertex.normal, to_next * -1.0) > 0.0
+            { return Color::new_rgb(1.0, 1.0, 1.0); }
+
+            return black;
+        }
+
+        (VertexKind::Surface, Some(p), Some(object)) =>
+        {
+            let hit = match vertex.hit
+            {
+                Some(hit) => hit,
+                None => return black
+            };
+
+            if ! is_diffuse(vertex) || ! same_side(vertex, direction(vertex.point, p.point), to_next)
+            { return black; }
+
+            return object.body.albedo(vertex.point, &hit) * FRAC_1_PI;
+        }
+
+        _ => return black
+    }
+}
+
+// Area density with which vertex samples next, having been reached from prev
+fn vertex_pdf(scene : &Scene, vertex : &Vertex, prev : Option<&Vertex>, next : &Vertex) -> f64
+{
+    let to_next = direction(vertex.point, next.point);
+
+    let pdf = match (vertex.kind, prev)
+    {
+        (VertexKind::Light, _) => return light_pdf_to(vertex, next),
+
+        (VertexKind::Camera, _) => scene.camera.pdf_dir(to_next),
+
+        (VertexKind::Surface, Some(p)) =>
+        {
+            if ! is_diffuse(vertex) || ! same_side(vertex, direction(vertex.point, p.point), to_next)
+            { return 0.0; }
+
+            vertex.normal.dot_product(to_next).abs() * FRAC_1_PI
+        }
+
+        (VertexKind::Surface, None) => return 0.0
+    };
+
+    return convert_density(pdf, vertex, next);
+}
+
+fn visible(scene : &Scene, from : Vector, to : Vector, rays : &mut u64) -> bool
+{
+    let w = to - from;
+    let distance = w.dot_product(w).sqrt();
+    let ray = Ray { origin : from, dest : w * (1.0 / distance) };
+
+    return scene.transmittance(ray, distance * (1.0 - 1e-6), None, None, rays).is_some();
+}
+
+// Extends the subpath by sampling BSDFs, pdf_dir is the solid angle density of the ray leaving its last vertex.
+// Returns the number of rays cast
+fn random_walk<'a>(scene : &'a Scene, settings : &RenderSettings, mut ray : Ray, mut beta : Color, pdf_dir : f64,
+    max_vertices : usize, path : &mut Vec<Vertex<'a>>) -> u64
+{
+    let mut pdf_fwd = pdf_dir;
+    let mut rays = 0;
+
+    while path.len() < max_vertices
+    {
+        let (object, hit_point, hit) = match scene.next_surface(ray, &mut rays)
+        {
+            Some(surface) => surface,
+            None => break
+        };
+
+        let prev = path.len() - 1;
+        let mut vertex = Vertex
+        {
+            kind : VertexKind::Surface,
+            point : hit_point,
+            normal : object.body.normal(hit_point, &hit),
+            object : Some(object),
+            hit : Some(hit),
+            beta,
+            delta : false,
+            pdf_fwd : 0.0,
+            pdf_rev : 0.0
+        };
+
+        vertex.pdf_fwd = convert_density(pdf_fwd, &path[prev], &vertex);
+        path.push(vertex);
+
+        if path.len() >= max_vertices
+        { break; }
+
+        let material = object.body.get_material();
+        let incoming = ray.dest;
+        let pdf_rev;
+
+        ray.origin = hit_point;
+
+        match material.mat_type
+        {
+            MatType::Diffuse =>
+            {
+                let facing_normal =
+                    if vertex.normal.dot_product(incoming) > 0.0
+                    { vertex.normal * -1.0 }
+                    else
+                    { vertex.normal };
+
+                diffuse_brdf(facing_normal, &mut ray);
+                beta = beta.mul_by_color(object.body.albedo(hit_point, &hit));
+                pdf_fwd = facing_normal.dot_product(ray.dest).max(0.0) * FRAC_1_PI;
+                pdf_rev = (-facing_normal.dot_product(incoming)).max(0.0) * FRAC_1_PI;
+            }
+
+            // Specular densities are left at 0, connections never end on them
+            MatType::Specular =>
+            {
+                specular_brdf(vertex.normal, &mut ray);
+                path[prev + 1].delta = true;
+                pdf_fwd = 0.0;
+                pdf_rev = 0.0;
+            }
+
+            MatType::Refractive =>
+            {
+                refractive_brdf(vertex.normal, &mut ray, material.ior.nominal());
+                path[prev + 1].delta = true;
+                pdf_fwd = 0.0;
+                pdf_rev = 0.0;
+            }
+
+            // Skipped by Scene::next_surface
+            MatType::Interface => break
+        }
+
+        path[prev].pdf_rev = convert_density(pdf_rev, &path[prev + 1], &path[prev]);
+
+        // Russian roulette based on path throughput, the first vertex is the camera or light
+        if path.len() > settings.min_bounces as usize
+        {
+            let survival_prob = beta.max_component().min(0.95);
+
+            if survival_prob <= 0.0 || random::gen_num() >= survival_prob
+            { break; }
+
+            beta = beta * (1.0 / survival_prob);
+        }
+    }
+
+    return rays;
+}
+
+fn camera_subpath<'a>(scene : &'a Scene, settings : &RenderSettings, ray : Ray, max_vertices : usize, rays : &mut u64) -> Vec<Vertex<'a>>
+{
+    let mut path = vec![Vertex
+    {
+        kind : VertexKind::Camera,
+        point : ray.origin,
+        normal : scene.camera.forward(),
+        object : None,
+        hit : None,
+        beta : Color::new_rgb(1.0, 1.0, 1.0),
+        delta : false,
+        pdf_fwd : 1.0,
+        pdf_rev : 0.0
+    }];
+
+    // Importance, cosine and densities of the camera ray cancel out
+    *rays += random_walk(scene, settings, ray, Color::new_rgb(1.0, 1.0, 1.0), scene.camera.pdf_dir(ray.dest), max_vertices, &mut path);
+
+    return path;
+}
+
+fn light_subpath<'a>(scene : &'a Scene, settings : &RenderSettings, max_vertices : usize, rays : &mut u64) -> Vec<Vertex<'a>>
+{
+    let mut path = Vec::new();
+
+    let sample = match sample_emission(scene)
+    {
+        Some(sample) => sample,
+        None => return path
+    };
+
+    let emission = sample.object.body.get_material().emission;
+
+    path.push(Vertex
+    {
+        kind : VertexKind::Light,
+        point : sample.ray.origin,
+        normal : sample.normal,
+        object : Some(sample.object),
+        hit : None,
+        beta : Color::new_rgb(1.0, 1.0, 1.0) * (emission / sample.pdf_pos),
+        delta : false,
+        pdf_fwd : sample.pdf_pos,
+        pdf_rev : 0.0
+    });
+
+    let beta = path[0].beta * (sample.normal.dot_product(sample.ray.dest).abs() / sample.pdf_dir);
+
+    *rays += random_walk(scene, settings, sample.ray, beta, sample.pdf_dir, max_vertices, &mut path);
+
+    return path;
+}
+
+fn remap_zero(pdf : f64) -> f64
+{
+    if pdf != 0.0
+    { return pdf; }
+
+    return 1.0;
+}
+
+// Balance heuristic weight of the strategy using s light and t camera vertices against all others producing the same path,
+// sampled replaces the endpoint that was sampled anew by the connection
+fn mis_weight(scene : &Scene, light_path : &[Vertex], camera_path : &[Vertex], sampled : Option<Vertex>, s : usize, t : usize)
+    -> f64
+{
+    if s + t == 2
+    { return 1.0; }
+
+    let mut light : Vec<Vertex> = light_path[..s].to_vec();
+    let mut camera : Vec<Vertex> = camera_path[..t].to_vec();
+
+    if let Some(vertex) = sampled
+    {
+        if t == 1
+        { camera[0] = vertex; }
+        else if s == 1
+        { light[0] = vertex; }
+    }
+
+    let pt = camera[t - 1];
+    let pt_minus = if t > 1 { Some(camera[t - 2]) } else { None };
+    let qs = if s > 0 { Some(light[s - 1]) } else { None };
+    let qs_minus = if s > 1 { Some(light[s - 2]) } else { None };
+
+    // Reverse densities at the connection, as if the path had been sampled from the other side
+    camera[t - 1].pdf_rev = match &qs
+    {
+        Some(q) => vertex_pdf(scene, q, qs_minus.as_ref(), &pt),
+        None => light_origin_pdf(scene, &pt)
+    };
+
+    // Emitters that cannot be sampled are only found by hitting them
+    if s == 0 && camera[t - 1].pdf_rev <= 0.0
+    { return 1.0; }
+
+    if let Some(p) = &pt_minus
+    {
+        camera[t - 2].pdf_rev = match &qs
+        {
+            Some(q) => vertex_pdf(scene, &pt, Some(q), p),
+            None => light_pdf_to(&pt, p)
+        };
+    }
+
+    if let Some(q) = &qs
+    { light[s - 1].pdf_rev = vertex_pdf(scene, &pt, pt_minus.as_ref(), q); }
+
+    if let (Some(q), Some(q_minus)) = (&qs, &qs_minus)
+    { light[s - 2].pdf_rev = vertex_pdf(scene, q, Some(&pt), q_minus); }
+
+    // The connected endpoints are never treated as specular
+    camera[t - 1].delta = false;
+
+    if s > 0
+    { light[s - 1].delta = false; }
+
+    let mut sum_ratios = 0.0;
+    let mut ratio = 1.0;
+
+    for i in (1..t).rev()
+    {
+        ratio = ratio * remap_zero(camera[i].pdf_rev) / remap_zero(camera[i].pdf_fwd);
+
+        if ! camera[i].delta && ! camera[i - 1].delta
+        { sum_ratios += ratio; }
+    }
+
+    ratio = 1.0;
+
+    for i in (0..s).rev()
+    {
+        ratio = ratio * remap_zero(light[i].pdf_rev) / remap_zero(light[i].pdf_fwd);
+
+        let prev_delta = i > 0 && light[i - 1].delta;
+
+        if ! light[i].delta && ! prev_delta
+        { sum_ratios += ratio; }
+    }
+
+    return 1.0 / (1.0 + sum_ratios);
+}
+
+// Contribution of the path made of the first s light and t camera vertices, with the pixel to splat it to for t = 1
+fn connect(scene : &Scene, light : &[Vertex], camera : &[Vertex], s : usize, t : usize, rays : &mut u64)
+    -> Option<(Color, Option<(usize, usize)>)>
+{
+    let pt = camera[t - 1];
+    let mut sampled : Option<Vertex> = None;
+    let mut pixel : Option<(usize, usize)> = None;
+    let contribution : Color;
+
+    if s == 0
+    {
+        // The camera subpath hit an emitter by itself
+        let object = pt.object?;
+        let dir = direction(camera[t - 2].point, pt.point);
+
+        contribution = pt.beta * emitted(object.body.as_ref(), pt.normal, dir);
+    }
+    else if t == 1
+    {
+        // Light tracing, the light subpath is connected straight to the camera
+        let qs = light[s - 1];
+
+        if qs.delta
+        { return None; }
+
+        let camera_pos = scene.camera.position();
+        pixel = Some(scene.camera.project(qs.point)?);
+
+        let w = camera_pos - qs.point;
+        let sq_distance = w.dot_product(w);
+        let to_camera = w * (1.0 / sq_distance.sqrt());
+        let cos_camera = (to_camera * -1.0).dot_product(scene.camera.forward());
+
+        let importance = scene.camera.importance(to_camera * -1.0) * cos_camera / sq_distance;
+
+        let prev = if s > 1 { Some(&light[s - 2]) } else { None };
+        contribution = qs.beta.mul_by_color(scatter(&qs, prev, camera_pos)) * (importance * qs.normal.dot_product(to_camera).abs());
+
+        if contribution.is_black() || ! visible(scene, qs.point, camera_pos, rays)
+        { return None; }
+
+        sampled = Some(Vertex { beta : Color::new_rgb(importance, importance, importance), ..camera[0] });
+    }
+    else if s == 1
+    {
+        // Next event estimation towards a newly sampled point on a light
+        if pt.delta || scene.lights.is_empty()
+        { return None; }
+
+        let light_count = scene.lights.len();
+        let light_num = ((random::gen_num() * light_count as f64) as usize).min(light_count - 1);
+        let light_object = &scene.objects[scene.lights[light_num]];
+
+        let (sample, pdf) = light_object.body.sample_from(pt.point, random::gen_num(), random::gen_num())?;
+        let dir = direction(pt.point, sample.point);
+        let radiance = emitted(light_object.body.as_ref(), sample.normal, dir) * light_count as f64 / pdf;
+
+        let mut vertex = Vertex
+        {
+            kind : VertexKind::Light,
+            point : sample.point,
+            normal : sample.normal,
+            object : Some(light_object),
+            hit : None,
+            beta : Color::new_rgb(radiance, radiance, radiance),
+            delta : false,
+            pdf_fwd : 0.0,
+            pdf_rev : 0.0
+        };
+
+        vertex.pdf_fwd = light_origin_pdf(scene, &vertex);
+
+        contribution = pt.beta.mul_by_color(scatter(&pt, Some(&camera[t - 2]), sample.point)).mul_by_color(vertex.beta)
+            * pt.normal.dot_product(dir).abs();
+
+        if contribution.is_black() || ! visible(scene, pt.point, sample.point, rays)
+        { return None; }
+
+        sampled = Some(vertex);
+    }
+    else
+    {
+        // Both subpaths end on diffuse surfaces that are joined by a shadow ray
+        let qs = light[s - 1];
+
+        if qs.delta || pt.delta
+        { return None; }
+
+        let w = pt.point - qs.point;
+        let sq_distance = w.dot_product(w);
+        let dir = w * (1.0 / sq_distance.sqrt());
+        let geometry = qs.normal.dot_product(dir).abs() * pt.normal.dot_product(dir).abs() / sq_distance;
+
+        contribution = qs.beta
+            .mul_by_color(scatter(&qs, Some(&light[s - 2]), pt.point))
+            .mul_by_color(scatter(&pt, Some(&camera[t - 2]), qs.point))
+            .mul_by_color(pt.beta) * geometry;
+
+        if contribution.is_black() || ! visible(scene, qs.point, pt.point, rays)
+        { return None; }
+    }
+
+    if contribution.is_black()
+    { return None; }
+
+    return Some((contribution * mis_weight(scene, light, camera, sampled, s, t), pixel));
+}
+
+// Radiance along a camera ray from all connections of its subpath to a light subpath, light tracing contributions go to splats.
+// The rays cast are added to rays
+pub fn trace_bidirectional(scene : &Scene, settings : &RenderSettings, ray : Ray, splats : &SplatBuffer, rays : &mut u64) -> Color
+{
+    let max_bounces = settings.max_bounces as usize;
+    let camera = camera_subpath(scene, settings, ray, max_bounces + 2, rays);
+    let light = light_subpath(scene, settings, max_bounces + 1, rays);
+
+    let mut color = Color::new_rgb(0.0, 0.0, 0.0);
+
+    for t in 1..=camera.len()
+    {
+        for s in 0..=light.len()
+        {
+            // Each strategy needs a camera and a light end, s + t - 2 is the number of bounces. Lights seen directly
+            // are only counted by camera paths hitting them, connecting their vertices to the camera would count them twice
+            if s + t < 2 || s + t - 2 > max_bounces || (s == 1 && t == 1)
+            { continue; }
+
+            if let Some((contribution, pixel)) = connect(scene, &light, &camera, s, t, rays)
+            {
+                match pixel
+                {
+                    Some((x, y)) => splats.add(x, y, contribution),
+                    None => color = color + contribution
+                }
+            }
+        }
+    }
+
+    return color;
+}
@@ -0,0 +1,152 @@
+use crate::aabb::Aabb;
+use crate::object::Hit;
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+const MAX_LEAF_SIZE : usize = 4;
+
+// Node is a leaf if count > 0, otherwise its children are stored at first and first + 1
+#[derive(Clone)]
+struct BvhNode
+{
+    bounds : Aabb,
+    first : usize,
+    count : usize
+}
+
+// Bounding volume hierarchy over primitives given by their bounds
+#[derive(Clone)]
+pub struct Bvh
+{
+    nodes : Vec<BvhNode>,
+    // Primitive indices ordered so that every leaf covers a contiguous range
+    indices : Vec<usize>
+}
+
+fn axis_value(vec : Vector, axis : usize) -> f64
+{
+    match axis
+    {
+        0 => vec.x,
+        1 => vec.y,
+        _ => vec.z
+    }
+}
+
+impl Bvh
+{
+    pub fn build(bounds : &[Aabb]) -> Self
+    {
+        let mut bvh = Bvh
+        {
+            nodes : Vec::with_capacity(2 * bounds.len()),
+            indices : (0..bounds.len()).collect()
+        };
+
+        if ! bounds.is_empty()
+        {
+            bvh.nodes.push(BvhNode { bounds : bounds[0], first : 0, count : bounds.len() });
+            bvh.subdivide(0, bounds);
+        }
+
+        return bvh;
+    }
+
+    // Splits a node at the median centroid along the longest axis of its centroids' bounds
+    fn subdivide(&mut self, node_index : usize, bounds : &[Aabb])
+    {
+        let first = self.nodes[node_index].first;
+        let count = self.nodes[node_index].count;
+        let range = &mut self.indices[first..first + count];
+
+        let node_bounds = range.iter().skip(1).fold(bounds[range[0]], |acc, i| acc.union(&bounds[*i]));
+        self.nodes[node_index].bounds = node_bounds;
+
+        if count <= MAX_LEAF_SIZE
+        { return; }
+
+        let centroids : Vec<Vector> = range.iter().map(|i| bounds[*i].centroid()).collect();
+        let centroid_bounds = Aabb::from_points(&centroids);
+        let extent = centroid_bounds.max - centroid_bounds.min;
+
+        let axis =
+            if extent.x >= extent.y && extent.x >= extent.z { 0 }
+            else if extent.y >= extent.z { 1 }
+            else { 2 };
+
+        let half = count / 2;
+
+        range.select_nth_unstable_by(half, |a, b|
+        {
+            let value_a = axis_value(bounds[*a].centroid(), axis);
+            let value_b = axis_value(bounds[*b].centroid(), axis);
+
+            value_a.total_cmp(&value_b)
+        });
+
+        let left = self.nodes.len();
+
+        self.nodes.push(BvhNode { bounds : node_bounds, first, count : half });
+        self.nodes.push(BvhNode { bounds : node_bounds, first : first + half, count : count - half });
+
+        self.nodes[node_index].first = left;
+        self.nodes[node_index].count = 0;
+
+        self.subdivide(left, bounds);
+        self.subdivide(left + 1, bounds);
+    }
+
+    // Closest hit returned by intersect_primitive over all primitives the ray may hit
+    pub fn intersect<F>(&self, ray : Ray, intersect_primitive : F) -> Option<Hit>
+        where F : FnMut(usize) -> Option<Hit>
+    { return self.traverse(ray, intersect_primitive).0; }
+
+    // Nodes whose bounding boxes intersect tests, for the node visit heatmap
+    pub fn node_visits<F>(&self, ray : Ray, intersect_primitive : F) -> u64
+        where F : FnMut(usize) -> Option<Hit>
+    { return self.traverse(ray, intersect_primitive).1; }
+
+    // Closest hit and the number of nodes tested to find it, inlined so intersect doesn't pay for the count
+    #[inline(always)]
+    fn traverse<F>(&self, ray : Ray, mut intersect_primitive : F) -> (Option<Hit>, u64)
+        where F : FnMut(usize) -> Option<Hit>
+    {
+        let mut closest : Option<Hit> = None;
+        let mut stack = Vec::with_capacity(64);
+        let mut visits = 0;
+
+        if ! self.nodes.is_empty()
+        { stack.push(0); }
+
+        while let Some(node_index) = stack.pop()
+        {
+            let node : &BvhNode = &self.nodes[node_index];
+            visits += 1;
+
+            if ! node.bounds.hit(ray, closest.map_or(f64::INFINITY, |hit| hit.distance))
+            { continue; }
+
+            if node.count > 0
+            {
+                for i in &self.indices[node.first..node.first + node.count]
+                {
+                    if let Some(hit) = intersect_primitive(*i)
+                    {
+                        if closest.is_none_or(|best| hit.distance < best.distance)
+                        { closest = Some(hit); }
+                    }
+                }
+            }
+            else
+            {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+            }
+        }
+
+        return (closest, visits);
+    }
+
+    pub fn bounds(&self) -> Option<Aabb>
+    { return self.nodes.first().map(|node| node.bounds); }
+}
@@ -2,18 +2,24 @@ use crate::Vector;
 use crate::ray::*;
 
 // Camera based on Peter Shirley's Ray Tracing In One Weekend book
+/// Pinhole camera the scene is seen through, which also sets the size of the rendered image.
 pub struct Camera
 {
     origin : Vector,
     horizontal : Vector,
     vertical : Vector,
     lower_left_corner : Vector,
+    /// Width of the image in pixels.
     pub img_width : u32,
+    /// Height of the image in pixels.
     pub img_height : u32
 }
 
 impl Camera
 {
+    /// Pinhole camera at `look_from` looking towards `look_at`; note that the point looked at comes
+    /// first. `vup` gives the upwards direction of the image, `vertical_fov` is in degrees and the
+    /// image is `width` by `height` pixels.
     pub fn new(look_at : Vector, look_from : Vector, vup : Vector, vertical_fov : f64, width : u32, height : u32) -> Self
     {
 
@@ -45,10 +51,10 @@ impl Camera
         }
     }
     
-    pub fn get_ray(&self, x : f64, y : f64) -> Ray
+    pub(crate) fn get_ray(&self, x : f64, y : f64) -> Ray
     {
-        let u = (x as f64 + 0.5) / self.img_width as f64;
-        let v = (y as f64 + 0.5) / self.img_height as f64;
+        let u = (x + 0.5) / self.img_width as f64;
+        let v = (y + 0.5) / self.img_height as f64;
 
         let mut new_ray = crate::ray::Ray::new();
 
@@ -60,4 +66,63 @@ impl Camera
 
         return new_ray;
     }
+
+    pub(crate) fn position(&self) -> Vector
+    { return self.origin; }
+
+    // Viewing direction, the image plane lies at distance 1 along it
+    pub(crate) fn forward(&self) -> Vector
+    { return self.lower_left_corner + self.horizontal * 0.5 + self.vertical * 0.5 - self.origin; }
+
+    // Height of a pixel on the image plane, i.e. at distance 1
+    pub(crate) fn pixel_footprint(&self) -> f64
+    { return self.vertical.dot_product(self.vertical).sqrt() / self.img_height as f64; }
+
+    fn image_plane_area(&self) -> f64
+    { return self.horizontal.dot_product(self.horizontal).sqrt() * self.vertical.dot_product(self.vertical).sqrt(); }
+
+    // Pixel whose rays from get_ray pass through the point, None if it is behind the camera or outside of the image
+    pub(crate) fn project(&self, point : Vector) -> Option<(usize, usize)>
+    {
+        let dir = point - self.origin;
+        let cos_theta = dir.dot_product(self.forward());
+
+        if cos_theta <= 0.0
+        { return None; }
+
+        let on_plane = self.origin + dir * (1.0 / cos_theta) - self.lower_left_corner;
+        let u = on_plane.dot_product(self.horizontal) / self.horizontal.dot_product(self.horizontal);
+        let v = on_plane.dot_product(self.vertical) / self.vertical.dot_product(self.vertical);
+
+        // Inverse of the offsets applied by get_ray
+        let x = (u * self.img_width as f64 - 0.5).floor();
+        let y = (v * self.img_height as f64 - 0.5).floor();
+
+        if x < 0.0 || y < 0.0 || x >= self.img_width as f64 || y >= self.img_height as f64
+        { return None; }
+
+        return Some((x as usize, y as usize));
+    }
+
+    // Importance emitted along the unit direction dir, normalized to integrate to 1 over the image plane
+    pub(crate) fn importance(&self, dir : Vector) -> f64
+    {
+        let cos_theta = dir.dot_product(self.forward());
+
+        if cos_theta <= 0.0
+        { return 0.0; }
+
+        return 1.0 / (self.image_plane_area() * cos_theta.powi(4));
+    }
+
+    // Solid angle density of camera rays along the unit direction dir
+    pub(crate) fn pdf_dir(&self, dir : Vector) -> f64
+    {
+        let cos_theta = dir.dot_product(self.forward());
+
+        if cos_theta <= 0.0
+        { return 0.0; }
+
+        return 1.0 / (self.image_plane_area() * cos_theta.powi(3));
+    }
 }
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Stops a render early. Clones share their state, so a clone can be kept by a signal handler or another
+/// thread while the render checks the original. The render finishes the pass it is on and returns the
+/// image of the passes done so far.
+#[derive(Clone, Default, Debug)]
+pub struct CancelToken
+{
+    cancelled : Arc<AtomicBool>
+}
+
+impl CancelToken
+{
+    /// Token that has not been cancelled.
+    pub fn new() -> Self
+    { CancelToken::default() }
+
+    /// Asks the render using this token or one of its clones to stop.
+    pub fn cancel(&self)
+    { self.cancelled.store(true, Ordering::Relaxed); }
+
+    /// Whether [`cancel`](CancelToken::cancel) was called since the token was made or reset.
+    pub fn is_cancelled(&self) -> bool
+    { return self.cancelled.load(Ordering::Relaxed); }
+
+    /// Clears the cancellation so the token can be used for another render.
+    pub fn reset(&self)
+    { self.cancelled.store(false, Ordering::Relaxed); }
+}
@@ -0,0 +1,194 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use path_tracer::{CropWindow, Filter, Image, Integrator, Sampler, OUTPUT_EXTENSIONS};
+
+// Command line options that override values loaded from the scene file
+#[derive(Clone, Default)]
+pub struct CliArgs
+{
+    // Scene rendered right away instead of choosing one from the menu
+    pub scene : Option<PathBuf>,
+    pub spp : Option<u32>,
+    // Seconds a render of --scene may take, as many samples per pixel as fit are rendered
+    pub time_limit : Option<f64>,
+    // Images written by a render of --scene, replacing the outputs of the scene file
+    pub outputs : Vec<PathBuf>,
+    // Folder the menu lists scenes from and the one its renders are written to
+    pub scenes_dir : Option<PathBuf>,
+    pub renders_dir : Option<PathBuf>,
+    // Folders searched for files referenced inside scenes when they are not next to the scene
+    pub asset_paths : Vec<PathBuf>,
+    // Scene file rewritten into the format of the second file's extension instead of rendering
+    pub convert : Option<(PathBuf, PathBuf)>,
+
+    pub min_bounces : Option<u32>,
+    pub max_bounces : Option<u32>,
+    // Forces spectral rendering
+    pub spectral : bool,
+    pub integrator : Option<Integrator>,
+    pub sampler : Option<Sampler>,
+    pub filter : Option<Filter>,
+    pub seed : Option<u64>,
+    pub threads : Option<usize>,
+    pub crop : Option<CropWindow>,
+    pub ao_radius : Option<f64>
+}
+
+pub const USAGE : &str = "Usage: path_tracer [--scene FILE [--spp N | --time-limit SECONDS] [--output FILE]...] [--scenes-dir DIR] [--renders-dir DIR]\n\
+    \x20   [--asset-path DIR]... [--min-bounces N] [--max-bounces N] [--spectral] [--integrator NAME] [--ao-radius R]\n\
+    \x20   [--sampler NAME] [--filter NAME] [--seed N] [--threads N] [--crop X,Y,WIDTH,HEIGHT]\n\
+    \x20      path_tracer convert INPUT OUTPUT\n\
+    Scenes are read from .json, .toml, .yaml and .yml files, convert rewrites one into the format of OUTPUT's extension\n\
+    glTF (.gltf, .glb), PLY and STL models can be included in scenes and converted into scene files\n\
+    Paths are used as given, relative ones start in the working directory\n\
+    Images are written as .ppm, .png or .jpg depending on the extension of --output\n\
+    Ctrl-C finishes the current pass and writes the image rendered so far, a second Ctrl-C quits right away\n\
+    Integrators: path, bdpt, normals, albedo, depth, ao, wireframe, path_length, bvh_visits\n\
+    Samplers: independent, stratified   Filters: box, tent, gaussian";
+
+fn parse_value<T : FromStr>(flag : &str, value : Option<String>) -> Result<T, String>
+{
+    let value = match value
+    {
+        Some(val) => val,
+        None => return Err(format!("Missing value for {}", flag))
+    };
+
+    match value.parse::<T>()
+    {
+        Ok(parsed) => Ok(parsed),
+        Err(..) => Err(format!("Invalid value '{}' for {}", value, flag))
+    }
+}
+
+fn parse_positive(flag : &str, value : Option<String>) -> Result<f64, String>
+{
+    let value = match value
+    {
+        Some(val) => val,
+        None => return Err(format!("Missing value for {}", flag))
+    };
+
+    match value.parse::<f64>()
+    {
+        Ok(parsed) if parsed > 0.0 => Ok(parsed),
+        _ => Err(format!("Invalid value '{}' for {}", value, flag))
+    }
+}
+
+fn parse_path(flag : &str, value : Option<String>) -> Result<PathBuf, String>
+{
+    match value
+    {
+        Some(val) => Ok(PathBuf::from(val)),
+        None => Err(format!("Missing value for {}", flag))
+    }
+}
+
+fn parse_named<T>(flag : &str, value : Option<String>, kind : &str, from_name : fn(&str) -> Option<T>) -> Result<T, String>
+{
+    let value = match value
+    {
+        Some(val) => val,
+        None => return Err(format!("Missing value for {}", flag))
+    };
+
+    match from_name(&value)
+    {
+        Some(parsed) => Ok(parsed),
+        None => Err(format!("Unknown {} '{}'", kind, value))
+    }
+}
+
+fn parse_output(flag : &str, value : Option<String>) -> Result<PathBuf, String>
+{
+    let path = parse_path(flag, value)?;
+
+    if ! Image::supports_output(&path)
+    { return Err(format!("{} must end in .{}", flag, OUTPUT_EXTENSIONS.join(", ."))); }
+
+    return Ok(path);
+}
+
+// Window given as x,y,width,height in pixels from the top left corner
+fn parse_crop(flag : &str, value : Option<String>) -> Result<CropWindow, String>
+{
+    let value = match value
+    {
+        Some(val) => val,
+        None => return Err(format!("Missing value for {}", flag))
+    };
+
+    let numbers : Vec<u32> = match value.split(',').map(|number| number.trim().parse::<u32>()).collect()
+    {
+        Ok(numbers) => numbers,
+        Err(..) => return Err(format!("Invalid value '{}' for {}", value, flag))
+    };
+
+    match numbers[..]
+    {
+        [x, y, width, height] if width > 0 && height > 0 => Ok(CropWindow { x, y, width, height }),
+        _ => Err(format!("Invalid value '{}' for {}", value, flag))
+    }
+}
+
+pub fn parse_args<I : Iterator<Item = String>>(args : I) -> Result<CliArgs, String>
+{
+    let mut cli_args = CliArgs::default();
+    let mut args = args.peekable();
+
+    if args.next_if(|arg| arg == "convert").is_some()
+    {
+        let input = parse_path("convert", args.next())?;
+        let output = parse_path("convert", args.next())?;
+
+        if let Some(arg) = args.next()
+        { return Err(format!("Unknown argument '{}'", arg)); }
+
+        cli_args.convert = Some((input, output));
+
+        return Ok(cli_args);
+    }
+
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "--scene" => cli_args.scene = Some(parse_path(&arg, args.next())?),
+            "--spp" => cli_args.spp = Some(parse_value(&arg, args.next())?),
+            "--time-limit" => cli_args.time_limit = Some(parse_positive(&arg, args.next())?),
+            "--output" => cli_args.outputs.push(parse_output(&arg, args.next())?),
+            "--scenes-dir" => cli_args.scenes_dir = Some(parse_path(&arg, args.next())?),
+            "--renders-dir" => cli_args.renders_dir = Some(parse_path(&arg, args.next())?),
+            "--asset-path" => cli_args.asset_paths.push(parse_path(&arg, args.next())?),
+            "--min-bounces" => cli_args.min_bounces = Some(parse_value(&arg, args.next())?),
+            "--max-bounces" => cli_args.max_bounces = Some(parse_value(&arg, args.next())?),
+            "--spectral" => cli_args.spectral = true,
+            "--integrator" => cli_args.integrator = Some(parse_named(&arg, args.next(), "integrator", Integrator::from_name)?),
+            "--ao-radius" => cli_args.ao_radius = Some(parse_positive(&arg, args.next())?),
+            "--sampler" => cli_args.sampler = Some(parse_named(&arg, args.next(), "sampler", Sampler::from_name)?),
+            "--filter" => cli_args.filter = Some(parse_named(&arg, args.next(), "filter", Filter::from_name)?),
+            "--seed" => cli_args.seed = Some(parse_value(&arg, args.next())?),
+            "--threads" => cli_args.threads = Some(parse_value(&arg, args.next())?),
+            "--crop" => cli_args.crop = Some(parse_crop(&arg, args.next())?),
+            _ => return Err(format!("Unknown argument '{}'", arg))
+        }
+    }
+
+    if let (Some(min), Some(max)) = (cli_args.min_bounces, cli_args.max_bounces)
+    {
+        if min > max
+        { return Err("--min-bounces must not be greater than --max-bounces".to_string()); }
+    }
+
+    if cli_args.scene.is_none() && (cli_args.spp.is_some() || cli_args.time_limit.is_some() || ! cli_args.outputs.is_empty())
+    { return Err("--spp, --time-limit and --output are only used together with --scene".to_string()); }
+
+    if cli_args.spectral && cli_args.integrator == Some(Integrator::Bidirectional)
+    { return Err("--spectral can't be used with --integrator bdpt, which renders in RGB".to_string()); }
+
+    if cli_args.spp.is_some() && cli_args.time_limit.is_some()
+    { return Err("--spp and --time-limit can't be used together".to_string()); }
+
+    return Ok(cli_args);
+}
@@ -1,16 +1,20 @@
 use std::ops::{Add, Mul};
 
+/// Linear RGB radiance or reflectance, renders are not clamped to 1.
 #[derive(Copy, Clone)]
 pub struct Color
 {
+    /// Red channel.
     pub r : f64,
+    /// Green channel.
     pub g : f64,
+    /// Blue channel.
     pub b : f64
 }
 
 impl Color
 {
-    // Converts hexadecimal values to RGB values
+    /// Colour of a hexadecimal `0xRRGGBB` value, with each channel scaled to [0, 1].
     pub fn new_hex(hex_code : i32) -> Self
     {
         Color
@@ -21,6 +25,7 @@ impl Color
         }
     }
 
+    /// Colour of the given channels.
     pub fn new_rgb(_r : f64, _g : f64 , _b : f64) -> Self
     {
         Color
@@ -31,7 +36,7 @@ impl Color
         }
     }
 
-    pub fn mul_by_color(&self, vec2 : Color) -> Color
+    pub(crate) fn mul_by_color(&self, vec2 : Color) -> Color
     {
         Color
         {
@@ -40,6 +45,13 @@ impl Color
             b : self.b * vec2.b
         }
     }
+
+    /// Largest of the three channels.
+    pub fn max_component(&self) -> f64
+    { self.r.max(self.g).max(self.b) }
+
+    pub(crate) fn is_black(&self) -> bool
+    { self.r <= 0.0 && self.g <= 0.0 && self.b <= 0.0 }
 }
 
 impl Add for Color {
@@ -63,17 +75,29 @@ impl Mul<f64> for Color {
         Color
         {
             r : self.r * constant,
-            g : self.r * constant,
-            b : self.r * constant
+            g : self.g * constant,
+            b : self.b * constant
         }
     }
 }
 
-pub fn string_to_hex_int(color : &str ) -> i32
+// Decodes an sRGB encoded channel in [0, 1] such as those of 8 bit images
+pub fn srgb_to_linear(value : f64) -> f64
 {
-    let no_prefix = color.trim_start_matches("#");
+    if value <= 0.04045
+    { return value / 12.92; }
+
+    return ((value + 0.055) / 1.055).powf(2.4);
+}
 
-    let result = i32::from_str_radix(no_prefix, 16);
+// None if the colour is not a hexadecimal RGB value
+pub fn string_to_hex_int(color : &str ) -> Option<i32>
+{
+    let no_prefix = color.trim_start_matches("#");
 
-    return  result.unwrap();
+    return match i32::from_str_radix(no_prefix, 16)
+    {
+        Ok(value) if (0..=0xFFFFFF).contains(&value) => Some(value),
+        _ => None
+    };
 }
\ No newline at end of file
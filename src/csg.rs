@@ -0,0 +1,297 @@
+use crate::aabb::Aabb;
+use crate::material::Material;
+use crate::object::{Body, Hit};
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+const EPSILON : f64 = 0.000001;
+
+/// How [`csg_builder`](crate::object_builder::csg_builder) combines its two solids.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CsgOperation
+{
+    /// Space inside either solid.
+    Union,
+    /// Space inside both solids.
+    Intersection,
+    /// Space inside the left solid but not the right one.
+    Difference
+}
+
+// Combines two sorted lists of disjoint intervals, keeping the spans where keep(in_a, in_b) holds
+fn combine(a : &[(Hit, Hit)], b : &[(Hit, Hit)], keep : fn(bool, bool) -> bool) -> Vec<(Hit, Hit)>
+{
+    // Every interval boundary toggles whether the line is inside the respective operand
+    let mut events : Vec<(Hit, bool)> = Vec::with_capacity(2 * (a.len() + b.len()));
+
+    for (start, end) in a
+    {
+        events.push((*start, true));
+        events.push((*end, true));
+    }
+
+    for (start, end) in b
+    {
+        events.push((*start, false));
+        events.push((*end, false));
+    }
+
+    events.sort_by(|x, y| x.0.distance.total_cmp(&y.0.distance));
+
+    let mut result = Vec::new();
+    let mut in_a = false;
+    let mut in_b = false;
+    let mut span_start = Hit::new(0.0);
+
+    for (hit, from_a) in events
+    {
+        let was_inside = keep(in_a, in_b);
+
+        if from_a
+        { in_a = ! in_a; }
+        else
+        { in_b = ! in_b; }
+
+        let is_inside = keep(in_a, in_b);
+
+        if ! was_inside && is_inside
+        { span_start = hit; }
+
+        else if was_inside && ! is_inside && hit.distance > span_start.distance
+        { result.push((span_start, hit)); }
+    }
+
+    return result;
+}
+
+pub fn interval_union(a : &[(Hit, Hit)], b : &[(Hit, Hit)]) -> Vec<(Hit, Hit)>
+{ return combine(a, b, |in_a, in_b| in_a || in_b); }
+
+pub fn interval_intersection(a : &[(Hit, Hit)], b : &[(Hit, Hit)]) -> Vec<(Hit, Hit)>
+{ return combine(a, b, |in_a, in_b| in_a && in_b); }
+
+pub fn interval_difference(a : &[(Hit, Hit)], b : &[(Hit, Hit)]) -> Vec<(Hit, Hit)>
+{ return combine(a, b, |in_a, in_b| in_a && ! in_b); }
+
+// Marks the boundaries of an operand's intervals as coming from that operand
+fn from_operand(mut intervals : Vec<(Hit, Hit)>, right : bool) -> Vec<(Hit, Hit)>
+{
+    for (start, end) in intervals.iter_mut()
+    {
+        start.operands = start.operands << 1 | right as u64;
+        end.operands = end.operands << 1 | right as u64;
+    }
+
+    return intervals;
+}
+
+// Two solids combined by a boolean operation
+#[derive(Clone)]
+pub struct Csg
+{
+    operation : CsgOperation,
+    left : Box<dyn Body + Send + Sync>,
+    right : Box<dyn Body + Send + Sync>,
+    material : Material
+}
+
+impl Csg
+{
+    pub fn new(op : CsgOperation, left_body : Box<dyn Body + Send + Sync>, right_body : Box<dyn Body + Send + Sync>,
+        mat : Material) -> Self
+    {
+        Csg
+        {
+            operation : op,
+            left : left_body,
+            right : right_body,
+            material : mat
+        }
+    }
+
+    // The operand a boundary came from and the hit as that operand returned it
+    fn operand(&self, hit : &Hit) -> (&(dyn Body + Send + Sync), Hit)
+    {
+        let operand_hit = Hit { operands : hit.operands >> 1, ..*hit };
+
+        if hit.operands & 1 == 0
+        { return (self.left.as_ref(), operand_hit); }
+
+        return (self.right.as_ref(), operand_hit);
+    }
+}
+
+impl Body for Csg
+{
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        return self.intervals(ray)?.into_iter()
+            .flat_map(|(start, end)| [start, end])
+            .find(|hit| hit.distance > EPSILON && hit.distance.is_finite());
+    }
+
+    fn normal(&self, vec : Vector, hit : &Hit) -> Vector
+    {
+        let (operand, operand_hit) = self.operand(hit);
+        let normal = operand.normal(vec, &operand_hit);
+
+        // Surfaces carved out by the right operand face into it
+        if self.operation == CsgOperation::Difference && hit.operands & 1 == 1
+        { return normal * -1.0; }
+
+        return normal;
+    }
+
+    fn uv(&self, vec : Vector, hit : &Hit) -> (f64, f64)
+    {
+        let (operand, operand_hit) = self.operand(hit);
+
+        return operand.uv(vec, &operand_hit);
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    {
+        let left = self.left.bounds();
+        let right = self.right.bounds();
+
+        return match self.operation
+        {
+            CsgOperation::Union => match (left, right)
+            {
+                (Some(l), Some(r)) => Some(l.union(&r)),
+                _ => None
+            },
+
+            // The result lies within either operand, so the smaller known bounds suffice
+            CsgOperation::Intersection => match (left, right)
+            {
+                (Some(l), Some(r)) =>
+                {
+                    let min = Vector { x : l.min.x.max(r.min.x), y : l.min.y.max(r.min.y), z : l.min.z.max(r.min.z) };
+                    let max = Vector { x : l.max.x.min(r.max.x), y : l.max.y.min(r.max.y), z : l.max.z.min(r.max.z) };
+
+                    Some(Aabb::new(min, max))
+                },
+                (Some(l), None) => Some(l),
+                (None, r) => r
+            },
+
+            CsgOperation::Difference => left
+        };
+    }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone();
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+
+    // Scene validation rejects operands that do not enclose a volume, ones combined in code contribute nothing
+    fn intervals(&self, ray : Ray) -> Option<Vec<(Hit, Hit)>>
+    {
+        let left = from_operand(self.left.intervals(ray).unwrap_or_default(), false);
+        let right = from_operand(self.right.intervals(ray).unwrap_or_default(), true);
+
+        return Some(match self.operation
+        {
+            CsgOperation::Union => interval_union(&left, &right),
+            CsgOperation::Intersection => interval_intersection(&left, &right),
+            CsgOperation::Difference => interval_difference(&left, &right)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::material::MatType;
+    use crate::object::Sphere;
+    use super::*;
+
+    fn spans(distances : &[(f64, f64)]) -> Vec<(Hit, Hit)>
+    { return distances.iter().map(|(start, end)| (Hit::new(*start), Hit::new(*end))).collect(); }
+
+    fn distances(intervals : Vec<(Hit, Hit)>) -> Vec<(f64, f64)>
+    { return intervals.iter().map(|(start, end)| (start.distance, end.distance)).collect(); }
+
+    fn white() -> Material
+    { return Material::new(0xFFFFFF, MatType::Diffuse, 0.0); }
+
+    fn sphere(x : f64, radius : f64) -> Box<dyn Body + Send + Sync>
+    { return Box::new(Sphere::new(Vector { x, y : 0.0, z : 0.0 }, radius, white())); }
+
+    fn ray_along_x(x : f64) -> Ray
+    { return Ray { origin : Vector { x, y : 0.0, z : 0.0 }, dest : Vector { x : 1.0, y : 0.0, z : 0.0 } }; }
+
+    #[test]
+    fn overlapping_intervals()
+    {
+        let (a, b) = (spans(&[(0.0, 2.0), (5.0, 6.0)]), spans(&[(1.0, 3.0)]));
+
+        assert_eq!(distances(interval_union(&a, &b)), vec![(0.0, 3.0), (5.0, 6.0)]);
+        assert_eq!(distances(interval_intersection(&a, &b)), vec![(1.0, 2.0)]);
+        assert_eq!(distances(interval_difference(&a, &b)), vec![(0.0, 1.0), (5.0, 6.0)]);
+        assert_eq!(distances(interval_difference(&b, &a)), vec![(2.0, 3.0)]);
+    }
+
+    #[test]
+    fn touching_intervals()
+    {
+        let (a, b) = (spans(&[(0.0, 1.0)]), spans(&[(1.0, 2.0)]));
+
+        // Touching spans keep the shared boundary but have no volume in common
+        assert_eq!(distances(interval_union(&a, &b)), vec![(0.0, 1.0), (1.0, 2.0)]);
+        assert_eq!(distances(interval_intersection(&a, &b)), vec![]);
+        assert_eq!(distances(interval_difference(&a, &b)), vec![(0.0, 1.0)]);
+    }
+
+    #[test]
+    fn nested_intervals()
+    {
+        let (outer, inner) = (spans(&[(0.0, 4.0)]), spans(&[(1.0, 2.0)]));
+
+        assert_eq!(distances(interval_union(&outer, &inner)), vec![(0.0, 4.0)]);
+        assert_eq!(distances(interval_intersection(&outer, &inner)), vec![(1.0, 2.0)]);
+        assert_eq!(distances(interval_difference(&outer, &inner)), vec![(0.0, 1.0), (2.0, 4.0)]);
+        assert_eq!(distances(interval_difference(&inner, &outer)), vec![]);
+    }
+
+    #[test]
+    fn ray_starting_inside_an_operand()
+    {
+        // Sphere of radius 2 at the origin with a bite of radius 1 taken out around x = 2
+        let bitten = Csg::new(CsgOperation::Difference, sphere(0.0, 2.0), sphere(2.0, 1.0), white());
+        let ray = ray_along_x(0.0);
+
+        // Intervals span the whole line, so the one the ray starts in begins behind it
+        assert_eq!(distances(bitten.intervals(ray).unwrap()), vec![(-2.0, 1.0)]);
+
+        let hit = bitten.intersect(ray).unwrap();
+        assert_eq!(hit.distance, 1.0);
+        assert_eq!(hit.operands, 1);
+    }
+
+    #[test]
+    fn carved_surfaces_face_into_the_right_operand()
+    {
+        let bitten = Csg::new(CsgOperation::Difference, sphere(0.0, 2.0), sphere(2.0, 1.0), white());
+
+        // The left operand keeps its normal
+        let outside = ray_along_x(-5.0);
+        let hit = bitten.intersect(outside).unwrap();
+        assert_eq!((hit.distance, hit.operands), (3.0, 0));
+        assert_eq!(bitten.normal(Vector { x : -2.0, y : 0.0, z : 0.0 }, &hit), Vector { x : -1.0, y : 0.0, z : 0.0 });
+
+        // The bite's surface faces away from the remaining solid, even inside another body
+        let nested = Csg::new(CsgOperation::Union, Box::new(bitten), sphere(10.0, 1.0), white());
+        let hit = nested.intersect(ray_along_x(0.0)).unwrap();
+        assert_eq!((hit.distance, hit.operands), (1.0, 0b10));
+        assert_eq!(nested.normal(Vector { x : 1.0, y : 0.0, z : 0.0 }, &hit), Vector { x : 1.0, y : 0.0, z : 0.0 });
+
+        // Right operands of other operations keep their normals
+        let union = Csg::new(CsgOperation::Union, sphere(10.0, 1.0), sphere(0.0, 2.0), white());
+        let hit = union.intersect(outside).unwrap();
+        assert_eq!((hit.distance, hit.operands), (3.0, 1));
+        assert_eq!(union.normal(Vector { x : -2.0, y : 0.0, z : 0.0 }, &hit), Vector { x : -1.0, y : 0.0, z : 0.0 });
+    }
+}
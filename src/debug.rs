@@ -0,0 +1,209 @@
+use crate::color::Color;
+use crate::material::MatType;
+use crate::random;
+use crate::ray::Ray;
+use crate::render::{diffuse_brdf, refractive_brdf, specular_brdf, Integrator};
+use crate::render_settings::RenderSettings;
+use crate::scene::Scene;
+use crate::vector::Vector;
+
+// Counts shown at the hot end of the heatmaps
+const PATH_LENGTH_SCALE : f64 = 16.0;
+const NODE_VISITS_SCALE : f64 = 256.0;
+
+// Width of wireframe lines in pixels
+const WIREFRAME_WIDTH : f64 = 1.0;
+
+// Scene wide values the debug views are normalized by
+pub struct DebugContext
+{
+    // Depth of the furthest bounded object, shown as white in the depth view
+    far : f64
+}
+
+impl DebugContext
+{
+    pub fn new(scene : &Scene) -> Self
+    {
+        let origin = scene.camera.position();
+        let forward = scene.camera.forward();
+        let mut furthest : f64 = 0.0;
+
+        for bounds in scene.objects.iter().filter_map(|object| object.bounds)
+        {
+            for corner in bounds.corners()
+            { furthest = furthest.max((corner - origin).dot_product(forward)); }
+        }
+
+        DebugContext
+        {
+            far : if furthest > 0.0 { furthest } else { 1.0 }
+        }
+    }
+}
+
+fn gray(value : f64) -> Color
+{ return Color::new_rgb(value, value, value); }
+
+// Blue through green to red as value goes from 0 to 1
+fn heat(value : f64) -> Color
+{
+    let t = value.clamp(0.0, 1.0);
+    let ramp = |centre : f64| (1.5 - (4.0 * t - centre).abs()).clamp(0.0, 1.0);
+
+    return Color::new_rgb(ramp(3.0), ramp(2.0), ramp(1.0));
+}
+
+fn facing(normal : Vector, dir : Vector) -> Vector
+{
+    if normal.dot_product(dir) > 0.0
+    { return normal * -1.0; }
+
+    return normal;
+}
+
+fn distance(a : Vector, b : Vector) -> f64
+{
+    let offset = a - b;
+
+    return offset.dot_product(offset).sqrt();
+}
+
+// White unless a surface within radius blocks a cosine weighted direction above the hit point
+fn ambient_occlusion(scene : &Scene, ray : Ray, radius : f64, rays : &mut u64) -> Color
+{
+    let (object, hit_point, hit) = match scene.next_surface(ray, rays)
+    {
+        Some(surface) => surface,
+        None => return gray(0.0)
+    };
+
+    let normal = facing(object.body.normal(hit_point, &hit), ray.dest);
+    let mut occlusion_ray = Ray { origin : hit_point, dest : normal };
+    diffuse_brdf(normal, &mut occlusion_ray);
+
+    return match scene.next_surface(occlusion_ray, rays)
+    {
+        Some((_, point, _)) if distance(point, hit_point) < radius => gray(0.0),
+        _ => gray(1.0)
+    };
+}
+
+// Polygon edges in white over surfaces shaded by the angle they are seen at
+fn wireframe(scene : &Scene, ray : Ray, rays : &mut u64) -> Color
+{
+    let (object, hit_point, hit) = match scene.next_surface(ray, rays)
+    {
+        Some(surface) => surface,
+        None => return gray(0.0)
+    };
+
+    if let Some(edge) = object.body.nearest_edge(hit_point, &hit)
+    {
+        // Each triangle draws half of the line along its edges
+        let half_width = 0.5 * WIREFRAME_WIDTH * scene.camera.pixel_footprint() * distance(hit_point, ray.origin);
+
+        if distance(hit_point, edge) < half_width
+        { return gray(1.0); }
+    }
+
+    return gray(0.5 * object.body.normal(hit_point, &hit).dot_product(ray.dest).abs());
+}
+
+// Bounces the path integrator's sampling makes before the path ends, ignoring media and light sampling
+fn path_length(scene : &Scene, settings : &RenderSettings, mut ray : Ray, rays : &mut u64) -> u32
+{
+    let mut throughput = Color::new_rgb(1.0, 1.0, 1.0);
+    let mut bounce = 0;
+
+    while bounce < settings.max_bounces
+    {
+        let (object, hit_point, hit) = match scene.next_surface(ray, rays)
+        {
+            Some(surface) => surface,
+            None => break
+        };
+
+        let normal = object.body.normal(hit_point, &hit);
+        let material = object.body.get_material();
+
+        ray.origin = hit_point;
+
+        match material.mat_type
+        {
+            MatType::Diffuse =>
+            {
+                diffuse_brdf(facing(normal, ray.dest), &mut ray);
+                throughput = throughput.mul_by_color(object.body.albedo(hit_point, &hit));
+            }
+
+            MatType::Specular => specular_brdf(normal, &mut ray),
+
+            MatType::Refractive => refractive_brdf(normal, &mut ray, material.ior.nominal()),
+
+            // Skipped by Scene::next_surface
+            MatType::Interface => {}
+        }
+
+        bounce += 1;
+
+        if bounce >= settings.min_bounces
+        {
+            let survival_prob = throughput.max_component().min(0.95);
+
+            if survival_prob <= 0.0 || random::gen_num() >= survival_prob
+            { break; }
+
+            throughput = throughput * (1.0 / survival_prob);
+        }
+    }
+
+    return bounce;
+}
+
+// Normal, albedo or depth of the first surface
+fn surface_view(scene : &Scene, ray : Ray, integrator : Integrator, context : &DebugContext, rays : &mut u64) -> Color
+{
+    let (object, hit_point, hit) = match scene.next_surface(ray, rays)
+    {
+        Some(surface) => surface,
+        None => return gray(0.0)
+    };
+
+    if integrator == Integrator::Normals
+    {
+        let normal = object.body.normal(hit_point, &hit);
+
+        return Color::new_rgb(0.5 * (normal.x + 1.0), 0.5 * (normal.y + 1.0), 0.5 * (normal.z + 1.0));
+    }
+
+    if integrator == Integrator::Albedo
+    { return object.body.albedo(hit_point, &hit); }
+
+    return gray((hit_point - ray.origin).dot_product(scene.camera.forward()) / context.far);
+}
+
+// Value of a debug view for a camera ray, black for the radiance integrators. The rays cast are added to rays
+pub fn trace_debug(scene : &Scene, settings : &RenderSettings, ray : Ray, integrator : Integrator, context : &DebugContext,
+    rays : &mut u64) -> Color
+{
+    return match integrator
+    {
+        Integrator::Normals | Integrator::Albedo | Integrator::Depth => surface_view(scene, ray, integrator, context, rays),
+
+        Integrator::AmbientOcclusion => ambient_occlusion(scene, ray, settings.ao_radius, rays),
+
+        Integrator::Wireframe => wireframe(scene, ray, rays),
+
+        Integrator::PathLength => heat(path_length(scene, settings, ray, rays) as f64 / PATH_LENGTH_SCALE),
+
+        Integrator::NodeVisits =>
+        {
+            *rays += 1;
+
+            heat(scene.node_visits(ray) as f64 / NODE_VISITS_SCALE)
+        }
+
+        Integrator::Path | Integrator::Bidirectional => gray(0.0)
+    };
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::path::Path;
+use gltf::camera::Projection;
+use gltf::khr_lights_punctual::Kind;
+use gltf::mesh::Mode;
+use crate::json_utils::{array_to_vector, Config, JsonIor, JsonMaterial, JsonMaterialRef, JsonObject, JsonResolution, MaterialLibrary};
+use crate::matrix::Matrix4;
+use crate::scene_validation::{SceneError, SceneIssue};
+use crate::vector::Vector;
+
+// Material of primitives that don't name one, glTF's default is a white dielectric
+const DEFAULT_MATERIAL : &str = "default";
+// Radius of the spheres standing in for point and spot lights
+const LIGHT_RADIUS : f64 = 0.05;
+// Height of the image rendered through the file's camera, the width follows the camera's aspect ratio
+const CAMERA_HEIGHT : u32 = 400;
+const DEFAULT_ASPECT_RATIO : f64 = 1.5;
+const DEFAULT_IOR : f64 = 1.5;
+
+// glTF stores matrices column major
+fn to_matrix(columns : [[f32; 4]; 4]) -> Matrix4
+{
+    let mut matrix = Matrix4::identity();
+
+    for (row, values) in matrix.m.iter_mut().enumerate()
+    {
+        for (col, value) in values.iter_mut().enumerate()
+        { *value = columns[col][row] as f64; }
+    }
+
+    return matrix;
+}
+
+fn to_array(vec : Vector) -> [f64; 3]
+{ return [vec.x, vec.y, vec.z]; }
+
+fn to_vector(values : [f32; 3]) -> Vector
+{ return array_to_vector([values[0] as f64, values[1] as f64, values[2] as f64]); }
+
+// Mirroring transforms turn the winding of triangles around
+fn flips_winding(matrix : &Matrix4) -> bool
+{
+    let m = &matrix.m;
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+
+    return det < 0.0;
+}
+
+// Colour factors are linear like the renderer's colours
+fn hex_color(rgb : [f32; 3]) -> String
+{
+    let channel = |value : f32| (value.clamp(0.0, 1.0) as f64 * 255.0).round() as u32;
+
+    return format!("{:02X}{:02X}{:02X}", channel(rgb[0]), channel(rgb[1]), channel(rgb[2]));
+}
+
+// Metallic-roughness materials are approximated by the closest material type, roughness is not represented
+fn convert_material(material : &gltf::Material, file_name : &str) -> JsonMaterial
+{
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, _] = pbr.base_color_factor();
+
+    let transmission = material.transmission().map(|t| t.transmission_factor()).unwrap_or(0.0);
+    let material_type = if transmission > 0.5 { "refractive" } else if pbr.metallic_factor() >= 0.5 { "specular" } else { "diffuse" };
+
+    // Emission is a single intensity, the emissive colour only contributes its brightest channel
+    let emissive = material.emissive_factor().iter().fold(0.0f32, |max, c| max.max(*c));
+    let emission = emissive as f64 * material.emissive_strength().unwrap_or(1.0) as f64;
+
+    return JsonMaterial
+    {
+        material_type : Some(material_type.to_string()),
+        color : Some(hex_color([r, g, b])),
+        emission : if emission > 0.0 { Some(emission) } else { None },
+        ior : if material_type == "refractive"
+            { Some(JsonIor::Constant(material.ior().map(|n| n as f64).unwrap_or(DEFAULT_IOR))) }
+            else
+            { None },
+        texture : pbr.base_color_texture().map(|info| format!("{}#{}", file_name, info.texture().source().index())),
+        ..Default::default()
+    };
+}
+
+struct Importer<'a>
+{
+    buffers : &'a [gltf::buffer::Data],
+    file_name : String,
+    materials : MaterialLibrary,
+    // Library names given to the file's materials, None for the default material
+    material_names : HashMap<Option<usize>, String>,
+    objects : Vec<JsonObject>,
+    has_camera : bool,
+    // Parts of the file that cannot be imported
+    issues : Vec<SceneIssue>
+}
+
+impl<'a> Importer<'a>
+{
+    // Materials keep their name in the file so scenes including it can override them, unnamed or clashing ones are numbered
+    fn material(&mut self, material : &gltf::Material) -> String
+    {
+        if let Some(name) = self.material_names.get(&material.index())
+        { return name.clone(); }
+
+        let mut name = match (material.index(), material.name())
+        {
+            (None, _) => DEFAULT_MATERIAL.to_string(),
+            (Some(_), Some(name)) => name.to_string(),
+            (Some(index), None) => format!("material_{}", index)
+        };
+
+        if self.materials.contains_key(&name)
+        { name = format!("{}_{}", name, material.index().unwrap_or(0)); }
+
+        self.materials.insert(name.clone(), convert_material(material, &self.file_name));
+        self.material_names.insert(material.index(), name.clone());
+
+        return name;
+    }
+
+    fn primitive(&mut self, primitive : &gltf::Primitive, transform : &Matrix4)
+    {
+        if primitive.mode() != Mode::Triangles
+        { return; }
+
+        let reader = primitive.reader(|buffer| self.buffers.get(buffer.index()).map(|data| &data[..]));
+
+        let vertices : Vec<[f64; 3]> = match reader.read_positions()
+        {
+            Some(positions) => positions.map(|p| to_array(transform.transform_point(to_vector(p)))).collect(),
+            None => return
+        };
+
+        let mut triangles : Vec<[usize; 3]> = match reader.read_indices()
+        {
+            Some(indices) =>
+            {
+                let indices : Vec<usize> = indices.into_u32().map(|i| i as usize).collect();
+
+                indices.chunks_exact(3).map(|tri| [tri[0], tri[1], tri[2]]).collect()
+            }
+            None => (0..vertices.len() / 3).map(|i| [3 * i, 3 * i + 1, 3 * i + 2]).collect()
+        };
+
+        if flips_winding(transform)
+        {
+            for tri in triangles.iter_mut()
+            { tri.swap(1, 2); }
+        }
+
+        let normal_matrix = transform.inverse().unwrap_or(Matrix4::identity()).transpose();
+        let normals = reader.read_normals()
+            .map(|normals| normals.map(|n| to_array(*normal_matrix.transform_vector(to_vector(n)).normalize())).collect());
+
+        let material = primitive.material();
+        let name = self.material(&material);
+
+        let uvs = match material.pbr_metallic_roughness().base_color_texture()
+        {
+            Some(info) => reader.read_tex_coords(info.tex_coord())
+                .map(|uvs| uvs.into_f32().map(|[u, v]| [u as f64, v as f64]).collect()),
+            None => None
+        };
+
+        self.objects.push(JsonObject::JsonMesh
+        {
+            vertices,
+            triangles,
+            normals,
+            uvs,
+            colors : None,
+            material : JsonMaterialRef::Named(name)
+        });
+    }
+
+    // Cameras look down their local -Z axis with +Y up
+    fn camera(&mut self, camera : &gltf::Camera, transform : &Matrix4)
+    {
+        // Only the first camera is used, like the first camera of a scene file
+        if self.has_camera
+        { return; }
+
+        let perspective = match camera.projection()
+        {
+            Projection::Perspective(perspective) => perspective,
+            Projection::Orthographic(_) => return
+        };
+
+        let look_from = transform.transform_point(array_to_vector([0.0, 0.0, 0.0]));
+        let forward = *transform.transform_vector(array_to_vector([0.0, 0.0, -1.0])).normalize();
+        let vup = transform.transform_vector(array_to_vector([0.0, 1.0, 0.0]));
+        let aspect_ratio = perspective.aspect_ratio().map(|a| a as f64).unwrap_or(DEFAULT_ASPECT_RATIO);
+
+        self.has_camera = true;
+        self.objects.push(JsonObject::JsonCamera
+        {
+            look_from : Some(to_array(look_from)),
+            look_at : Some(to_array(look_from + forward)),
+            vup : Some(to_array(vup)),
+            fov : Some((perspective.yfov() as f64).to_degrees()),
+            resolution : JsonResolution
+            {
+                width : (CAMERA_HEIGHT as f64 * aspect_ratio).round() as u32,
+                height : CAMERA_HEIGHT
+            }
+        });
+    }
+
+    // Point and spot lights become small spheres emitting the light's intensity, spot cones are not represented.
+    // Directional lights have no place to put a sphere and are reported instead
+    fn light(&mut self, light : &gltf::khr_lights_punctual::Light, transform : &Matrix4)
+    {
+        if let Kind::Directional = light.kind()
+        {
+            self.issues.push(SceneIssue
+            {
+                file : Some(self.file_name.clone()),
+                object : None,
+                field : format!("KHR_lights_punctual.lights.{}", light.index()),
+                reason : "directional lights are not supported".to_string()
+            });
+
+            return;
+        }
+
+        let brightest = light.color().iter().fold(0.0f32, |max, c| max.max(*c)) as f64;
+
+        self.objects.push(JsonObject::JsonLight
+        {
+            center : Some(to_array(transform.transform_point(array_to_vector([0.0, 0.0, 0.0])))),
+            radius : Some(LIGHT_RADIUS),
+            emission : Some(light.intensity() as f64 * brightest / (PI * LIGHT_RADIUS * LIGHT_RADIUS))
+        });
+    }
+
+    fn node(&mut self, node : &gltf::Node, parent : &Matrix4)
+    {
+        let transform = parent.mul(&to_matrix(node.transform().matrix()));
+
+        if let Some(mesh) = node.mesh()
+        {
+            for primitive in mesh.primitives()
+            { self.primitive(&primitive, &transform); }
+        }
+
+        if let Some(camera) = node.camera()
+        { self.camera(&camera, &transform); }
+
+        if let Some(light) = node.light()
+        { self.light(&light, &transform); }
+
+        for child in node.children()
+        { self.node(&child, &transform); }
+    }
+}
+
+// Converts the default scene of a .gltf or .glb file into scene objects, textures refer back to the file's images
+pub fn read_gltf(path : &Path) -> Result<Config, SceneError>
+{
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+    let buffers = gltf::import_buffers(&document, path.parent(), blob)?;
+
+    let mut importer = Importer
+    {
+        buffers : &buffers,
+        file_name : path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+        materials : MaterialLibrary::new(),
+        material_names : HashMap::new(),
+        objects : Vec::new(),
+        has_camera : false,
+        issues : Vec::new()
+    };
+
+    if let Some(scene) = document.default_scene().or_else(|| document.scenes().next())
+    {
+        for node in scene.nodes()
+        { importer.node(&node, &Matrix4::identity()); }
+    }
+
+    if ! importer.issues.is_empty()
+    { return Err(SceneError::Invalid(importer.issues)); }
+
+    return Ok(Config
+    {
+        materials : importer.materials,
+        objects : importer.objects
+    });
+}
@@ -1,12 +1,20 @@
-use std::{io::Write};
+use std::io::{self, Write};
+use std::path::Path;
 use crate::Color;
 
+/// Extensions of the image formats [`Image::write`] supports.
+pub const OUTPUT_EXTENSIONS : &[&str] = &["ppm", "png", "jpg", "jpeg"];
+
+/// Rendered image holding linear radiance, which is gamma encoded and clamped only when written.
 #[derive(Clone)]
 pub struct Image
 {
+    /// Width in pixels.
     pub width : u32,
+    /// Height in pixels.
     pub height : u32,
-    pub buffer : Vec<Vec<Color>>
+    // Rows of pixels, the bottom row first
+    pub(crate) buffer : Vec<Vec<Color>>
 }
 
 fn to_writable_byte(val : f64) -> u8
@@ -18,6 +26,7 @@ fn to_writable_byte(val : f64) -> u8
 
 impl Image
 {
+    /// Black image of the given size.
     pub fn new(w : u32, h: u32 ) -> Self
     {
         Image
@@ -28,16 +37,20 @@ impl Image
         }
     }
 
-    fn to_writable_buff(self) -> Vec<u8>
+    /// Linear radiance of a pixel as it appears in the written file, `x` from the left and `y` from the top.
+    pub fn pixel(&self, x : u32, y : u32) -> Color
+    { return self.buffer[(self.height - 1 - y) as usize][x as usize]; }
+
+    fn to_writable_buff(&self) -> Vec<u8>
     {
         let  buff_size = self.height * self.width * 3;
-        let mut buffer = Vec::with_capacity(buff_size as usize);
-        buffer.resize(buff_size as usize, 0);
+        let mut buffer = vec![0; buff_size as usize];
         let mut buff_index = 0;
 
+        // The buffer holds the bottom row first, as the camera's image plane is laid out
         for col in self.buffer.iter().rev()
         {
-            for row in col.iter().rev()
+            for row in col.iter()
             {
                 buffer[buff_index] = to_writable_byte(row.r);
                 buffer[buff_index + 1] = to_writable_byte(row.g);
@@ -50,27 +63,56 @@ impl Image
         return buffer;
     }
 
-    pub fn write_to_ppm(self, file_name : &str) -> std::io::Result<()>
+    /// Whether [`write`](Image::write) can write an image to the path, judged by its extension.
+    pub fn supports_output(path : &Path) -> bool
+    { return output_extension(path).is_some_and(|extension| OUTPUT_EXTENSIONS.contains(&extension.as_str())); }
+
+    /// Writes the image in the format of the path's extension, creating missing folders.
+    pub fn write(&self, path : &Path) -> io::Result<()>
     {
-        let file_path  = format!("{}{}", get_renders_folder(), file_name);
+        let extension = output_extension(path).unwrap_or_default();
+
+        if extension == "ppm"
+        { return self.write_to_ppm(&path.to_string_lossy()); }
+
+        if ! Image::supports_output(path)
+        {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("unsupported image extension '{}', use {}", extension, OUTPUT_EXTENSIONS.join(", "))));
+        }
+
+        if let Some(folder) = path.parent()
+        { std::fs::create_dir_all(folder)?; }
 
-        let buffer = self.clone().to_writable_buff();
+        let image = ::image::RgbImage::from_raw(self.width, self.height, self.to_writable_buff())
+            .expect("Image buffer doesn't match its size");
+
+        return image.save(path).map_err(io::Error::other);
+    }
+
+    // Writes to the path as given, creating missing folders
+    pub(crate) fn write_to_ppm(&self, file_path : &str) -> std::io::Result<()>
+    {
+        if let Some(folder) = std::path::Path::new(file_path).parent()
+        { std::fs::create_dir_all(folder)?; }
+
+        let buffer = self.to_writable_buff();
         
         let ppm_head = format!("P3\n{} {}\n{}\n", self.width, self.height, 255);
         
         let mut file = std::fs::File::create(file_path)?;
-        file.write(ppm_head.as_bytes())?;
+        file.write_all(ppm_head.as_bytes())?;
         let indent = 1;
 
         for byte in buffer
         {
             let byte_write = format!("{} ", byte);
 
-            file.write(byte_write.as_bytes())?;
+            file.write_all(byte_write.as_bytes())?;
 
             if indent % self.width * 3 == 0
             {
-                file.write("\n".as_bytes())?;
+                file.write_all("\n".as_bytes())?;
             }
         }
 
@@ -78,31 +120,5 @@ impl Image
     }
 }
 
-fn get_renders_folder() -> String
-{
-    let mut path_to_exe = match  std::env::current_exe()
-    {
-        Ok(path) =>
-        {
-            path
-        }
-        Err(_) => 
-        {
-            panic!("Error occured while getting path!");
-        }
-    };
-    
-    path_to_exe.pop();
-    
-    let path_string = path_to_exe.to_str().unwrap();
-
-    let renders_path : String;
-    
-    if cfg!(target_os = "windows")
-    { renders_path = format!("{}{}", path_string, "\\renders\\"); }
-    
-    else
-    { renders_path = format!("{}{}", path_string, "/renders/"); };
-
-    return renders_path;
-}
\ No newline at end of file
+fn output_extension(path : &Path) -> Option<String>
+{ return path.extension().map(|extension| extension.to_string_lossy().to_lowercase()); }
@@ -0,0 +1,227 @@
+use std::sync::Arc;
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::object::{Body, Hit, SurfaceSample};
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+// Shared body placed in the scene by an affine transform
+#[derive(Clone)]
+pub struct Instance
+{
+    body : Arc<dyn Body + Send + Sync>,
+    // Object space to world space and back
+    transform : Matrix4,
+    inv_transform : Matrix4,
+    // Scale of a transform that keeps shapes. Uniform samples of the shared body stay uniform under such transforms,
+    // so only these instances can be sampled as area lights
+    scale : Option<f64>,
+    // Replaces the shared body's material when set
+    material : Option<Material>
+}
+
+impl Instance
+{
+    // None if the transform cannot be inverted
+    pub fn new(shared_body : Arc<dyn Body + Send + Sync>, object_to_world : Matrix4, mat : Option<Material>) -> Option<Self>
+    {
+        let world_to_object = object_to_world.inverse()?;
+
+        Some(Instance
+        {
+            body : shared_body,
+            transform : object_to_world,
+            inv_transform : world_to_object,
+            scale : object_to_world.uniform_scale(),
+            material : mat
+        })
+    }
+
+    // Object space ray with a normalized direction and the length the world direction maps to
+    fn to_local_ray(&self, ray : Ray) -> (Ray, f64)
+    {
+        let mut local_dest = self.inv_transform.transform_vector(ray.dest);
+        let dest_scale = local_dest.dot_product(local_dest).sqrt();
+        local_dest.normalize();
+
+        let local_ray = Ray
+        {
+            origin : self.inv_transform.transform_point(ray.origin),
+            dest : local_dest
+        };
+
+        return (local_ray, dest_scale);
+    }
+
+    // Normals are transformed by the inverse transpose to stay perpendicular under non uniform scaling
+    fn to_world_normal(&self, local_normal : Vector) -> Vector
+    { return *self.inv_transform.transpose().transform_vector(local_normal).normalize(); }
+
+    fn to_world_sample(&self, local : SurfaceSample) -> SurfaceSample
+    {
+        return SurfaceSample
+        {
+            point : self.transform.transform_point(local.point),
+            normal : self.to_world_normal(local.normal)
+        };
+    }
+}
+
+impl Body for Instance
+{
+    // The shared body's hit is passed back to it by the shading methods, only its distance changes
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        let (local_ray, dest_scale) = self.to_local_ray(ray);
+        let mut hit = self.body.intersect(local_ray)?;
+
+        // Distances along the normalized object space ray are scaled back into world units
+        hit.distance /= dest_scale;
+
+        return Some(hit);
+    }
+
+    fn normal(&self, vec : Vector, hit : &Hit) -> Vector
+    { return self.to_world_normal(self.body.normal(self.inv_transform.transform_point(vec), hit)); }
+
+    fn uv(&self, vec : Vector, hit : &Hit) -> (f64, f64)
+    { return self.body.uv(self.inv_transform.transform_point(vec), hit); }
+
+    // A replaced material replaces the shared body's vertex colours as well
+    fn albedo(&self, vec : Vector, hit : &Hit) -> Color
+    {
+        let local = self.inv_transform.transform_point(vec);
+
+        return match &self.material
+        {
+            Some(material) => material.albedo(|| self.body.uv(local, hit)),
+            None => self.body.albedo(local, hit)
+        };
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    { return self.body.bounds().map(|bounds| bounds.transform(&self.transform)); }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone().unwrap_or_else(|| self.body.get_material());
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+
+    fn area(&self) -> Option<f64>
+    {
+        let scale = self.scale?;
+
+        return self.body.area().map(|area| area * scale * scale);
+    }
+
+    fn sample_surface(&self, u1 : f64, u2 : f64) -> Option<SurfaceSample>
+    {
+        self.scale?;
+
+        return self.body.sample_surface(u1, u2).map(|local| self.to_world_sample(local));
+    }
+
+    // Solid angles stay the same under transforms that keep shapes, so the shared body's densities carry over
+    fn sample_from(&self, origin : Vector, u1 : f64, u2 : f64) -> Option<(SurfaceSample, f64)>
+    {
+        self.scale?;
+
+        let (local, pdf) = self.body.sample_from(self.inv_transform.transform_point(origin), u1, u2)?;
+
+        return Some((self.to_world_sample(local), pdf));
+    }
+
+    fn pdf_from(&self, origin : Vector, point : Vector, normal : Vector) -> f64
+    {
+        if self.scale.is_none()
+        { return 0.0; }
+
+        let local_normal = *self.transform.transpose().transform_vector(normal).normalize();
+
+        return self.body.pdf_from(self.inv_transform.transform_point(origin), self.inv_transform.transform_point(point), local_normal);
+    }
+
+    fn two_sided(&self) -> bool
+    { return self.body.two_sided(); }
+
+    fn intervals(&self, ray : Ray) -> Option<Vec<(Hit, Hit)>>
+    {
+        let (local_ray, dest_scale) = self.to_local_ray(ray);
+        let mut intervals = self.body.intervals(local_ray)?;
+
+        for (start, end) in intervals.iter_mut()
+        {
+            start.distance /= dest_scale;
+            end.distance /= dest_scale;
+        }
+
+        return Some(intervals);
+    }
+
+    fn nearest_edge(&self, point : Vector, hit : &Hit) -> Option<Vector>
+    {
+        let local_edge = self.body.nearest_edge(self.inv_transform.transform_point(point), hit)?;
+
+        return Some(self.transform.transform_point(local_edge));
+    }
+
+    fn node_visits(&self, ray : Ray) -> u64
+    { return self.body.node_visits(self.to_local_ray(ray).0); }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::material::MatType;
+    use crate::object::{Cuboid, Sphere};
+    use super::*;
+
+    fn vector(x : f64, y : f64, z : f64) -> Vector
+    { return Vector { x, y, z }; }
+
+    fn white() -> Material
+    { return Material::new(0xFFFFFF, MatType::Diffuse, 0.0); }
+
+    fn instance(body : Arc<dyn Body + Send + Sync>, translate : Vector, rotate : Vector, scale : Vector) -> Instance
+    { return Instance::new(body, Matrix4::from_transform(translate, rotate, scale), None).unwrap(); }
+
+    fn assert_hit(body : &dyn Body, origin : Vector, dest : Vector, distance : f64, normal : Vector)
+    {
+        let hit = body.intersect(Ray { origin, dest }).unwrap();
+        let error = body.normal(origin + dest * hit.distance, &hit) - normal;
+
+        assert!((hit.distance - distance).abs() < 1e-9, "distance {} is not {}", hit.distance, distance);
+        assert!(error.dot_product(error) < 1e-18, "normal is off by {:?}", error);
+    }
+
+    #[test]
+    fn stretched_sphere()
+    {
+        // Ellipsoid x^2 / 4 + y^2 + z^2 = 1 around (5, 0, 0)
+        let sphere : Arc<dyn Body + Send + Sync> = Arc::new(Sphere::new(vector(0.0, 0.0, 0.0), 1.0, white()));
+        let ellipsoid = instance(sphere, vector(5.0, 0.0, 0.0), vector(0.0, 0.0, 0.0), vector(2.0, 1.0, 1.0));
+        let down = vector(0.0, -1.0, 0.0);
+
+        // Distances are in world units although the ray is shortened in object space
+        assert_hit(&ellipsoid, vector(0.0, 0.0, 0.0), vector(1.0, 0.0, 0.0), 3.0, vector(-1.0, 0.0, 0.0));
+        assert_hit(&ellipsoid, vector(5.0, 5.0, 0.0), down, 4.0, vector(0.0, 1.0, 0.0));
+
+        // Normals follow the inverse transpose, the gradient (x / 2, 2 y) of the ellipsoid rather than the stretched sphere normal
+        let half = 0.5_f64.sqrt();
+        assert_hit(&ellipsoid, vector(5.0 + 2.0 * half, 5.0, 0.0), down, 5.0 - half, vector(1.0, 2.0, 0.0) * (1.0 / 5.0_f64.sqrt()));
+    }
+
+    #[test]
+    fn rotated_box()
+    {
+        // Long along x before a quarter turn around z, so along y afterwards
+        let cube : Arc<dyn Body + Send + Sync> = Arc::new(Cuboid::new(vector(-1.0, -1.0, -1.0), vector(1.0, 1.0, 1.0), white()));
+        let beam = instance(cube, vector(0.0, 0.0, 0.0), vector(0.0, 0.0, 90.0), vector(3.0, 1.0, 1.0));
+
+        assert_hit(&beam, vector(0.0, -5.0, 0.0), vector(0.0, 1.0, 0.0), 2.0, vector(0.0, -1.0, 0.0));
+        assert_hit(&beam, vector(-5.0, 0.0, 0.0), vector(1.0, 0.0, 0.0), 4.0, vector(-1.0, 0.0, 0.0));
+    }
+}
@@ -1,325 +1,1151 @@
-use serde::{Deserialize};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use crate::scene::{Scene};
 use crate::color::{string_to_hex_int};
-use crate::material::{MatType};
+use crate::material::{Ior, Material, MatType};
 use crate::vector::Vector;
 use crate::camera::Camera;
-use crate::object_builder::{plane_builder, sphere_builder, sphere_light_builder};
+use crate::color::Color;
+use crate::medium::Medium;
+use crate::object::{Body, Object};
+use crate::matrix::Matrix4;
+use crate::aabb::Aabb;
+use crate::csg::CsgOperation;
+use crate::sdf::SdfNode;
+use crate::render::Integrator;
+use crate::render_settings::{CropWindow, Filter, RenderSettings, Sampler};
+use crate::scene_include::{expand_scene, ObjectOrigin};
+use crate::scene_validation::{validate_scene, validate_settings, SceneAssets, SceneError, SceneIssue};
+use crate::object_builder::{box_builder, cone_builder, csg_builder, cylinder_builder, disk_builder, instance_builder, mesh_builder,
+    plane_builder, quad_builder, sdf_builder, sphere_builder, sphere_light_builder, torus_builder};
 
-// Structures for serde deserialization
-#[derive(Debug, Deserialize, Clone)]
-struct JsonMaterial
+// Structures for serde (de)serialization
+
+/// Material as written in a scene file. Every field may be left out and taken from the inherited
+/// material instead.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct JsonMaterial
 {
+    /// Named material providing the fields this one leaves out.
+    pub inherits : Option<String>,
+    /// "diffuse", "specular", "refractive" or "interface".
     #[serde(rename = "type")]
-    material_type : String,
-    color : String
+    pub material_type : Option<String>,
+    /// Hex color such as "#FF8000".
+    pub color : Option<String>,
+    /// Radiance emitted by surfaces with this material, 0 for none.
+    pub emission : Option<f64>,
+    /// Fills the body's interior.
+    pub medium : Option<JsonMedium>,
+    /// Refractive index of dielectrics, a number or a dispersion model.
+    pub ior : Option<JsonIor>,
+    /// Image multiplying the color, or "model.glb#N" for an image stored in a glTF file.
+    pub texture : Option<String>
+}
+
+/// Objects either name a material of the scene's materials or describe one in place.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum JsonMaterialRef
+{
+    /// Name of one of the scene's materials.
+    Named(String),
+    /// Material used by this object only.
+    Inline(Box<JsonMaterial>)
+}
+
+/// Materials of a scene by name.
+pub type MaterialLibrary = BTreeMap<String, JsonMaterial>;
+
+/// Index of refraction of a material.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum JsonIor
+{
+    /// Same index at every wavelength.
+    Constant(f64),
+    /// Index varying with the wavelength.
+    Model(JsonIorModel)
+}
+
+/// Dispersion model, coefficients are given for wavelengths in micrometres.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "model")]
+pub enum JsonIorModel
+{
+    /// n = a + b / λ².
+    #[serde(rename = "cauchy")]
+    Cauchy
+    {
+        /// Index the model approaches at long wavelengths.
+        a : f64,
+        /// Strength of the dispersion.
+        b : f64
+    },
+
+    /// n² = 1 + Σ b λ² / (λ² - c).
+    #[serde(rename = "sellmeier")]
+    Sellmeier
+    {
+        /// Strength of each resonance.
+        b : [f64; 3],
+        /// Squared wavelength of each resonance.
+        c : [f64; 3]
+    }
+}
+
+/// Participating medium inside a body or filling the scene.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct JsonMedium
+{
+    /// Absorption per unit length for the red, green and blue channels.
+    pub absorption : [f64; 3],
+    /// Scattering per unit length for the red, green and blue channels.
+    pub scattering : [f64; 3],
+    /// Henyey-Greenstein asymmetry between -1 and 1, 0 scatters uniformly.
+    pub anisotropy : Option<f64>,
+    /// Spatially varying density the coefficients are multiplied with.
+    pub density : Option<JsonVoxelGrid>
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct JsonResolution
+/// Voxel grid file of densities.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct JsonVoxelGrid
 {
-    width : u32,
-    height : u32
+    /// Grid file next to the scene or in one of the asset search paths.
+    pub file : String,
+    /// Lowest corner of the box in world space the grid is stretched over.
+    pub min : [f64; 3],
+    /// Highest corner of that box.
+    pub max : [f64; 3]
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Size of the rendered image in pixels.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct JsonResolution
+{
+    /// Pixels per row.
+    pub width : u32,
+    /// Rows of pixels.
+    pub height : u32
+}
+
+/// Axis aligned box.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct JsonBounds
+{
+    /// Lowest corner.
+    pub min : [f64; 3],
+    /// Highest corner.
+    pub max : [f64; 3]
+}
+
+/// Node of a signed distance function, either a primitive or an operator on other nodes. Centres
+/// default to the origin.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(tag = "type")]
-enum JsonObject 
+pub enum JsonSdfNode
 {
+    /// Sphere.
+    #[serde(rename = "sphere")]
+    Sphere
+    {
+        /// Centre.
+        center : Option<[f64; 3]>,
+        /// Radius.
+        radius : f64
+    },
+
+    /// Axis aligned box, optionally with rounded edges.
+    #[serde(rename = "box")]
+    Box
+    {
+        /// Centre.
+        center : Option<[f64; 3]>,
+        /// Half the box's extent along each axis.
+        size : [f64; 3],
+        /// Radius of the rounded edges, 0 by default.
+        rounding : Option<f64>
+    },
+
+    /// Torus in the xz plane.
+    #[serde(rename = "torus")]
+    Torus
+    {
+        /// Centre.
+        center : Option<[f64; 3]>,
+        /// Distance from the centre to the middle of the tube.
+        major_radius : f64,
+        /// Radius of the tube.
+        minor_radius : f64
+    },
+
+    /// Capped cylinder along the y axis.
+    #[serde(rename = "cylinder")]
+    Cylinder
+    {
+        /// Centre.
+        center : Option<[f64; 3]>,
+        /// Radius.
+        radius : f64,
+        /// Full height.
+        height : f64
+    },
+
+    /// Mandelbulb fractal.
+    #[serde(rename = "mandelbulb")]
+    Mandelbulb
+    {
+        /// Centre.
+        center : Option<[f64; 3]>,
+        /// Size relative to the fractal's natural size, 1 by default.
+        scale : Option<f64>,
+        /// Exponent of the iteration, 8 by default.
+        power : Option<f64>,
+        /// Iterations per distance estimate, 12 by default.
+        iterations : Option<u32>
+    },
+
+    /// Space inside either node.
+    #[serde(rename = "union")]
+    Union
+    {
+        /// First node.
+        left : Box<JsonSdfNode>,
+        /// Second node.
+        right : Box<JsonSdfNode>
+    },
+
+    /// Space inside both nodes.
+    #[serde(rename = "intersection")]
+    Intersection
+    {
+        /// First node.
+        left : Box<JsonSdfNode>,
+        /// Second node.
+        right : Box<JsonSdfNode>
+    },
+
+    /// Space inside left but not inside right.
+    #[serde(rename = "difference")]
+    Difference
+    {
+        /// Node that is cut.
+        left : Box<JsonSdfNode>,
+        /// Node that is removed.
+        right : Box<JsonSdfNode>
+    },
+
+    /// Union blending the nodes where they meet.
+    #[serde(rename = "smooth_union")]
+    SmoothUnion
+    {
+        /// First node.
+        left : Box<JsonSdfNode>,
+        /// Second node.
+        right : Box<JsonSdfNode>,
+        /// Width of the blend.
+        smoothness : f64
+    },
+
+    /// Infinite repetition of a node.
+    #[serde(rename = "repeat")]
+    Repeat
+    {
+        /// Repeated node.
+        child : Box<JsonSdfNode>,
+        /// Spacing along each axis, 0 disables repetition along an axis.
+        period : [f64; 3]
+    },
+
+    /// Node twisted around the y axis.
+    #[serde(rename = "twist")]
+    Twist
+    {
+        /// Twisted node.
+        child : Box<JsonSdfNode>,
+        /// Radians per unit along the y axis.
+        rate : f64
+    },
+
+    /// Node moved by an offset.
+    #[serde(rename = "translate")]
+    Translate
+    {
+        /// Moved node.
+        child : Box<JsonSdfNode>,
+        /// Offset.
+        offset : [f64; 3]
+    }
+}
+
+/// Entry of a scene file's objects, an object or one of the camera, settings, atmosphere, prototype
+/// and include entries. Fields that are `Option`s are checked when the scene is loaded, so leaving
+/// out a required one is reported rather than failing to parse.
+// Variants are named after the scene file entries they are read from
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum JsonObject
+{
+    /// Infinite plane of the points p with normal · p + d = 0.
     #[serde(rename = "plane")]
     JsonPlane 
     {
+        /// Normal.
         normal : Option<[f64; 3]>,
+        /// Offset of the plane equation.
         d : Option<f64>,
-        material : JsonMaterial
+        /// Material.
+        material : JsonMaterialRef
     },
     
+    /// Sphere.
     #[serde(rename = "sphere")]
     JsonSphere
     {
+        /// Centre.
         center : Option<[f64; 3]>,
+        /// Radius.
         radius : Option<f64>,
-        material: JsonMaterial
+        /// Material.
+        material: JsonMaterialRef
     },
     
+    /// Box, axis aligned unless rotated.
+    #[serde(rename = "box")]
+    JsonBox
+    {
+        /// Lowest corner before rotating.
+        min : Option<[f64; 3]>,
+        /// Highest corner before rotating.
+        max : Option<[f64; 3]>,
+        /// Rotation around the box's centre in degrees.
+        rotation : Option<[f64; 3]>,
+        /// Material.
+        material : JsonMaterialRef
+    },
+
+    /// Capped cylinder.
+    #[serde(rename = "cylinder")]
+    JsonCylinder
+    {
+        /// Centre of the bottom cap.
+        base : Option<[f64; 3]>,
+        /// Direction from the bottom to the top cap.
+        axis : Option<[f64; 3]>,
+        /// Radius.
+        radius : Option<f64>,
+        /// Distance between the caps.
+        height : Option<f64>,
+        /// Material.
+        material : JsonMaterialRef
+    },
+
+    /// Capped cone.
+    #[serde(rename = "cone")]
+    JsonCone
+    {
+        /// Centre of the base, the apex lies height units along the axis.
+        base : Option<[f64; 3]>,
+        /// Direction from the base to the apex.
+        axis : Option<[f64; 3]>,
+        /// Radius of the base.
+        radius : Option<f64>,
+        /// Distance from the base to the apex.
+        height : Option<f64>,
+        /// Material.
+        material : JsonMaterialRef
+    },
+
+    /// Flat disk.
+    #[serde(rename = "disk")]
+    JsonDisk
+    {
+        /// Centre.
+        center : Option<[f64; 3]>,
+        /// Normal.
+        normal : Option<[f64; 3]>,
+        /// Radius.
+        radius : Option<f64>,
+        /// Material.
+        material : JsonMaterialRef
+    },
+
+    /// Torus.
+    #[serde(rename = "torus")]
+    JsonTorus
+    {
+        /// Centre.
+        center : Option<[f64; 3]>,
+        /// Axis the torus is symmetric around.
+        axis : Option<[f64; 3]>,
+        /// Distance from the centre to the middle of the tube.
+        major_radius : Option<f64>,
+        /// Radius of the tube.
+        minor_radius : Option<f64>,
+        /// Material.
+        material : JsonMaterialRef
+    },
+
+    /// Parallelogram.
+    #[serde(rename = "quad")]
+    JsonQuad
+    {
+        /// Corner the edges start at.
+        corner : Option<[f64; 3]>,
+        /// First edge, the normal is u x v.
+        u : Option<[f64; 3]>,
+        /// Second edge.
+        v : Option<[f64; 3]>,
+        /// Emissive quads emit only along their normal unless two sided.
+        two_sided : Option<bool>,
+        /// Material.
+        material : JsonMaterialRef
+    },
+
+    /// Triangle mesh.
+    #[serde(rename = "mesh")]
+    JsonMesh
+    {
+        /// Vertex positions.
+        vertices : Vec<[f64; 3]>,
+        /// Indices into vertices, counter clockwise winding faces the normal.
+        triangles : Vec<[usize; 3]>,
+        /// Optional per vertex normals for smooth shading.
+        normals : Option<Vec<[f64; 3]>>,
+        /// Optional per vertex texture coordinates.
+        uvs : Option<Vec<[f64; 2]>>,
+        /// Optional per vertex linear RGB colours multiplying the material's color.
+        colors : Option<Vec<[f64; 3]>>,
+        /// Material.
+        material : JsonMaterialRef
+    },
+
+    /// Surface of a distance function composed from primitives and operators.
+    #[serde(rename = "sdf")]
+    JsonSdf
+    {
+        /// Distance function.
+        root : JsonSdfNode,
+        /// Required if the distance function is unbounded.
+        bounds : Option<JsonBounds>,
+        /// Fraction of the distance marched per step, lower it for twisted shapes.
+        step_scale : Option<f64>,
+        /// Material.
+        material : JsonMaterialRef
+    },
+
+    /// Boolean combination of two solids.
+    #[serde(rename = "csg")]
+    JsonCsg
+    {
+        /// "union", "intersection" or "difference", the latter removes right from left.
+        operation : String,
+        /// First operand.
+        left : Box<JsonObject>,
+        /// Second operand.
+        right : Box<JsonObject>,
+        /// Defaults to the left operand's material.
+        material : Option<JsonMaterialRef>
+    },
+
+    /// Object that is not rendered by itself but can be placed by instances.
+    #[serde(rename = "prototype")]
+    JsonPrototype
+    {
+        /// Name instances refer to.
+        name : String,
+        /// Object that is placed.
+        object : Box<JsonObject>
+    },
+
+    /// Transformed copy of a prototype.
+    #[serde(rename = "instance")]
+    JsonInstance
+    {
+        /// Name of the prototype.
+        prototype : String,
+        /// Offset applied after rotating and scaling.
+        translate : Option<[f64; 3]>,
+        /// Rotation in degrees around the x, then y and then z axis.
+        rotate : Option<[f64; 3]>,
+        /// Scale along each axis.
+        scale : Option<[f64; 3]>,
+        /// Overrides the prototype's material.
+        material : Option<JsonMaterialRef>
+    },
+
+    /// Materials and objects of another scene file.
+    #[serde(rename = "include")]
+    JsonInclude
+    {
+        /// Relative to the including file or one of the asset search paths.
+        file : String,
+        /// Offset of the included objects, applied like an instance's transform.
+        translate : Option<[f64; 3]>,
+        /// Rotation of the included objects in degrees.
+        rotate : Option<[f64; 3]>,
+        /// Scale of the included objects.
+        scale : Option<[f64; 3]>,
+        /// Replaces the material of every included object.
+        material : Option<JsonMaterialRef>
+    },
+
+    /// Spherical light.
     #[serde(rename = "light")]
     JsonLight
     {
+        /// Centre.
         center : Option<[f64; 3]>,
+        /// Radius.
         radius : Option<f64>,
+        /// Emitted radiance.
         emission: Option<f64>
     },
 
+    /// Camera, only the first one is used.
     #[serde(rename = "camera")]
     JsonCamera
     {
+        /// Position.
         look_from : Option<[f64; 3]>,
+        /// Point in the centre of the image.
         look_at : Option<[f64; 3]>,
+        /// Direction that is up in the image.
         vup : Option<[f64; 3]>,
+        /// Vertical field of view in degrees.
         fov : Option<f64>,
+        /// Size of the image.
         resolution : JsonResolution
+    },
+
+    /// Medium filling the space outside of all bodies.
+    #[serde(rename = "atmosphere")]
+    JsonAtmosphere
+    {
+        /// Medium.
+        medium : JsonMedium
+    },
+
+    /// Render settings, unset ones keep their defaults.
+    #[serde(rename = "settings")]
+    JsonSettings
+    {
+        /// Samples per pixel.
+        spp : Option<u32>,
+        /// Seconds, renders as many samples per pixel as fit in them instead of spp.
+        time_limit : Option<f64>,
+        /// Bounces before paths may be ended by russian roulette.
+        min_bounces : Option<u32>,
+        /// Bounces after which paths are ended.
+        max_bounces : Option<u32>,
+        /// Renders with sampled wavelengths instead of RGB.
+        spectral : Option<bool>,
+        /// "path", "bdpt" or one of the debug views.
+        integrator : Option<String>,
+        /// "independent" or "stratified".
+        sampler : Option<String>,
+        /// "box", "tent" or "gaussian".
+        filter : Option<String>,
+        /// Makes renders repeatable, fresh random numbers are drawn every time if left out.
+        seed : Option<u64>,
+        /// Render threads, 0 uses every core.
+        threads : Option<usize>,
+        /// x, y, width and height in pixels from the top left corner.
+        crop : Option<[u32; 4]>,
+        /// Images written after rendering, relative to the working directory.
+        outputs : Option<Vec<PathBuf>>,
+        /// Photons for the caustic map of the path integrator.
+        caustic_photons : Option<u32>,
+        /// Radius caustic photons are gathered in.
+        caustic_radius : Option<f64>,
+        /// Distance the ambient occlusion view looks for occluders.
+        ao_radius : Option<f64>
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct Config {
-    objects: Vec<JsonObject>,
+/// Scene description as it is written in scene files.
+///
+/// ```
+/// use path_tracer::{read_scene_file, write_scene_file, Config, JsonMaterial, JsonMaterialRef, JsonObject};
+///
+/// let red = JsonMaterial { material_type : Some("diffuse".to_string()), color : Some("#FF0000".to_string()), ..Default::default() };
+/// let mut config = Config { materials : [("red".to_string(), red)].into(), objects : Vec::new() };
+///
+/// config.objects.push(JsonObject::JsonSphere
+/// {
+///     center : Some([0.0, 1.0, 0.0]),
+///     radius : Some(1.0),
+///     material : JsonMaterialRef::Named("red".to_string())
+/// });
+///
+/// let path = std::env::temp_dir().join("sphere.yaml");
+/// write_scene_file(&config, &path).expect("Writing the scene failed");
+///
+/// assert_eq!(read_scene_file(&path).expect("Reading the scene failed"), config);
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Config {
+    /// Materials objects can refer to by name.
+    #[serde(default, skip_serializing_if = "MaterialLibrary::is_empty")]
+    pub materials: MaterialLibrary,
+    /// Objects, camera, settings and includes in the order of the file.
+    pub objects: Vec<JsonObject>,
 }
 
-fn scene_add_planes(object_map : &HashMap<String, Vec<JsonObject>>, mut scene : Scene) -> Scene
+pub fn array_to_vector(arr : [f64; 3]) -> Vector
 {
-    if let Some(objects) =  object_map.get("plane")
+    let mut vec = Vector::new();
+    vec.set_vector(arr[0], arr[1], arr[2]);
+
+    return vec;
+}
+
+// Fields that validate_scene has checked to be present and well formed. A field that still can't be
+// built is a gap in validation, reported like a validation issue instead of ending the program
+fn validated<T>(value : Option<T>, field : &str) -> Result<T, SceneIssue>
+{
+    return value.ok_or_else(|| SceneIssue
     {
-        for object in objects 
-        {
-            if let JsonObject::JsonPlane 
-            {
-                normal,
-                d,
-                material
-            } = object
-            {
-                let unwrapped_normal = normal.unwrap();
-                let unwrapped_d = d.unwrap();
-                let unwrapped_mat_type = material.material_type.clone();
-                let unwrapped_color = material.color.clone();
-
-                let mut plane_normal = Vector::new();
-                plane_normal.set_vector(unwrapped_normal[0], unwrapped_normal[1], unwrapped_normal[2]);
-
-                let plane_mat_type: MatType = match unwrapped_mat_type.as_str() {
-                    "diffuse" => MatType::Diffuse,
-                    "specular" => MatType::Specular,
-                    _ => MatType::Refractive
-                };
-
-                let plane = plane_builder(plane_normal, unwrapped_d, plane_mat_type , string_to_hex_int(&unwrapped_color));
-
-                scene.add(plane);
-            }
-        }
+        file : None,
+        object : None,
+        field : field.to_string(),
+        reason : "could not be built although the scene passed validation".to_string()
+    });
+}
+
+// Issue of a field nested in `field`
+fn nested(field : &str, mut issue : SceneIssue) -> SceneIssue
+{
+    issue.field = format!("{}.{}", field, issue.field);
+
+    return issue;
+}
+
+fn string_to_mat_type(mat_type : &str) -> MatType
+{
+    match mat_type
+    {
+        "diffuse" => MatType::Diffuse,
+        "specular" => MatType::Specular,
+        "interface" => MatType::Interface,
+        _ => MatType::Refractive
     }
+}
 
-    return scene
+fn string_to_csg_operation(operation : &str) -> Option<CsgOperation>
+{
+    match operation
+    {
+        "union" => Some(CsgOperation::Union),
+        "intersection" => Some(CsgOperation::Intersection),
+        "difference" => Some(CsgOperation::Difference),
+        _ => None
+    }
 }
 
-fn scene_add_spheres(object_map : &HashMap<String, Vec<JsonObject>>, mut scene : Scene) -> Scene
+fn json_to_medium(medium : &JsonMedium, assets : &SceneAssets) -> Result<Medium, SceneIssue>
 {
-    if let Some(objects) =  object_map.get("sphere") 
+    let [ar, ag, ab] = medium.absorption;
+    let [sr, sg, sb] = medium.scattering;
+
+    let mut result = Medium::new(Color::new_rgb(ar, ag, ab), Color::new_rgb(sr, sg, sb), medium.anisotropy.unwrap_or(0.0));
+
+    if let Some(grid) = &medium.density
     {
-        for object in objects 
-        {
-            if let JsonObject::JsonSphere 
-            {
-                center,
-                radius,
-                material
-            } = object
-            {
-                let unwrapped_center = center.unwrap();
-                let unwrapped_radius = radius.unwrap();
-                let unwrapped_mat_type = material.material_type.clone();
-                let unwrapped_color = material.color.clone();
-
-                let mut sphere_center = Vector::new();
-                sphere_center.set_vector(unwrapped_center[0], unwrapped_center[1], unwrapped_center[2]);
-
-                let plane_mat_type: MatType = match unwrapped_mat_type.as_str() {
-                    "diffuse" => MatType::Diffuse,
-                    "specular" => MatType::Specular,
-                    _ => MatType::Refractive,
-                };
-
-                let plane = sphere_builder(sphere_center, unwrapped_radius, plane_mat_type , string_to_hex_int(&unwrapped_color));
-
-                scene.add(plane);
-            }
-        }
+        let bounds = Aabb::new(array_to_vector(grid.min), array_to_vector(grid.max));
+
+        result.density = Some(validated(assets.grids.get(&grid.file, bounds), "density")?);
     }
 
-    return scene
+    return Ok(result);
 }
 
-fn create_scene_and_add_camera(object_map : &HashMap<String, Vec<JsonObject>>) -> Scene
+// Follows the chain of inherited materials, fields set closer to the object override the inherited ones
+pub fn resolve_material(reference : &JsonMaterialRef, materials : &MaterialLibrary) -> Result<JsonMaterial, String>
 {
-    let unwrapped_look_from;
-    let unwrapped_look_at;
-    let unwrapped_vup;
-    let mut unwrapped_fov : f64 = 0.0;
-    let mut unwrapped_width : u32 = 0;
-    let mut unwrapped_height : u32 = 0;
+    let mut resolved = match reference
+    {
+        JsonMaterialRef::Named(name) => JsonMaterial { inherits : Some(name.clone()), ..Default::default() },
+        JsonMaterialRef::Inline(material) => (**material).clone()
+    };
 
-    let mut camera_look_from = Vector::new();
-    let mut camera_look_at = Vector::new();
-    let mut camera_vup = Vector::new();
+    let mut visited : Vec<String> = Vec::new();
 
-    if let Some(objects) =  object_map.get("camera")
+    while let Some(name) = resolved.inherits.take()
     {
-        if let Some(camera) = objects.first()
+        if visited.contains(&name)
+        { return Err(format!("materials inheriting from '{}' form a cycle", name)); }
+
+        let parent = match materials.get(&name)
         {
-            if let JsonObject::JsonCamera 
-            {
-                look_from,
-                look_at,
-                vup,
-                fov,
-                resolution,
-            } = camera
-            {
-                unwrapped_look_from = look_from.unwrap();
-                unwrapped_look_at = look_at.unwrap();
-                unwrapped_vup = vup.unwrap();
-                unwrapped_fov = fov.unwrap();
-                unwrapped_width = resolution.width;
-                unwrapped_height = resolution.height;
-
-                camera_look_from.set_vector(unwrapped_look_from[0], unwrapped_look_from[1], unwrapped_look_from[2]);
-                camera_look_at.set_vector(unwrapped_look_at[0], unwrapped_look_at[1], unwrapped_look_at[2]);
-                camera_vup.set_vector(unwrapped_vup[0], unwrapped_vup[1], unwrapped_vup[2]);
-            }
-        }
+            Some(parent) => parent,
+            None => return Err(format!("unknown material '{}'", name))
+        };
+
+        resolved = JsonMaterial
+        {
+            inherits : parent.inherits.clone(),
+            material_type : resolved.material_type.or_else(|| parent.material_type.clone()),
+            color : resolved.color.or_else(|| parent.color.clone()),
+            emission : resolved.emission.or(parent.emission),
+            medium : resolved.medium.or_else(|| parent.medium.clone()),
+            ior : resolved.ior.or_else(|| parent.ior.clone()),
+            texture : resolved.texture.or_else(|| parent.texture.clone())
+        };
+
+        visited.push(name);
     }
-    let cam = Camera::new(camera_look_at, camera_look_from, camera_vup, unwrapped_fov, unwrapped_width, unwrapped_height);
-    let scene = Scene{ objects: vec![], camera : cam };
 
-    return scene
+    return Ok(resolved);
 }
 
-fn scene_add_lights(object_map : &HashMap<String, Vec<JsonObject>>, mut scene : Scene) -> Scene
+fn json_to_material(reference : &JsonMaterialRef, materials : &MaterialLibrary, assets : &SceneAssets) -> Result<Material, SceneIssue>
 {
-    if let Some(objects) =  object_map.get("light") {
-        for object in objects 
+    let material = validated(resolve_material(reference, materials).ok(), "inherits")?;
+    let mat_type = string_to_mat_type(&validated(material.material_type, "type")?);
+
+    let color = validated(material.color.as_deref().and_then(string_to_hex_int), "color")?;
+    let mut result = Material::new(color, mat_type, material.emission.unwrap_or(0.0));
+    result.medium = material.medium.as_ref().map(|medium| json_to_medium(medium, assets)).transpose().map_err(|issue| nested("medium", issue))?;
+    result.texture = material.texture.as_deref().map(|texture| validated(assets.textures.get(texture), "texture")).transpose()?;
+
+    if let Some(ior) = &material.ior
+    {
+        result.ior = match ior
         {
-            if let JsonObject::JsonLight 
-            {
-                center,
-                radius,
-                emission
-            } = object
-            {
-                let unwrapped_center = center.unwrap();
-                let unwrapped_radius = radius.unwrap();
-                let unwrapped_emission = emission.unwrap();
-
-                let mut light_center = Vector::new();
-                light_center.set_vector(unwrapped_center[0], unwrapped_center[1], unwrapped_center[2]);
-
-                let light = sphere_light_builder(light_center, unwrapped_radius, unwrapped_emission);
-
-                scene.add(light);
-            }
-        }
+            JsonIor::Constant(n) => Ior::Constant(*n),
+            JsonIor::Model(JsonIorModel::Cauchy { a, b }) => Ior::Cauchy { a : *a, b : *b },
+            JsonIor::Model(JsonIorModel::Sellmeier { b, c }) => Ior::Sellmeier { b : *b, c : *c }
+        };
     }
-    
-    return scene
+
+    return Ok(result);
 }
 
-fn scene_builder(object_map : HashMap<String, Vec<JsonObject>>) -> Scene
+pub fn json_to_sdf_node(node : &JsonSdfNode) -> SdfNode
 {
-    //let mut scene = Scene{ objects: vec![] , camera : Camera};
-    let mut scene = create_scene_and_add_camera(&object_map);
-    scene = scene_add_planes(&object_map, scene);
-    scene = scene_add_spheres(&object_map, scene);
-    scene = scene_add_lights(&object_map, scene);
-    
-    return scene
+    let origin = [0.0, 0.0, 0.0];
+
+    match node
+    {
+        JsonSdfNode::Sphere { center, radius } =>
+            SdfNode::Sphere { center : array_to_vector(center.unwrap_or(origin)), radius : *radius },
+
+        JsonSdfNode::Box { center, size, rounding } => SdfNode::Cuboid
+        {
+            center : array_to_vector(center.unwrap_or(origin)),
+            half_size : array_to_vector(*size),
+            rounding : rounding.unwrap_or(0.0)
+        },
+
+        JsonSdfNode::Torus { center, major_radius, minor_radius } => SdfNode::Torus
+        {
+            center : array_to_vector(center.unwrap_or(origin)),
+            major_radius : *major_radius,
+            minor_radius : *minor_radius
+        },
+
+        JsonSdfNode::Cylinder { center, radius, height } => SdfNode::Cylinder
+        {
+            center : array_to_vector(center.unwrap_or(origin)),
+            radius : *radius,
+            half_height : height * 0.5
+        },
+
+        JsonSdfNode::Mandelbulb { center, scale, power, iterations } => SdfNode::Mandelbulb
+        {
+            center : array_to_vector(center.unwrap_or(origin)),
+            scale : scale.unwrap_or(1.0),
+            power : power.unwrap_or(8.0),
+            iterations : iterations.unwrap_or(12)
+        },
+
+        JsonSdfNode::Union { left, right } =>
+            SdfNode::Union(Box::new(json_to_sdf_node(left)), Box::new(json_to_sdf_node(right))),
+
+        JsonSdfNode::Intersection { left, right } =>
+            SdfNode::Intersection(Box::new(json_to_sdf_node(left)), Box::new(json_to_sdf_node(right))),
+
+        JsonSdfNode::Difference { left, right } =>
+            SdfNode::Difference(Box::new(json_to_sdf_node(left)), Box::new(json_to_sdf_node(right))),
+
+        JsonSdfNode::SmoothUnion { left, right, smoothness } => SdfNode::SmoothUnion
+        {
+            left : Box::new(json_to_sdf_node(left)),
+            right : Box::new(json_to_sdf_node(right)),
+            smoothness : *smoothness
+        },
+
+        JsonSdfNode::Repeat { child, period } =>
+            SdfNode::Repeat { child : Box::new(json_to_sdf_node(child)), period : array_to_vector(*period) },
+
+        JsonSdfNode::Twist { child, rate } =>
+            SdfNode::Twist { child : Box::new(json_to_sdf_node(child)), rate : *rate },
+
+        JsonSdfNode::Translate { child, offset } =>
+            SdfNode::Translate { child : Box::new(json_to_sdf_node(child)), offset : array_to_vector(*offset) }
+    }
 }
 
-pub fn get_scenes_folder() -> String
+// Builds a renderable object, None for entries such as the camera or settings that are not objects
+fn build_object(object : &JsonObject, prototypes : &HashMap<String, Arc<dyn Body + Send + Sync>>,
+    materials : &MaterialLibrary, assets : &SceneAssets) -> Result<Option<Object>, SceneIssue>
 {
-    let mut path_to_exe = match  std::env::current_exe()
+    let build_material = |reference : &JsonMaterialRef| json_to_material(reference, materials, assets).map_err(|issue| nested("material", issue));
+
+    let built = match object
     {
-        Ok(path) =>
+        JsonObject::JsonPlane { normal, d, material } =>
+            plane_builder(array_to_vector(validated(*normal, "normal")?), validated(*d, "d")?, build_material(material)?),
+
+        JsonObject::JsonSphere { center, radius, material } =>
+            sphere_builder(array_to_vector(validated(*center, "center")?), validated(*radius, "radius")?, build_material(material)?),
+
+        JsonObject::JsonBox { min, max, rotation, material } =>
+            box_builder(array_to_vector(validated(*min, "min")?), array_to_vector(validated(*max, "max")?), rotation.map(array_to_vector),
+                build_material(material)?),
+
+        JsonObject::JsonCylinder { base, axis, radius, height, material } =>
+            cylinder_builder(array_to_vector(validated(*base, "base")?), array_to_vector(validated(*axis, "axis")?),
+                validated(*radius, "radius")?, validated(*height, "height")?, build_material(material)?),
+
+        JsonObject::JsonCone { base, axis, radius, height, material } =>
+            cone_builder(array_to_vector(validated(*base, "base")?), array_to_vector(validated(*axis, "axis")?),
+                validated(*radius, "radius")?, validated(*height, "height")?, build_material(material)?),
+
+        JsonObject::JsonDisk { center, normal, radius, material } =>
+            disk_builder(array_to_vector(validated(*center, "center")?), array_to_vector(validated(*normal, "normal")?),
+                validated(*radius, "radius")?, build_material(material)?),
+
+        JsonObject::JsonTorus { center, axis, major_radius, minor_radius, material } =>
+            torus_builder(array_to_vector(validated(*center, "center")?), array_to_vector(validated(*axis, "axis")?),
+                validated(*major_radius, "major_radius")?, validated(*minor_radius, "minor_radius")?, build_material(material)?),
+
+        JsonObject::JsonQuad { corner, u, v, two_sided, material } =>
+            quad_builder(array_to_vector(validated(*corner, "corner")?), array_to_vector(validated(*u, "u")?),
+                array_to_vector(validated(*v, "v")?), two_sided.unwrap_or(false), build_material(material)?),
+
+        JsonObject::JsonMesh { vertices, triangles, normals, uvs, colors, material } =>
+            mesh_builder(vertices.iter().map(|v| array_to_vector(*v)).collect(), triangles.clone(),
+                normals.as_ref().map(|n| n.iter().map(|v| array_to_vector(*v)).collect()), uvs.clone(),
+                colors.as_ref().map(|c| c.iter().map(|[r, g, b]| Color::new_rgb(*r, *g, *b)).collect()),
+                build_material(material)?),
+
+        JsonObject::JsonSdf { root, bounds, step_scale, material } =>
+            validated(sdf_builder(json_to_sdf_node(root), bounds.as_ref().map(|b| Aabb::new(array_to_vector(b.min), array_to_vector(b.max))),
+                step_scale.unwrap_or(1.0), build_material(material)?), "bounds")?,
+
+        JsonObject::JsonCsg { operation, left, right, material } =>
         {
-            path
+            let left_object = validated(build_object(left, prototypes, materials, assets).map_err(|issue| nested("left", issue))?, "left")?;
+            let right_object = validated(build_object(right, prototypes, materials, assets).map_err(|issue| nested("right", issue))?, "right")?;
+            let csg_material = material.as_ref().map(build_material).transpose()?;
+
+            csg_builder(validated(string_to_csg_operation(operation), "operation")?, left_object, right_object, csg_material)
         }
-        Err(_) => 
+
+        JsonObject::JsonInstance { prototype, translate, rotate, scale, material } =>
         {
-            panic!("Error occured while getting path!");
+            let shared_body = validated(prototypes.get(prototype), "prototype")?.clone();
+
+            let transform = Matrix4::from_transform(
+                array_to_vector(translate.unwrap_or([0.0, 0.0, 0.0])),
+                array_to_vector(rotate.unwrap_or([0.0, 0.0, 0.0])),
+                array_to_vector(scale.unwrap_or([1.0, 1.0, 1.0])));
+
+            validated(instance_builder(shared_body, transform, material.as_ref().map(build_material).transpose()?), "scale")?
         }
+
+        JsonObject::JsonLight { center, radius, emission } =>
+            sphere_light_builder(array_to_vector(validated(*center, "center")?), validated(*radius, "radius")?,
+                validated(*emission, "emission")?),
+
+        JsonObject::JsonPrototype { .. } | JsonObject::JsonInclude { .. } | JsonObject::JsonCamera { .. } |
+        JsonObject::JsonAtmosphere { .. } | JsonObject::JsonSettings { .. } => return Ok(None)
     };
-    path_to_exe.pop();
-    let path_string = path_to_exe.to_str().unwrap();
 
-    //println!("{}", path_string);
+    return Ok(Some(built));
+}
 
-    
-    let scenes_path : String;
-    if cfg!(target_os = "windows")
-    { scenes_path = format!("{}{}", path_string, "\\scenes\\"); }
-    else
-    { scenes_path = format!("{}{}", path_string, "/scenes/"); };
-    
-    //let scenes_path =format!("{}{}", path_string, "\\scenes\\" );
+// Prototypes are built in order, so a prototype may instance the ones defined before it
+fn build_prototypes(objects : &[JsonObject], materials : &MaterialLibrary, assets : &SceneAssets) -> Result<HashMap<String, Arc<dyn Body + Send + Sync>>, SceneIssue>
+{
+    let mut prototypes : HashMap<String, Arc<dyn Body + Send + Sync>> = HashMap::new();
 
-    //println!("{}", scenes_path);
+    for (index, object) in objects.iter().enumerate()
+    {
+        if let JsonObject::JsonPrototype { name, object } = object
+        {
+            let built = build_object(object, &prototypes, materials, assets).map_err(|issue| at_object(index, nested("object", issue)))?;
 
-    return scenes_path;
+            if let Some(built) = built
+            { prototypes.insert(name.clone(), Arc::from(built.body)); }
+        }
+    }
+
+    return Ok(prototypes);
 }
 
-fn deserialize_json(scene_name : &String) -> Result<HashMap<String, Vec<JsonObject>>, Box<dyn std::error::Error>>
+fn at_object(index : usize, mut issue : SceneIssue) -> SceneIssue
 {
-    let scenes_path = get_scenes_folder();
-    let file_path = format!("{}{}", scenes_path, scene_name);
-
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-
-    let config: Config = serde_json::from_reader(reader).map_err(|err| Box::new(err) as Box<dyn std::error::Error>)?;
-
-    let mut object_map: HashMap<String, Vec<JsonObject>> = HashMap::new();
-
-    for object in config.objects {
-        match &object {
-            JsonObject::JsonPlane { material, .. } => 
-            {
-                object_map
-                    .entry("plane".to_string())
-                    .or_insert(Vec::new())
-                    .push(object.clone());
-                object_map
-                    .entry(material.material_type.clone())
-                    .or_insert(Vec::new())
-                    .push(object);
-            }
-            JsonObject::JsonSphere { material, .. } => 
-            {
-                object_map
-                    .entry("sphere".to_string())
-                    .or_insert(Vec::new())
-                    .push(object.clone());
-                object_map
-                    .entry(material.material_type.clone())
-                    .or_insert(Vec::new())
-                    .push(object);
-            }
-            JsonObject::JsonLight { .. } => 
-            {
-                object_map
-                    .entry("light".to_string())
-                    .or_insert(Vec::new())
-                    .push(object);
-            }
-            JsonObject::JsonCamera { .. } => 
-            {
-                object_map
-                    .entry("camera".to_string())
-                    .or_insert(Vec::new())
-                    .push(object);
-            }
-        }
+    issue.object = Some(index);
+
+    return issue;
+}
+
+// Objects are added in the order of the file, so the scene's objects and lights are indexed like the file's
+fn scene_add_objects(objects : &[JsonObject], materials : &MaterialLibrary, assets : &SceneAssets, mut scene : Scene)
+    -> Result<Scene, SceneIssue>
+{
+    let prototypes = build_prototypes(objects, materials, assets)?;
+
+    for (index, object) in objects.iter().enumerate()
+    {
+        if let Some(built) = build_object(object, &prototypes, materials, assets).map_err(|issue| at_object(index, issue))?
+        { scene.add(built); }
     }
+
+    return Ok(scene)
+}
+
+fn create_scene_and_add_camera(objects : &[JsonObject]) -> Result<Scene, SceneIssue>
+{
+    let camera = objects.iter().find_map(|object| match object
+    {
+        JsonObject::JsonCamera { look_from, look_at, vup, fov, resolution } => Some((look_from, look_at, vup, fov, resolution)),
+        _ => None
+    });
+
+    let (look_from, look_at, vup, fov, resolution) = validated(camera, "camera")?;
+    let camera_field = |value, field| validated(value, field).map_err(|issue| nested("camera", issue));
+
+    let cam = Camera::new(array_to_vector(camera_field(*look_at, "look_at")?), array_to_vector(camera_field(*look_from, "look_from")?),
+        array_to_vector(camera_field(*vup, "vup")?), validated(*fov, "camera.fov")?, resolution.width, resolution.height);
+    let scene = Scene::new(cam);
+
+    return Ok(scene)
+}
+
+fn settings_builder(objects : &[JsonObject]) -> Result<RenderSettings, SceneIssue>
+{
+    let mut settings = RenderSettings::default();
+
+    // Only the first settings entry is used, like the first camera and atmosphere
+    let first = objects.iter().find(|object| matches!(object, JsonObject::JsonSettings { .. }));
+
+    if let Some(JsonObject::JsonSettings { spp, time_limit, min_bounces, max_bounces, spectral, integrator, sampler,
+        filter, seed, threads, crop, outputs, caustic_photons, caustic_radius, ao_radius }) = first
+    {
+        if let Some(samples) = spp
+        { settings.spp = *samples; }
+
+        if let Some(seconds) = time_limit
+        { settings.time_limit = Duration::try_from_secs_f64(*seconds).ok(); }
+
+        if let Some(min) = min_bounces
+        { settings.min_bounces = *min; }
+
+        if let Some(max) = max_bounces
+        { settings.max_bounces = *max; }
+
+        if let Some(spectral_mode) = spectral
+        { settings.spectral = *spectral_mode; }
+
+        if let Some(name) = integrator
+        { settings.integrator = validated(Integrator::from_name(name), "integrator")?; }
+
+        if let Some(name) = sampler
+        { settings.sampler = validated(Sampler::from_name(name), "sampler")?; }
+
+        if let Some(name) = filter
+        { settings.filter = validated(Filter::from_name(name), "filter")?; }
+
+        settings.seed = *seed;
+
+        if let Some(count) = threads
+        { settings.threads = *count; }
+
+        if let Some([x, y, width, height]) = crop
+        { settings.crop = Some(CropWindow { x : *x, y : *y, width : *width, height : *height }); }
+
+        if let Some(paths) = outputs
+        { settings.outputs = paths.clone(); }
+
+        if let Some(photons) = caustic_photons
+        { settings.caustic_photons = *photons; }
+
+        if let Some(radius) = caustic_radius
+        { settings.caustic_radius = *radius; }
+
+        if let Some(radius) = ao_radius
+        { settings.ao_radius = *radius; }
+    }
+
+    return Ok(settings)
+}
+
+fn scene_add_atmosphere(objects : &[JsonObject], assets : &SceneAssets, mut scene : Scene) -> Result<Scene, SceneIssue>
+{
+    let medium = objects.iter().find_map(|object| match object
+    {
+        JsonObject::JsonAtmosphere { medium } => Some(medium),
+        _ => None
+    });
+
+    scene.atmosphere = medium.map(|medium| json_to_medium(medium, assets)).transpose().map_err(|issue| nested("medium", issue))?;
+
+    return Ok(scene)
+}
+
+fn scene_builder(objects : Vec<JsonObject>, materials : MaterialLibrary, assets : &SceneAssets) -> Result<Scene, SceneIssue>
+{
+    //let mut scene = Scene{ objects: vec![] , camera : Camera};
+    let mut scene = create_scene_and_add_camera(&objects)?;
+    scene = scene_add_objects(&objects, &materials, assets, scene)?;
+    scene = scene_add_atmosphere(&objects, assets, scene)?;
     
-    Ok(object_map)
+    return Ok(scene)
+}
+
+// Issues refer to objects of the expanded scene, report them where they were written
+fn locate(mut issue : SceneIssue, origins : &[ObjectOrigin]) -> SceneIssue
+{
+    if let Some(index) = issue.object
+    {
+        let origin = &origins[index];
+
+        issue.file = origin.file.clone();
+        issue.object = Some(origin.index);
+    }
+
+    return issue;
+}
+
+fn deserialize_json(scene_path : &Path, asset_paths : &[PathBuf], assets : &mut SceneAssets)
+    -> Result<(Vec<JsonObject>, MaterialLibrary, Vec<ObjectOrigin>), SceneError>
+{
+    let expanded = expand_scene(scene_path, asset_paths)?;
+    let mut issues = expanded.issues;
+    assets.missing = expanded.missing;
+
+    for issue in validate_scene(&expanded.objects, &expanded.materials, assets)
+    { issues.push(locate(issue, &expanded.origins)); }
+
+    if !issues.is_empty()
+    { return Err(SceneError::Invalid(issues)); }
+
+    Ok((expanded.objects, expanded.materials, expanded.origins))
+}
+
+/// Loads the scene file at `scene_path` together with the render settings it asks for. Files it
+/// references are searched next to it and then in `asset_paths`.
+pub fn get_scene_from_json(scene_path : &Path, asset_paths : &[PathBuf]) -> Result<(Scene, RenderSettings), SceneError>
+{
+    // Files loaded while validating, the built scene keeps the ones it uses
+    let mut assets = SceneAssets::default();
+    let (objects, materials, origins) = deserialize_json(scene_path, asset_paths, &mut assets)?;
+    let invalid = |issue| SceneError::Invalid(vec![locate(issue, &origins)]);
+
+    let settings = settings_builder(&objects).map_err(invalid)?;
+    let scene = scene_builder(objects, materials, &assets).map_err(invalid)?;
+
+    // Media are only known once the scene is built
+    let issues = validate_settings(&scene, &settings);
+
+    if ! issues.is_empty()
+    { return Err(SceneError::Invalid(issues)); }
+
+    Ok((scene, settings))
 }
 
-pub fn get_scene_from_json(scene_name : &String) -> Scene
+#[cfg(test)]
+mod tests
 {
-    let object_map_result = deserialize_json(scene_name);
-    let object_map = object_map_result.unwrap();
+    use super::*;
+
+    const CAMERA : &str = r#"{ "type" : "camera", "look_from" : [0, 0, -5], "look_at" : [0, 0, 0], "vup" : [0, 1, 0], "fov" : 60,
+        "resolution" : { "width" : 64, "height" : 48 } }"#;
+    const PROTOTYPE : &str = r##"{ "type" : "prototype", "name" : "ball",
+        "object" : { "type" : "sphere", "center" : [0, 0, 0], "radius" : 1, "material" : { "type" : "diffuse", "color" : "#FF0000" } } }"##;
+
+    fn objects(json : &[&str]) -> Vec<JsonObject>
+    { return json.iter().map(|object| serde_json::from_str(object).unwrap()).collect(); }
+
+    #[test]
+    fn every_scene_file_builds()
+    {
+        let folder = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        let mut paths : Vec<PathBuf> = std::fs::read_dir(&folder).unwrap().map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json")).collect();
+        paths.sort();
 
-    scene_builder(object_map)
-}
\ No newline at end of file
+        assert!(!paths.is_empty());
+
+        for path in paths
+        {
+            if let Err(error) = get_scene_from_json(&path, &[])
+            { panic!("{}: {}", path.display(), error); }
+        }
+    }
+
+    #[test]
+    fn changed_grid_is_loaded_again()
+    {
+        let folder = std::env::temp_dir().join(format!("path_tracer_grid_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+
+        let scene = format!(r##"{{ "objects" : [ {}, {{ "type" : "sphere", "center" : [0, 0, 0], "radius" : 1, "material" :
+            {{ "type" : "interface", "color" : "#FFFFFF", "medium" : {{ "absorption" : [0, 0, 0], "scattering" : [1, 1, 1],
+            "density" : {{ "file" : "grid.vgrid", "min" : [-1, -1, -1], "max" : [1, 1, 1] }} }} }} }} ] }}"##, CAMERA);
+        std::fs::write(folder.join("scene.json"), scene).unwrap();
+
+        // Every load of the scene reads the grid file as it is then
+        for density in [1.0_f32, 3.0]
+        {
+            let grid : Vec<u8> = [&b"VGRD"[..], &1_u32.to_le_bytes(), &1_u32.to_le_bytes(), &1_u32.to_le_bytes(), &density.to_le_bytes()].concat();
+            std::fs::write(folder.join("grid.vgrid"), grid).unwrap();
+
+            let (scene, _) = get_scene_from_json(&folder.join("scene.json"), &[]).unwrap();
+            let medium = scene.objects[0].body.get_material().medium.unwrap();
+
+            assert_eq!(medium.density.unwrap().max_density(), density as f64);
+        }
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn validated_instances_build()
+    {
+        for scale in ["[1, 1, 1]", "[2, 0.5, -1]", "[0.01, 0.01, 0.01]"]
+        {
+            let instance = format!(r#"{{ "type" : "instance", "prototype" : "ball", "scale" : {} }}"#, scale);
+            let scene = objects(&[CAMERA, PROTOTYPE, &instance]);
+
+            let mut assets = SceneAssets::default();
+
+            assert!(validate_scene(&scene, &MaterialLibrary::new(), &mut assets).is_empty());
+            assert!(scene_builder(scene, MaterialLibrary::new(), &assets).is_ok());
+        }
+    }
+
+    #[test]
+    fn nearly_singular_instance_is_reported()
+    {
+        let scene = objects(&[CAMERA, PROTOTYPE, r#"{ "type" : "instance", "prototype" : "ball", "scale" : [1e-5, 1e-5, 1e-5] }"#]);
+        let mut assets = SceneAssets::default();
+        let issues = validate_scene(&scene, &MaterialLibrary::new(), &mut assets);
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].object, issues[0].field.as_str()), (Some(2), "scale"));
+
+        // Building without validation reports the instance instead of panicking
+        let error = scene_builder(scene, MaterialLibrary::new(), &assets).err().unwrap();
+        assert_eq!((error.object, error.field.as_str()), (Some(2), "scale"));
+    }
+}
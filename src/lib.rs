@@ -0,0 +1,102 @@
+//! Physically based path tracer.
+//!
+//! Scenes are either loaded from scene files with [`get_scene_from_json`] or built in code by adding
+//! objects made with the functions in [`object_builder`] to a [`Scene`]. [`render`] renders a scene
+//! with the given [`RenderSettings`] into an in-memory [`Image`] which can be read pixel by pixel or
+//! written to a PPM, PNG or JPEG file. [`render_with_progress`] additionally reports progress and can
+//! be stopped early through a [`CancelToken`].
+//!
+//! Scene files are read into a [`Config`] by [`read_scene_file`], which describes the scene with
+//! [`JsonObject`] entries and a [`MaterialLibrary`] and can be edited and written back with
+//! [`write_scene_file`].
+//!
+//! ```
+//! use path_tracer::{render, Camera, Filter, Material, MatType, RenderSettings, Scene, Vector};
+//! use path_tracer::object_builder::{plane_builder, sphere_builder, sphere_light_builder};
+//!
+//! let look_from = Vector { x : 0.0, y : 1.0, z : 5.0 };
+//! let look_at = Vector { x : 0.0, y : 1.0, z : 0.0 };
+//! let vup = Vector { x : 0.0, y : 1.0, z : 0.0 };
+//! let mut scene = Scene::new(Camera::new(look_at, look_from, vup, 40.0, 160, 120));
+//!
+//! scene.add(plane_builder(vup, 0.0, Material::new(0xDDDDDD, MatType::Diffuse, 0.0)));
+//! scene.add(sphere_builder(look_at, 1.0, Material::new(0xCC4433, MatType::Diffuse, 0.0)));
+//! scene.add(sphere_light_builder(Vector { x : 2.0, y : 4.0, z : 2.0 }, 0.5, 40.0));
+//!
+//! let mut settings = RenderSettings::new(8);
+//! settings.filter = Filter::Tent;
+//! settings.seed = Some(7);
+//!
+//! let image = render(&scene, &settings).expect("Starting the render failed");
+//! let top_left = image.pixel(0, 0);
+//!
+//! // Nothing lies above the camera's level view on the left, so the top left corner is black
+//! assert_eq!(top_left.max_component(), 0.0);
+//!
+//! image.write(&std::env::temp_dir().join("spheres.png")).expect("Writing the render failed");
+//! ```
+
+#![warn(missing_docs)]
+
+extern crate rand;
+
+// Building scenes in code
+mod vector;
+mod matrix;
+mod aabb;
+mod ray;
+mod color;
+mod material;
+mod texture;
+mod medium;
+mod voxel_grid;
+mod spectrum;
+mod object;
+mod quadric;
+mod mesh;
+mod instance;
+mod csg;
+mod sdf;
+pub mod object_builder;
+mod camera;
+mod scene;
+
+// Rendering
+mod render;
+mod render_settings;
+mod progress;
+mod cancel;
+mod image;
+
+// Scene files
+mod json_utils;
+mod scene_format;
+mod scene_validation;
+
+mod random;
+mod bvh;
+mod lights;
+mod bdpt;
+mod photon_map;
+mod debug;
+mod scene_include;
+mod gltf_import;
+mod mesh_import;
+
+pub use crate::camera::Camera;
+pub use crate::cancel::CancelToken;
+pub use crate::color::Color;
+pub use crate::csg::CsgOperation;
+pub use crate::image::{Image, OUTPUT_EXTENSIONS};
+pub use crate::json_utils::{get_scene_from_json, Config, JsonBounds, JsonIor, JsonIorModel, JsonMaterial, JsonMaterialRef, JsonMedium,
+    JsonObject, JsonResolution, JsonSdfNode, JsonVoxelGrid, MaterialLibrary};
+pub use crate::material::{Ior, MatType, Material};
+pub use crate::medium::Medium;
+pub use crate::object::Object;
+pub use crate::progress::{ConsoleProgress, NoProgress, Progress, ProgressReporter};
+pub use crate::render::{render, render_with_progress, Integrator};
+pub use crate::render_settings::{CropWindow, Filter, RenderSettings, Sampler};
+pub use crate::scene::Scene;
+pub use crate::scene_format::{convert_scene_file, read_scene_file, write_scene_file, SceneFormat};
+pub use crate::scene_validation::{validate_settings, SceneError, SceneIssue};
+pub use crate::vector::Vector;
@@ -0,0 +1,149 @@
+use crate::color::Color;
+use crate::medium::Medium;
+use crate::object::{Body, Object};
+use crate::random;
+use crate::ray::Ray;
+use crate::render::diffuse_brdf;
+use crate::scene::Scene;
+use crate::spectrum::Wavelengths;
+use crate::vector::Vector;
+use std::f64::consts::FRAC_1_PI;
+
+// Direct light arriving at a point from a sampled point on an area light
+pub struct LightSample
+{
+    pub dir : Vector,
+    pub emission : f64,
+    // Attenuation by media along the shadow ray
+    pub transmittance : Color,
+    // Probability density with respect to solid angle, including the choice of the light
+    pub pdf : f64
+}
+
+// Ray leaving a point on a light, as used to start light paths
+pub struct EmissionSample<'a>
+{
+    pub object : &'a Object,
+    pub ray : Ray,
+    pub normal : Vector,
+    // Area density of the point, including the choice of the light
+    pub pdf_pos : f64,
+    // Solid angle density of the direction
+    pub pdf_dir : f64
+}
+
+// Emission leaving a surface towards the opposite of ray_dir
+pub fn emitted(body : &(dyn Body + Send + Sync), normal : Vector, ray_dir : Vector) -> f64
+{
+    if body.two_sided() || normal.dot_product(ray_dir) < 0.0
+    { return body.get_material().emission; }
+
+    return 0.0;
+}
+
+// Picks a light uniformly, samples a point on it and checks its visibility from point, which lies in medium.
+// The shadow rays are added to rays
+pub fn sample_light(scene : &Scene, point : Vector, medium : Option<Medium>, wavelengths : Option<Wavelengths>, rays : &mut u64)
+    -> Option<LightSample>
+{
+    let light = pick_light(scene)?;
+    let light_count = scene.lights.len();
+
+    let (sample, pdf) = light.body.sample_from(point, random::gen_num(), random::gen_num())?;
+
+    let to_light = sample.point - point;
+    let distance = to_light.dot_product(to_light).sqrt();
+    let dir = to_light * (1.0 / distance);
+
+    let emission = emitted(light.body.as_ref(), sample.normal, dir);
+
+    if emission <= 0.0
+    { return None; }
+
+    // Shadow ray, the light itself is hit at the sampled distance
+    let shadow_ray = Ray { origin : point, dest : dir };
+    let transmittance = scene.transmittance(shadow_ray, distance * (1.0 - 1e-6), medium, wavelengths, rays)?;
+
+    return Some(LightSample { dir, emission, transmittance, pdf : pdf / light_count as f64 });
+}
+
+// Density with which sample_light would have produced a ray from origin hitting the object at point
+pub fn light_pdf(scene : &Scene, object : &Object, origin : Vector, point : Vector, normal : Vector) -> f64
+{
+    if object.body.get_material().emission <= 0.0 || object.body.area().is_none()
+    { return 0.0; }
+
+    return object.body.pdf_from(origin, point, normal) / scene.lights.len() as f64;
+}
+
+pub fn power_heuristic(pdf : f64, other_pdf : f64) -> f64
+{
+    let sq_pdf = pdf * pdf;
+    let sq_other = other_pdf * other_pdf;
+
+    if sq_pdf + sq_other <= 0.0
+    { return 0.0; }
+
+    return sq_pdf / (sq_pdf + sq_other);
+}
+
+fn pick_light(scene : &Scene) -> Option<&Object>
+{
+    if scene.lights.is_empty()
+    { return None; }
+
+    let light_count = scene.lights.len();
+    let light_num = ((random::gen_num() * light_count as f64) as usize).min(light_count - 1);
+
+    return Some(&scene.objects[scene.lights[light_num]]);
+}
+
+// Density of a point on the object as the start of a light path
+pub fn light_area_pdf(scene : &Scene, object : &Object) -> f64
+{
+    let area = match object.body.area()
+    {
+        Some(area) if area > 0.0 && object.body.get_material().emission > 0.0 => area,
+        _ => return 0.0
+    };
+
+    return 1.0 / (area * scene.lights.len() as f64);
+}
+
+// Density of dir leaving a light, which emits cosine weighted into either hemisphere of two sided bodies
+pub fn emission_pdf(body : &(dyn Body + Send + Sync), normal : Vector, dir : Vector) -> f64
+{
+    let cos_theta = normal.dot_product(dir);
+
+    if body.two_sided()
+    { return 0.5 * cos_theta.abs() * FRAC_1_PI; }
+
+    return cos_theta.max(0.0) * FRAC_1_PI;
+}
+
+// Picks a light uniformly, a point on it uniformly by area and a direction by emission_pdf
+pub fn sample_emission(scene : &Scene) -> Option<EmissionSample<'_>>
+{
+    let light = pick_light(scene)?;
+    let sample = light.body.sample_surface(random::gen_num(), random::gen_num())?;
+    let pdf_pos = light_area_pdf(scene, light);
+
+    if pdf_pos <= 0.0
+    { return None; }
+
+    let side =
+        if light.body.two_sided() && random::gen_num() < 0.5
+        { sample.normal * -1.0 }
+        else
+        { sample.normal };
+
+    let mut ray = Ray { origin : sample.point, dest : side };
+    diffuse_brdf(side, &mut ray);
+
+    let pdf_dir = emission_pdf(light.body.as_ref(), sample.normal, ray.dest);
+
+    if pdf_dir <= 0.0
+    { return None; }
+
+    return Some(EmissionSample { object : light, ray, normal : sample.normal, pdf_pos, pdf_dir });
+}
@@ -1,25 +1,49 @@
-use crate::color::Color;
-use crate::image::Image;
-use crate::material::MatType;
-use crate::vector::Vector;
-extern crate rand;
-use crate::user_input::main_loop;
+use path_tracer::convert_scene_file;
+use crate::user_input::{main_loop, render_scene_file};
+use crate::cli_args::{parse_args, USAGE};
 
-mod vector;
-mod ray;
-mod color;
-mod material;
-mod object;
-mod image;
-mod render;
-mod scene;
-mod random;
-mod camera;
+// Interactive menu and command line, everything else lives in the library
 mod user_input;
-mod json_utils;
-mod object_builder;
+mod cli_args;
 
 fn main()
 {
-     main_loop();
+    let cli_args = match parse_args(std::env::args().skip(1))
+    {
+        Ok(args) => args,
+        Err(err) =>
+        {
+            eprintln!("{}\n{}", err, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some((input, output)) = &cli_args.convert
+    {
+        match convert_scene_file(input, output)
+        {
+            Ok(()) => println!("Converted {} to {}", input.display(), output.display()),
+            Err(err) =>
+            {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    match &cli_args.scene
+    {
+        Some(scene_path) =>
+        {
+            if let Err(err) = render_scene_file(scene_path, &cli_args)
+            {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+
+        None => main_loop(cli_args)
+    }
 }
\ No newline at end of file
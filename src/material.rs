@@ -1,30 +1,125 @@
+use std::sync::Arc;
 use crate::color::Color;
+use crate::medium::Medium;
+use crate::spectrum::SODIUM_D_LINE;
+use crate::texture::Texture;
 
+pub const DEFAULT_REFRACTION_INDEX : f64 = 1.52;
+
+/// How light scatters at a body's surface.
 #[derive(PartialEq, Copy, Clone)]
 pub enum MatType
 {
+    /// Lambertian reflector.
     Diffuse,
+    /// Perfect mirror.
     Specular,
-    Refractive
+    /// Smooth dielectric such as glass, refracting by the material's index of refraction.
+    Refractive,
+    /// Invisible surface that only bounds the body's medium.
+    Interface
+}
+
+/// Refractive index of a dielectric as a function of wavelength. RGB renders use the index at 589 nm,
+/// spectral renders disperse light by the wavelength dependent models.
+#[derive(Clone, Copy)]
+pub enum Ior
+{
+    /// The same index for all wavelengths.
+    Constant(f64),
+    /// Cauchy's equation n = a + b / λ² with λ in micrometres.
+    Cauchy
+    {
+        /// Index at long wavelengths.
+        a : f64,
+        /// Dispersion coefficient in square micrometres.
+        b : f64
+    },
+    /// Sellmeier's equation n² = 1 + Σ bᵢ λ² / (λ² - cᵢ) with λ in micrometres.
+    Sellmeier
+    {
+        /// Strengths of the three resonances.
+        b : [f64; 3],
+        /// Squared resonance wavelengths in square micrometres.
+        c : [f64; 3]
+    }
 }
 
-#[derive(Copy, Clone)]
+impl Ior
+{
+    pub(crate) fn at(&self, lambda_nm : f64) -> f64
+    {
+        let sq_lambda = (lambda_nm * 0.001).powi(2);
+
+        match self
+        {
+            Ior::Constant(n) => *n,
+            Ior::Cauchy { a, b } => a + b / sq_lambda,
+            Ior::Sellmeier { b, c } =>
+            {
+                let sum : f64 = (0..3).map(|i| b[i] * sq_lambda / (sq_lambda - c[i])).sum();
+
+                (1.0 + sum).max(1.0).sqrt()
+            }
+        }
+    }
+
+    // Index used when rendering in RGB
+    pub(crate) fn nominal(&self) -> f64
+    { return self.at(SODIUM_D_LINE); }
+
+    pub(crate) fn is_dispersive(&self) -> bool
+    { return ! matches!(self, Ior::Constant(..)); }
+}
+
+/// Surface and interior of a body.
+#[derive(Clone)]
 pub struct Material
 {
+    /// Linear reflectance of diffuse and specular surfaces and the tint of refractive ones.
     pub color : Color,
+    /// How light scatters at the surface.
     pub mat_type : MatType,
-    pub emission : f64
+    /// Radiance emitted by the surface, bodies with a positive emission are lights.
+    pub emission : f64,
+    /// Fills the body's interior, None for vacuum.
+    pub medium : Option<Medium>,
+    /// Only used by refractive materials.
+    pub ior : Ior,
+    // Multiplies the color at the body's surface coordinates
+    pub(crate) texture : Option<Arc<Texture>>
 }
 
 impl Material
 {
+    /// Material of the given type with a colour written as `0xRRGGBB`, whose channels are used as
+    /// linear reflectances. Bodies with a positive emission are lights, refractive materials get the
+    /// refractive index of glass and no medium or texture.
     pub fn new(clr : i32, _mat_type : MatType, mat_emission : f64) -> Self
     {
         Material
         {
             color : Color::new_hex(clr),
             mat_type : _mat_type,
-            emission : mat_emission
+            emission : mat_emission,
+            medium : None,
+            ior : Ior::Constant(DEFAULT_REFRACTION_INDEX),
+            texture : None
         }
     }
+
+    // Color at a point on the surface, the point's uv is only needed for textured materials
+    pub(crate) fn albedo(&self, uv : impl FnOnce() -> (f64, f64)) -> Color
+    {
+        return match &self.texture
+        {
+            Some(texture) =>
+            {
+                let (u, v) = uv();
+
+                self.color.mul_by_color(texture.sample(u, v))
+            }
+            None => self.color
+        };
+    }
 }
\ No newline at end of file
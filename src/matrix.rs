@@ -0,0 +1,262 @@
+use crate::vector::Vector;
+
+// Row major 4x4 matrix used for affine transformations
+#[derive(Clone, Copy)]
+pub struct Matrix4
+{
+    pub m : [[f64; 4]; 4]
+}
+
+impl Matrix4
+{
+    pub fn identity() -> Self
+    {
+        Matrix4
+        {
+            m : [[1.0, 0.0, 0.0, 0.0],
+                 [0.0, 1.0, 0.0, 0.0],
+                 [0.0, 0.0, 1.0, 0.0],
+                 [0.0, 0.0, 0.0, 1.0]]
+        }
+    }
+
+    pub fn translation(offset : Vector) -> Self
+    {
+        let mut result = Matrix4::identity();
+
+        result.m[0][3] = offset.x;
+        result.m[1][3] = offset.y;
+        result.m[2][3] = offset.z;
+
+        return result;
+    }
+
+    pub fn scaling(scale : Vector) -> Self
+    {
+        let mut result = Matrix4::identity();
+
+        result.m[0][0] = scale.x;
+        result.m[1][1] = scale.y;
+        result.m[2][2] = scale.z;
+
+        return result;
+    }
+
+    pub fn rotation_x(angle : f64) -> Self
+    {
+        let (sin, cos) = angle.sin_cos();
+        let mut result = Matrix4::identity();
+
+        result.m[1][1] = cos;
+        result.m[1][2] = -sin;
+        result.m[2][1] = sin;
+        result.m[2][2] = cos;
+
+        return result;
+    }
+
+    pub fn rotation_y(angle : f64) -> Self
+    {
+        let (sin, cos) = angle.sin_cos();
+        let mut result = Matrix4::identity();
+
+        result.m[0][0] = cos;
+        result.m[0][2] = sin;
+        result.m[2][0] = -sin;
+        result.m[2][2] = cos;
+
+        return result;
+    }
+
+    pub fn rotation_z(angle : f64) -> Self
+    {
+        let (sin, cos) = angle.sin_cos();
+        let mut result = Matrix4::identity();
+
+        result.m[0][0] = cos;
+        result.m[0][1] = -sin;
+        result.m[1][0] = sin;
+        result.m[1][1] = cos;
+
+        return result;
+    }
+
+    // Rotation around the x, then y and then z axis, angles are in degrees
+    pub fn rotation_degrees(angles : Vector) -> Self
+    {
+        let to_rad = std::f64::consts::PI / 180.0;
+
+        let rot_x = Matrix4::rotation_x(angles.x * to_rad);
+        let rot_y = Matrix4::rotation_y(angles.y * to_rad);
+        let rot_z = Matrix4::rotation_z(angles.z * to_rad);
+
+        return rot_z.mul(&rot_y).mul(&rot_x);
+    }
+
+    // Scales first, then rotates (in degrees) and finally translates
+    pub fn from_transform(translate : Vector, rotate : Vector, scale : Vector) -> Self
+    {
+        let translation = Matrix4::translation(translate);
+        let rotation = Matrix4::rotation_degrees(rotate);
+        let scaling = Matrix4::scaling(scale);
+
+        return translation.mul(&rotation).mul(&scaling);
+    }
+
+    pub fn mul(&self, other : &Matrix4) -> Matrix4
+    {
+        let mut result = Matrix4 { m : [[0.0; 4]; 4] };
+
+        for row in 0..4
+        {
+            for col in 0..4
+            {
+                result.m[row][col] = (0..4).map(|i| self.m[row][i] * other.m[i][col]).sum();
+            }
+        }
+
+        return result;
+    }
+
+    pub fn transpose(&self) -> Matrix4
+    {
+        let mut result = Matrix4 { m : [[0.0; 4]; 4] };
+
+        for row in 0..4
+        {
+            for col in 0..4
+            { result.m[row][col] = self.m[col][row]; }
+        }
+
+        return result;
+    }
+
+    // Inverse of an affine matrix, None if the linear part is singular
+    pub fn inverse(&self) -> Option<Matrix4>
+    {
+        let a = &self.m;
+
+        let det = a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1])
+                - a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0])
+                + a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0]);
+
+        if det.abs() < 1e-12
+        { return None; }
+
+        let inv_det = 1.0 / det;
+        let mut result = Matrix4::identity();
+
+        result.m[0][0] = (a[1][1] * a[2][2] - a[1][2] * a[2][1]) * inv_det;
+        result.m[0][1] = (a[0][2] * a[2][1] - a[0][1] * a[2][2]) * inv_det;
+        result.m[0][2] = (a[0][1] * a[1][2] - a[0][2] * a[1][1]) * inv_det;
+        result.m[1][0] = (a[1][2] * a[2][0] - a[1][0] * a[2][2]) * inv_det;
+        result.m[1][1] = (a[0][0] * a[2][2] - a[0][2] * a[2][0]) * inv_det;
+        result.m[1][2] = (a[0][2] * a[1][0] - a[0][0] * a[1][2]) * inv_det;
+        result.m[2][0] = (a[1][0] * a[2][1] - a[1][1] * a[2][0]) * inv_det;
+        result.m[2][1] = (a[0][1] * a[2][0] - a[0][0] * a[2][1]) * inv_det;
+        result.m[2][2] = (a[0][0] * a[1][1] - a[0][1] * a[1][0]) * inv_det;
+
+        // Inverse translation is the negated translation transformed by the inverted linear part
+        let translation = Vector { x : a[0][3], y : a[1][3], z : a[2][3] };
+        let inv_translation = result.transform_vector(translation);
+
+        result.m[0][3] = -inv_translation.x;
+        result.m[1][3] = -inv_translation.y;
+        result.m[2][3] = -inv_translation.z;
+
+        return Some(result);
+    }
+
+    // Scale factor of a linear part that only rotates, mirrors and scales uniformly, None if it distorts shapes
+    pub fn uniform_scale(&self) -> Option<f64>
+    {
+        let columns = [0, 1, 2].map(|col| Vector { x : self.m[0][col], y : self.m[1][col], z : self.m[2][col] });
+        let sq_scale = columns[0].dot_product(columns[0]);
+
+        // Loose enough for matrices read from single precision files
+        let tolerance = 1e-6 * sq_scale;
+
+        for i in 0..3
+        {
+            for j in 0..3
+            {
+                let expected = if i == j { sq_scale } else { 0.0 };
+
+                if (columns[i].dot_product(columns[j]) - expected).abs() > tolerance
+                { return None; }
+            }
+        }
+
+        return Some(sq_scale.sqrt());
+    }
+
+    pub fn transform_point(&self, point : Vector) -> Vector
+    {
+        let m = &self.m;
+
+        Vector
+        {
+            x : m[0][0] * point.x + m[0][1] * point.y + m[0][2] * point.z + m[0][3],
+            y : m[1][0] * point.x + m[1][1] * point.y + m[1][2] * point.z + m[1][3],
+            z : m[2][0] * point.x + m[2][1] * point.y + m[2][2] * point.z + m[2][3]
+        }
+    }
+
+    pub fn transform_vector(&self, vec : Vector) -> Vector
+    {
+        let m = &self.m;
+
+        Vector
+        {
+            x : m[0][0] * vec.x + m[0][1] * vec.y + m[0][2] * vec.z,
+            y : m[1][0] * vec.x + m[1][1] * vec.y + m[1][2] * vec.z,
+            z : m[2][0] * vec.x + m[2][1] * vec.y + m[2][2] * vec.z
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn vector(x : f64, y : f64, z : f64) -> Vector
+    { return Vector { x, y, z }; }
+
+    fn assert_close(actual : &Matrix4, expected : &Matrix4)
+    {
+        for row in 0..4
+        {
+            for col in 0..4
+            { assert!((actual.m[row][col] - expected.m[row][col]).abs() < 1e-12, "{:?} is not {:?}", actual.m, expected.m); }
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform()
+    {
+        let transform = Matrix4::from_transform(vector(1.0, -2.0, 3.0), vector(30.0, 45.0, 60.0), vector(2.0, 0.5, -3.0));
+        let inverse = transform.inverse().unwrap();
+
+        assert_close(&transform.mul(&inverse), &Matrix4::identity());
+        assert_close(&inverse.mul(&transform), &Matrix4::identity());
+
+        let point = vector(0.3, 4.0, -1.5);
+        let back = inverse.transform_point(transform.transform_point(point)) - point;
+        assert!(back.dot_product(back) < 1e-24);
+
+        // The inverse of a translation moves back, directions are not translated
+        let moved = Matrix4::translation(vector(1.0, 2.0, 3.0)).inverse().unwrap();
+        assert_eq!(moved.transform_point(vector(1.0, 2.0, 3.0)), vector(0.0, 0.0, 0.0));
+        assert_eq!(moved.transform_vector(vector(1.0, 2.0, 3.0)), vector(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn singular_transforms_have_no_inverse()
+    {
+        assert!(Matrix4::scaling(vector(1.0, 0.0, 1.0)).inverse().is_none());
+        // The determinant of 1e-15 is below the threshold even though no axis is zero
+        assert!(Matrix4::scaling(vector(1e-5, 1e-5, 1e-5)).inverse().is_none());
+        assert!(Matrix4::scaling(vector(1e-3, 1e-3, 1e-3)).inverse().is_some());
+    }
+}
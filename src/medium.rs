@@ -0,0 +1,253 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+use crate::color::Color;
+use crate::random;
+use crate::ray::Ray;
+use crate::spectrum::Wavelengths;
+use crate::vector::{orthonormal_sys, Vector};
+use crate::voxel_grid::VoxelGrid;
+
+/// Participating medium such as fog or smoke filling a body or the space outside of all bodies.
+#[derive(Clone)]
+pub struct Medium
+{
+    /// Absorption coefficient per channel in inverse scene units.
+    pub absorption : Color,
+    /// Scattering coefficient per channel in inverse scene units.
+    pub scattering : Color,
+    /// Henyey-Greenstein asymmetry, positive values scatter forward.
+    pub anisotropy : f64,
+    // Scales the coefficients at every point, the medium is homogeneous without it
+    pub(crate) density : Option<Arc<VoxelGrid>>
+}
+
+// Outcome of sampling a free flight distance along a ray
+pub struct MediumSample
+{
+    pub distance : f64,
+    // False if the ray reached max_distance without scattering
+    pub scattered : bool,
+    // Transmittance, times the scattering coefficient at scattering events, over the sampling density
+    pub weight : Color
+}
+
+fn average(color : Color) -> f64
+{ return (color.r + color.g + color.b) / 3.0; }
+
+// Exponentially distributed step for the given rate
+fn free_path(rate : f64) -> f64
+{ return -(1.0 - random::gen_num()).ln() / rate; }
+
+fn channel_transmittance(extinction : f64, distance : f64) -> f64
+{
+    // Avoids 0 * infinity for channels that do not attenuate
+    if extinction <= 0.0
+    { return 1.0; }
+
+    return (-extinction * distance).exp();
+}
+
+impl Medium
+{
+    /// Homogeneous medium, the anisotropy `g` is clamped to (-1, 1).
+    pub fn new(medium_absorption : Color, medium_scattering : Color, g : f64) -> Self
+    {
+        Medium
+        {
+            absorption : medium_absorption,
+            scattering : medium_scattering,
+            anisotropy : g.clamp(-0.99, 0.99),
+            density : None
+        }
+    }
+
+    // Coefficients at the path's wavelengths in spectral mode, unchanged otherwise
+    pub(crate) fn at_wavelengths(&self, wavelengths : Option<Wavelengths>) -> Medium
+    {
+        let mut result = self.clone();
+
+        if let Some(w) = wavelengths
+        {
+            result.absorption = w.upsample(self.absorption);
+            result.scattering = w.upsample(self.scattering);
+        }
+
+        return result;
+    }
+
+    pub(crate) fn extinction(&self) -> Color
+    { return self.absorption + self.scattering; }
+
+    // Fraction of light passing along the ray for distance, estimated by ratio tracking for voxel grids
+    pub(crate) fn transmittance(&self, ray : Ray, distance : f64) -> Color
+    {
+        return match &self.density
+        {
+            Some(grid) => self.ratio_tracking(grid, ray, distance),
+            None => self.homogeneous_transmittance(distance)
+        };
+    }
+
+    // Samples where the ray scatters before max_distance, by delta tracking for voxel grids
+    pub(crate) fn sample_distance(&self, ray : Ray, max_distance : f64) -> MediumSample
+    {
+        return match &self.density
+        {
+            Some(grid) => self.delta_tracking(grid, ray, max_distance),
+            None => self.sample_homogeneous(max_distance)
+        };
+    }
+
+    // Upper bound on the extinction of any channel anywhere in the grid
+    fn majorant(&self, grid : &VoxelGrid) -> f64
+    { return grid.max_density() * self.extinction().max_component(); }
+
+    fn ratio_tracking(&self, grid : &VoxelGrid, ray : Ray, distance : f64) -> Color
+    {
+        let mut result = Color::new_rgb(1.0, 1.0, 1.0);
+        let majorant = self.majorant(grid);
+
+        let (mut t, t_far) = match grid.bounds().clip(ray, distance)
+        {
+            Some(span) if majorant > 0.0 => span,
+            _ => return result
+        };
+
+        let extinction = self.extinction();
+
+        loop
+        {
+            t += free_path(majorant);
+
+            if t >= t_far
+            { return result; }
+
+            // Every tentative collision keeps the fraction of the majorant that is null
+            let local = extinction * (grid.density(ray.origin + ray.dest * t) / majorant);
+            result = result.mul_by_color(Color::new_rgb(1.0 - local.r, 1.0 - local.g, 1.0 - local.b));
+
+            if result.is_black()
+            { return result; }
+        }
+    }
+
+    // Weighted delta tracking, the weights keep it unbiased when the channels' coefficients differ
+    fn delta_tracking(&self, grid : &VoxelGrid, ray : Ray, max_distance : f64) -> MediumSample
+    {
+        let mut weight = Color::new_rgb(1.0, 1.0, 1.0);
+        let majorant = self.majorant(grid);
+
+        let (mut t, t_far) = match grid.bounds().clip(ray, max_distance)
+        {
+            Some(span) if majorant > 0.0 => span,
+            _ => return MediumSample { distance : max_distance, scattered : false, weight }
+        };
+
+        let extinction = self.extinction();
+
+        loop
+        {
+            t += free_path(majorant);
+
+            if t >= t_far
+            { return MediumSample { distance : max_distance, scattered : false, weight }; }
+
+            let density = grid.density(ray.origin + ray.dest * t);
+            let local_extinction = extinction * density;
+            let real_prob = average(local_extinction) / majorant;
+
+            if random::gen_num() < real_prob
+            {
+                weight = weight.mul_by_color(self.scattering * (density / average(local_extinction)));
+
+                return MediumSample { distance : t, scattered : true, weight };
+            }
+
+            let null = Color::new_rgb(majorant - local_extinction.r, majorant - local_extinction.g, majorant - local_extinction.b);
+            weight = weight.mul_by_color(null * (1.0 / (majorant * (1.0 - real_prob))));
+        }
+    }
+
+    fn homogeneous_transmittance(&self, distance : f64) -> Color
+    {
+        let extinction = self.extinction();
+
+        return Color::new_rgb(
+            channel_transmittance(extinction.r, distance),
+            channel_transmittance(extinction.g, distance),
+            channel_transmittance(extinction.b, distance));
+    }
+
+    // Samples the distance to the next scattering event from the extinction of a uniformly chosen channel
+    fn sample_homogeneous(&self, max_distance : f64) -> MediumSample
+    {
+        let extinction = self.extinction();
+        let channels = [extinction.r, extinction.g, extinction.b];
+        let channel = ((random::gen_num() * 3.0) as usize).min(2);
+
+        let distance =
+            if channels[channel] > 0.0
+            { free_path(channels[channel]) }
+            else
+            { f64::INFINITY };
+
+        let scattered = distance < max_distance;
+        let travelled = distance.min(max_distance);
+        let transmittance = self.homogeneous_transmittance(travelled);
+
+        // Densities of the event under each channel's sampling, averaged over the channel choice
+        let density =
+            if scattered
+            { (transmittance.r * extinction.r + transmittance.g * extinction.g + transmittance.b * extinction.b) / 3.0 }
+            else
+            { (transmittance.r + transmittance.g + transmittance.b) / 3.0 };
+
+        if density <= 0.0
+        { return MediumSample { distance : travelled, scattered, weight : Color::new_rgb(0.0, 0.0, 0.0) }; }
+
+        let weight =
+            if scattered
+            { transmittance.mul_by_color(self.scattering) * (1.0 / density) }
+            else
+            { transmittance * (1.0 / density) };
+
+        return MediumSample { distance : travelled, scattered, weight };
+    }
+
+    // Henyey-Greenstein phase function, cos_theta is measured between the propagation directions before and after
+    pub(crate) fn phase(&self, cos_theta : f64) -> f64
+    {
+        let g = self.anisotropy;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+
+        return (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt());
+    }
+
+    // Scattered direction for a ray travelling along dir, its density is phase of the cosine between them
+    pub(crate) fn sample_phase(&self, dir : Vector) -> Vector
+    {
+        let g = self.anisotropy;
+        let u1 = random::gen_num();
+        let u2 = random::gen_num();
+
+        let cos_theta =
+            if g.abs() < 1e-3
+            { 1.0 - 2.0 * u1 }
+            else
+            {
+                let sq = (1.0 - g * g) / (1.0 - g + 2.0 * g * u1);
+                ((1.0 + g * g - sq * sq) / (2.0 * g)).clamp(-1.0, 1.0)
+            };
+
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+
+        let mut axis_x = Vector::new();
+        let mut axis_y = Vector::new();
+        orthonormal_sys(dir, &mut axis_x, &mut axis_y);
+
+        let mut scattered = axis_x * (sin_theta * phi.cos()) + axis_y * (sin_theta * phi.sin()) + dir * cos_theta;
+
+        return *scattered.normalize();
+    }
+}
@@ -0,0 +1,271 @@
+use std::sync::Arc;
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::color::Color;
+use crate::material::Material;
+use crate::object::{Body, Hit, SurfaceSample};
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+const EPSILON : f64 = 0.000001;
+
+// Geometry shared between all copies of a mesh
+pub struct MeshData
+{
+    pub vertices : Vec<Vector>,
+    pub triangles : Vec<[usize; 3]>,
+    // Optional per vertex normals used for smooth shading
+    pub normals : Option<Vec<Vector>>,
+    // Optional per vertex texture coordinates
+    pub uvs : Option<Vec<[f64; 2]>>,
+    // Optional per vertex colours multiplying the material's color
+    pub colors : Option<Vec<Color>>,
+    bvh : Bvh,
+    // Running sum of the triangles' areas, for picking triangles in proportion to their area
+    areas : Vec<f64>
+}
+
+// Triangle mesh accelerated by a bounding volume hierarchy
+#[derive(Clone)]
+pub struct Mesh
+{
+    data : Arc<MeshData>,
+    material : Material
+}
+
+impl MeshData
+{
+    pub fn new(mesh_vertices : Vec<Vector>, mesh_triangles : Vec<[usize; 3]>, mesh_normals : Option<Vec<Vector>>, mesh_uvs : Option<Vec<[f64; 2]>>,
+        mesh_colors : Option<Vec<Color>>) -> Self
+    {
+        let bounds : Vec<Aabb> = mesh_triangles.iter()
+            .map(|tri| Aabb::from_points(&[mesh_vertices[tri[0]], mesh_vertices[tri[1]], mesh_vertices[tri[2]]]))
+            .collect();
+
+        let mut total_area = 0.0;
+        let areas : Vec<f64> = mesh_triangles.iter()
+            .map(|tri|
+            {
+                let cross = (mesh_vertices[tri[1]] - mesh_vertices[tri[0]]).cross_product(mesh_vertices[tri[2]] - mesh_vertices[tri[0]]);
+                total_area += 0.5 * cross.dot_product(cross).sqrt();
+
+                total_area
+            })
+            .collect();
+
+        MeshData
+        {
+            bvh : Bvh::build(&bounds),
+            areas,
+            vertices : mesh_vertices,
+            triangles : mesh_triangles,
+            normals : mesh_normals,
+            uvs : mesh_uvs,
+            colors : mesh_colors
+        }
+    }
+
+    fn corners(&self, triangle : usize) -> [Vector; 3]
+    {
+        let tri = self.triangles[triangle];
+
+        return [self.vertices[tri[0]], self.vertices[tri[1]], self.vertices[tri[2]]];
+    }
+
+    // Moller-Trumbore ray triangle intersection
+    fn intersect_triangle(&self, triangle : usize, ray : Ray) -> Option<Hit>
+    {
+        let [v0, v1, v2] = self.corners(triangle);
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+
+        let p = ray.dest.cross_product(edge2);
+        let det = edge1.dot_product(p);
+
+        if det.abs() < 1e-12
+        { return None; }
+
+        let inv_det = 1.0 / det;
+        let s = ray.origin - v0;
+        let u = s.dot_product(p) * inv_det;
+
+        if ! (0.0..=1.0).contains(&u)
+        { return None; }
+
+        let q = s.cross_product(edge1);
+        let v = ray.dest.dot_product(q) * inv_det;
+
+        if v < 0.0 || u + v > 1.0
+        { return None; }
+
+        let t = edge2.dot_product(q) * inv_det;
+
+        if t <= EPSILON
+        { return None; }
+
+        return Some(Hit { triangle : Some((triangle, [1.0 - u - v, u, v])), ..Hit::new(t) });
+    }
+
+    // Interpolated texture coordinates, or the barycentric coordinates within the triangle for meshes without them
+    pub fn uv(&self, triangle : usize, bary : [f64; 3]) -> (f64, f64)
+    {
+        if let Some(uvs) = &self.uvs
+        {
+            let tri = self.triangles[triangle];
+            let u = uvs[tri[0]][0] * bary[0] + uvs[tri[1]][0] * bary[1] + uvs[tri[2]][0] * bary[2];
+            let v = uvs[tri[0]][1] * bary[0] + uvs[tri[1]][1] * bary[1] + uvs[tri[2]][1] * bary[2];
+
+            return (u, v);
+        }
+
+        return (bary[1], bary[2]);
+    }
+
+    // Closest point on an edge of the triangle to a point on it
+    pub fn nearest_edge(&self, triangle : usize, point : Vector) -> Vector
+    {
+        let corners = self.corners(triangle);
+        let mut nearest : Option<(Vector, f64)> = None;
+
+        for i in 0..3
+        {
+            let start = corners[i];
+            let edge = corners[(i + 1) % 3] - start;
+            let t = ((point - start).dot_product(edge) / edge.dot_product(edge)).clamp(0.0, 1.0);
+            let on_edge = start + edge * t;
+            let offset = point - on_edge;
+            let sq_distance = offset.dot_product(offset);
+
+            if nearest.is_none_or(|(_, best)| sq_distance < best)
+            { nearest = Some((on_edge, sq_distance)); }
+        }
+
+        return nearest.map_or(point, |(on_edge, _)| on_edge);
+    }
+
+    pub fn area(&self) -> f64
+    { return self.areas.last().copied().unwrap_or(0.0); }
+
+    // Uniformly distributed point on the surface, u1 picks the triangle and is then reused within it
+    pub fn sample_surface(&self, u1 : f64, u2 : f64) -> Option<SurfaceSample>
+    {
+        let total = self.area();
+
+        if total <= 0.0
+        { return None; }
+
+        let target = u1 * total;
+        let triangle = self.areas.partition_point(|area| *area <= target).min(self.areas.len() - 1);
+        let start = if triangle > 0 { self.areas[triangle - 1] } else { 0.0 };
+        let share = self.areas[triangle] - start;
+
+        // Where u1 falls within the triangle's share is uniform again
+        let u = if share > 0.0 { ((target - start) / share).clamp(0.0, 1.0) } else { 0.0 };
+        let root = u.sqrt();
+        let [v0, v1, v2] = self.corners(triangle);
+        let point = v0 * (1.0 - root) + v1 * (root * (1.0 - u2)) + v2 * (root * u2);
+
+        return Some(SurfaceSample { point, normal : self.face_normal(triangle) });
+    }
+
+    pub fn face_normal(&self, triangle : usize) -> Vector
+    {
+        let [v0, v1, v2] = self.corners(triangle);
+
+        return *(v1 - v0).cross_product(v2 - v0).normalize();
+    }
+}
+
+impl Mesh
+{
+    pub fn new(mesh_data : Arc<MeshData>, mat : Material) -> Self
+    {
+        Mesh
+        {
+            data : mesh_data,
+            material : mat
+        }
+    }
+}
+
+impl Body for Mesh
+{
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        return self.data.bvh.intersect(ray, |triangle| self.data.intersect_triangle(triangle, ray));
+    }
+
+    fn node_visits(&self, ray : Ray) -> u64
+    { return self.data.bvh.node_visits(ray, |triangle| self.data.intersect_triangle(triangle, ray)); }
+
+    // Interpolated vertex normal if the mesh has them, face normal otherwise
+    fn normal(&self, _vec : Vector, hit : &Hit) -> Vector
+    {
+        // Hits returned by intersect always carry their triangle
+        let (triangle, bary) = match hit.triangle
+        {
+            Some(found) => found,
+            None => return Vector { x : 0.0, y : 1.0, z : 0.0 }
+        };
+
+        if let Some(normals) = &self.data.normals
+        {
+            let tri = self.data.triangles[triangle];
+            let mut interpolated = normals[tri[0]] * bary[0] + normals[tri[1]] * bary[1] + normals[tri[2]] * bary[2];
+
+            return *interpolated.normalize();
+        }
+
+        return self.data.face_normal(triangle);
+    }
+
+    fn uv(&self, _vec : Vector, hit : &Hit) -> (f64, f64)
+    {
+        return match hit.triangle
+        {
+            Some((triangle, bary)) => self.data.uv(triangle, bary),
+            None => (0.0, 0.0)
+        };
+    }
+
+    fn albedo(&self, vec : Vector, hit : &Hit) -> Color
+    {
+        let albedo = self.material.albedo(|| self.uv(vec, hit));
+
+        return match (&self.data.colors, hit.triangle)
+        {
+            (Some(colors), Some((triangle, bary))) =>
+            {
+                let tri = self.data.triangles[triangle];
+
+                albedo.mul_by_color(colors[tri[0]] * bary[0] + colors[tri[1]] * bary[1] + colors[tri[2]] * bary[2])
+            }
+            _ => albedo
+        };
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    { return self.data.bvh.bounds(); }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone();
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+
+    fn area(&self) -> Option<f64>
+    {
+        let area = self.data.area();
+
+        if area > 0.0
+        { return Some(area); }
+
+        return None;
+    }
+
+    fn sample_surface(&self, u1 : f64, u2 : f64) -> Option<SurfaceSample>
+    { return self.data.sample_surface(u1, u2); }
+
+    fn nearest_edge(&self, point : Vector, hit : &Hit) -> Option<Vector>
+    { return hit.triangle.map(|(triangle, _)| self.data.nearest_edge(triangle, point)); }
+}
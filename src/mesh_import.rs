@@ -0,0 +1,592 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::color::srgb_to_linear;
+use crate::json_utils::{Config, JsonMaterial, JsonMaterialRef, JsonObject, MaterialLibrary};
+use crate::scene_validation::SceneError;
+
+// Material of imported meshes, replaced through the material of the include directive
+const DEFAULT_MATERIAL : &str = "default";
+
+// Binary STL files start with an 80 byte header and a triangle count, followed by 50 bytes per triangle
+const STL_HEADER_SIZE : usize = 84;
+const STL_TRIANGLE_SIZE : usize = 50;
+
+#[derive(Default)]
+struct MeshFile
+{
+    vertices : Vec<[f64; 3]>,
+    triangles : Vec<[usize; 3]>,
+    normals : Option<Vec<[f64; 3]>>,
+    colors : Option<Vec<[f64; 3]>>
+}
+
+// Mesh files hold a single white mesh the including scene places and gives a material
+fn to_config(mesh : MeshFile) -> Config
+{
+    let mut materials = MaterialLibrary::new();
+
+    materials.insert(DEFAULT_MATERIAL.to_string(), JsonMaterial
+    {
+        material_type : Some("diffuse".to_string()),
+        color : Some("FFFFFF".to_string()),
+        ..Default::default()
+    });
+
+    let mesh = JsonObject::JsonMesh
+    {
+        vertices : mesh.vertices,
+        triangles : mesh.triangles,
+        normals : mesh.normals,
+        uvs : None,
+        colors : mesh.colors,
+        material : JsonMaterialRef::Named(DEFAULT_MATERIAL.to_string())
+    };
+
+    return Config
+    {
+        materials,
+        objects : vec![mesh]
+    };
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyEncoding
+{
+    Ascii,
+    LittleEndian,
+    BigEndian
+}
+
+#[derive(Clone, Copy)]
+enum PlyType
+{
+    I8, U8, I16, U16, I32, U32, F32, F64
+}
+
+impl PlyType
+{
+    fn parse(name : &str) -> Result<PlyType, String>
+    {
+        return match name
+        {
+            "char" | "int8" => Ok(PlyType::I8),
+            "uchar" | "uint8" => Ok(PlyType::U8),
+            "short" | "int16" => Ok(PlyType::I16),
+            "ushort" | "uint16" => Ok(PlyType::U16),
+            "int" | "int32" => Ok(PlyType::I32),
+            "uint" | "uint32" => Ok(PlyType::U32),
+            "float" | "float32" => Ok(PlyType::F32),
+            "double" | "float64" => Ok(PlyType::F64),
+            _ => Err(format!("unknown property type '{}'", name))
+        };
+    }
+
+    fn size(&self) -> usize
+    {
+        return match self
+        {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8
+        };
+    }
+}
+
+enum PlyProperty
+{
+    Scalar { name : String, value_type : PlyType },
+    List { name : String, count_type : PlyType, item_type : PlyType }
+}
+
+struct PlyElement
+{
+    name : String,
+    count : usize,
+    properties : Vec<PlyProperty>
+}
+
+// Reads the values of the body one at a time, whitespace separated in ASCII files
+struct PlyReader<'a>
+{
+    encoding : PlyEncoding,
+    bytes : &'a [u8],
+    offset : usize
+}
+
+impl<'a> PlyReader<'a>
+{
+    fn ascii_token(&mut self) -> Result<&'a str, String>
+    {
+        while self.offset < self.bytes.len() && self.bytes[self.offset].is_ascii_whitespace()
+        { self.offset += 1; }
+
+        let start = self.offset;
+
+        while self.offset < self.bytes.len() && !self.bytes[self.offset].is_ascii_whitespace()
+        { self.offset += 1; }
+
+        if start == self.offset
+        { return Err("file ends before all elements were read".to_string()); }
+
+        return std::str::from_utf8(&self.bytes[start..self.offset]).map_err(|err| err.to_string());
+    }
+
+    fn read(&mut self, value_type : PlyType) -> Result<f64, String>
+    {
+        if self.encoding == PlyEncoding::Ascii
+        {
+            let token = self.ascii_token()?;
+
+            return token.parse::<f64>().map_err(|_| format!("'{}' is not a number", token));
+        }
+
+        let size = value_type.size();
+
+        if self.offset + size > self.bytes.len()
+        { return Err("file ends before all elements were read".to_string()); }
+
+        let mut raw = [0_u8; 8];
+        raw[..size].copy_from_slice(&self.bytes[self.offset..self.offset + size]);
+        self.offset += size;
+
+        // Values are decoded as little endian, big endian ones are reversed first
+        if self.encoding == PlyEncoding::BigEndian
+        { raw[..size].reverse(); }
+
+        return Ok(match value_type
+        {
+            PlyType::I8 => raw[0] as i8 as f64,
+            PlyType::U8 => raw[0] as f64,
+            PlyType::I16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+            PlyType::U16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+            PlyType::I32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            PlyType::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            PlyType::F32 => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            PlyType::F64 => f64::from_le_bytes(raw)
+        });
+    }
+}
+
+// Header lines up to end_header and the offset of the body behind them
+fn ply_header(bytes : &[u8]) -> Result<(Vec<String>, usize), String>
+{
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len()
+    {
+        let end = bytes[offset..].iter().position(|b| *b == b'\n').map(|i| offset + i).unwrap_or(bytes.len());
+        let line = String::from_utf8_lossy(&bytes[offset..end]).trim().to_string();
+        offset = end + 1;
+
+        if line == "end_header"
+        { return Ok((lines, offset.min(bytes.len()))); }
+
+        lines.push(line);
+    }
+
+    return Err("header has no end_header line".to_string());
+}
+
+fn parse_ply_header(lines : &[String]) -> Result<(PlyEncoding, Vec<PlyElement>), String>
+{
+    if lines.first().map(|line| line.as_str()) != Some("ply")
+    { return Err("not a PLY file".to_string()); }
+
+    let mut encoding = None;
+    let mut elements : Vec<PlyElement> = Vec::new();
+
+    for line in &lines[1..]
+    {
+        let words : Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice()
+        {
+            ["format", format, _] => encoding = Some(match *format
+            {
+                "ascii" => PlyEncoding::Ascii,
+                "binary_little_endian" => PlyEncoding::LittleEndian,
+                "binary_big_endian" => PlyEncoding::BigEndian,
+                _ => return Err(format!("unknown format '{}'", format))
+            }),
+
+            ["element", name, count] => elements.push(PlyElement
+            {
+                name : name.to_string(),
+                count : count.parse().map_err(|_| format!("element {} has an invalid count '{}'", name, count))?,
+                properties : Vec::new()
+            }),
+
+            ["property", "list", count_type, item_type, name] =>
+            {
+                let element = elements.last_mut().ok_or("property before the first element")?;
+
+                element.properties.push(PlyProperty::List
+                {
+                    name : name.to_string(),
+                    count_type : PlyType::parse(count_type)?,
+                    item_type : PlyType::parse(item_type)?
+                });
+            }
+
+            ["property", value_type, name] =>
+            {
+                let element = elements.last_mut().ok_or("property before the first element")?;
+
+                element.properties.push(PlyProperty::Scalar { name : name.to_string(), value_type : PlyType::parse(value_type)? });
+            }
+
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+
+            _ => return Err(format!("unexpected header line '{}'", line))
+        }
+    }
+
+    return match encoding
+    {
+        Some(encoding) => Ok((encoding, elements)),
+        None => Err("header has no format line".to_string())
+    };
+}
+
+// Reads vertex positions, normals and colours and face index lists, other elements are skipped
+fn parse_ply(bytes : &[u8]) -> Result<MeshFile, String>
+{
+    let (lines, body) = ply_header(bytes)?;
+    let (encoding, elements) = parse_ply_header(&lines)?;
+    let mut reader = PlyReader { encoding, bytes, offset : body };
+    let mut mesh = MeshFile::default();
+
+    for element in &elements
+    {
+        let names : Vec<&str> = element.properties.iter().map(|property| match property
+        {
+            PlyProperty::Scalar { name, .. } | PlyProperty::List { name, .. } => name.as_str()
+        }).collect();
+
+        let find = |wanted : &[&str]| -> Option<Vec<usize>>
+        { return wanted.iter().map(|name| names.iter().position(|n| n == name)).collect(); };
+
+        let position = find(&["x", "y", "z"]);
+        let normal = find(&["nx", "ny", "nz"]);
+        let color = find(&["red", "green", "blue"]).or_else(|| find(&["r", "g", "b"]));
+        let indices = find(&["vertex_indices"]).or_else(|| find(&["vertex_index"]));
+
+        let is_vertex = element.name == "vertex";
+        let is_face = element.name == "face";
+
+        if is_vertex && position.is_none()
+        { return Err("vertices have no x, y and z properties".to_string()); }
+
+        // Counts come from the header and aren't trusted for preallocating, the reads below fail once the body runs out
+        if is_vertex && normal.is_some()
+        { mesh.normals = Some(Vec::new()); }
+
+        if is_vertex && color.is_some()
+        { mesh.colors = Some(Vec::new()); }
+
+        // Nothing to read, and the count alone would keep the loop busy
+        if element.properties.is_empty()
+        { continue; }
+
+        for _ in 0..element.count
+        {
+            let mut scalars = vec![0.0; element.properties.len()];
+            let mut list : Vec<usize> = Vec::new();
+
+            for (i, property) in element.properties.iter().enumerate()
+            {
+                match property
+                {
+                    PlyProperty::Scalar { value_type, .. } => scalars[i] = reader.read(*value_type)?,
+
+                    PlyProperty::List { count_type, item_type, .. } =>
+                    {
+                        let count = reader.read(*count_type)? as usize;
+                        let wanted = is_face && indices.as_ref().is_some_and(|index| index[0] == i);
+
+                        for _ in 0..count
+                        {
+                            let item = reader.read(*item_type)?;
+
+                            if wanted
+                            { list.push(item as usize); }
+                        }
+                    }
+                }
+            }
+
+            let triple = |index : &[usize]| [scalars[index[0]], scalars[index[1]], scalars[index[2]]];
+
+            if is_vertex
+            {
+                if let Some(index) = &position
+                { mesh.vertices.push(triple(index)); }
+
+                if let (Some(normals), Some(index)) = (&mut mesh.normals, &normal)
+                { normals.push(triple(index)); }
+
+                // Integer colours are 8 bit sRGB, float colours are already in [0, 1]
+                if let (Some(colors), Some(index)) = (&mut mesh.colors, &color)
+                {
+                    let scale = match &element.properties[index[0]]
+                    {
+                        PlyProperty::Scalar { value_type : PlyType::F32 | PlyType::F64, .. } => 1.0,
+                        _ => 255.0
+                    };
+
+                    colors.push(triple(index).map(|c| srgb_to_linear(c / scale)));
+                }
+            }
+
+            // Polygons are split into a fan of triangles around their first corner
+            for i in 2..list.len()
+            { mesh.triangles.push([list[0], list[i - 1], list[i]]); }
+        }
+    }
+
+    return Ok(mesh);
+}
+
+// Vertices are shared between the triangles touching them, STL stores them once per triangle
+struct StlBuilder
+{
+    mesh : MeshFile,
+    indices : HashMap<[u64; 3], usize>
+}
+
+impl StlBuilder
+{
+    fn vertex(&mut self, position : [f64; 3]) -> usize
+    {
+        let key = position.map(|c| c.to_bits());
+        let next = self.mesh.vertices.len();
+        let index = *self.indices.entry(key).or_insert(next);
+
+        if index == next
+        { self.mesh.vertices.push(position); }
+
+        return index;
+    }
+
+    fn triangle(&mut self, corners : [[f64; 3]; 3])
+    {
+        let triangle = corners.map(|corner| self.vertex(corner));
+
+        self.mesh.triangles.push(triangle);
+    }
+}
+
+// Binary files are recognised by their size matching the triangle count, ASCII files start with "solid"
+fn parse_stl(bytes : &[u8]) -> Result<MeshFile, String>
+{
+    let mut builder = StlBuilder { mesh : MeshFile::default(), indices : HashMap::new() };
+
+    let binary_count = bytes.get(80..STL_HEADER_SIZE).map(|count| u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize);
+
+    if binary_count.is_some_and(|count| bytes.len() == STL_HEADER_SIZE + count * STL_TRIANGLE_SIZE)
+    {
+        // Facet normals are skipped, the winding of the corners already orients the triangle
+        for triangle in bytes[STL_HEADER_SIZE..].chunks_exact(STL_TRIANGLE_SIZE)
+        {
+            let corner = |i : usize| -> [f64; 3]
+            {
+                let offset = 12 + 12 * i;
+                let value = |at : usize| f32::from_le_bytes([triangle[at], triangle[at + 1], triangle[at + 2], triangle[at + 3]]) as f64;
+
+                return [value(offset), value(offset + 4), value(offset + 8)];
+            };
+
+            builder.triangle([corner(0), corner(1), corner(2)]);
+        }
+
+        return Ok(builder.mesh);
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let mut words = text.split_whitespace();
+
+    if words.next() != Some("solid")
+    { return Err("binary STL size does not match its triangle count".to_string()); }
+
+    let mut corners : Vec<[f64; 3]> = Vec::new();
+
+    while let Some(word) = words.next()
+    {
+        match word
+        {
+            "vertex" =>
+            {
+                let mut corner = [0.0; 3];
+
+                for value in corner.iter_mut()
+                {
+                    let token = words.next().ok_or("file ends inside a vertex")?;
+                    *value = token.parse().map_err(|_| format!("'{}' is not a number", token))?;
+                }
+
+                corners.push(corner);
+            }
+
+            "endfacet" =>
+            {
+                if corners.len() != 3
+                { return Err(format!("facet has {} vertices instead of 3", corners.len())); }
+
+                builder.triangle([corners[0], corners[1], corners[2]]);
+                corners.clear();
+            }
+
+            _ => {}
+        }
+    }
+
+    // Binary files may start with "solid" as well, a truncated one ends up here
+    if builder.mesh.triangles.is_empty()
+    { return Err("no facets found, or the binary STL size does not match its triangle count".to_string()); }
+
+    return Ok(builder.mesh);
+}
+
+pub fn read_ply(path : &Path) -> Result<Config, SceneError>
+{
+    let bytes = fs::read(path)?;
+
+    return parse_ply(&bytes).map(to_config).map_err(|err| SceneError::Parse(err.into()));
+}
+
+pub fn read_stl(path : &Path) -> Result<Config, SceneError>
+{
+    let bytes = fs::read(path)?;
+
+    return parse_stl(&bytes).map(to_config).map_err(|err| SceneError::Parse(err.into()));
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const TRIANGLE : [[f64; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+    // Header of a binary PLY file with a single triangle, followed by its body in the given byte order
+    fn binary_ply(format : &str, to_bytes : fn(f32) -> [u8; 4], index_bytes : fn(i32) -> [u8; 4]) -> Vec<u8>
+    {
+        let header = format!("ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n", format);
+        let mut bytes = header.into_bytes();
+
+        for vertex in TRIANGLE
+        {
+            for value in vertex
+            { bytes.extend_from_slice(&to_bytes(value as f32)); }
+        }
+
+        bytes.push(3);
+
+        for index in [0, 1, 2]
+        { bytes.extend_from_slice(&index_bytes(index)); }
+
+        return bytes;
+    }
+
+    #[test]
+    fn ascii_ply_with_normals_and_colours()
+    {
+        let file = "ply\nformat ascii 1.0\ncomment made by hand\nelement vertex 3\nproperty float x\nproperty float y\n\
+            property float z\nproperty float nx\nproperty float ny\nproperty float nz\nproperty uchar red\n\
+            property uchar green\nproperty uchar blue\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n\
+            0 0 0 0 0 1 255 0 0\n1 0 0 0 0 1 0 255 0\n0 1 0 0 0 1 0 0 255\n3 0 1 2\n";
+
+        let mesh = parse_ply(file.as_bytes()).unwrap();
+
+        assert_eq!(mesh.vertices, TRIANGLE.to_vec());
+        assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+        assert_eq!(mesh.normals, Some(vec![[0.0, 0.0, 1.0]; 3]));
+        assert_eq!(mesh.colors, Some(vec![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]));
+    }
+
+    #[test]
+    fn binary_ply_in_both_byte_orders()
+    {
+        let little = binary_ply("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes);
+        let big = binary_ply("binary_big_endian", f32::to_be_bytes, i32::to_be_bytes);
+
+        for bytes in [little, big]
+        {
+            let mesh = parse_ply(&bytes).unwrap();
+
+            assert_eq!(mesh.vertices, TRIANGLE.to_vec());
+            assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+            assert!(mesh.normals.is_none() && mesh.colors.is_none());
+        }
+    }
+
+    #[test]
+    fn ply_polygons_are_split_into_fans()
+    {
+        let file = "ply\nformat ascii 1.0\nelement vertex 5\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+            0 0 0\n1 0 0\n1 1 0\n0.5 1.5 0\n0 1 0\n5 0 1 2 3 4\n";
+
+        let mesh = parse_ply(file.as_bytes()).unwrap();
+
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+    }
+
+    #[test]
+    fn ply_counts_beyond_the_body_are_errors()
+    {
+        let file = "ply\nformat binary_little_endian 1.0\nelement vertex 18446744073709551615\nproperty float x\n\
+            property float y\nproperty float z\nproperty float nx\nproperty float ny\nproperty float nz\nend_header\n";
+
+        assert!(parse_ply(file.as_bytes()).is_err());
+
+        let file = "ply\nformat binary_little_endian 1.0\nelement empty 18446744073709551615\nend_header\n";
+
+        assert!(parse_ply(file.as_bytes()).unwrap().vertices.is_empty());
+    }
+
+    #[test]
+    fn ascii_stl_shares_vertices()
+    {
+        let file = "solid square\n\
+            facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 1 1 0\nendloop\nendfacet\n\
+            facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 1 0\nvertex 0 1 0\nendloop\nendfacet\n\
+            endsolid square\n";
+
+        let mesh = parse_stl(file.as_bytes()).unwrap();
+
+        assert_eq!(mesh.vertices, vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn binary_stl()
+    {
+        // Starts with "solid" like many exporters write, the size still identifies the file as binary
+        let mut bytes = b"solid exported".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 12]);
+
+        for vertex in TRIANGLE
+        {
+            for value in vertex
+            { bytes.extend_from_slice(&(value as f32).to_le_bytes()); }
+        }
+
+        bytes.extend_from_slice(&[0; 2]);
+
+        let mesh = parse_stl(&bytes).unwrap();
+
+        assert_eq!(mesh.vertices, TRIANGLE.to_vec());
+        assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+
+        bytes.pop();
+
+        assert!(parse_stl(&bytes).is_err());
+    }
+}
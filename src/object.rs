@@ -1,20 +1,147 @@
+use crate::aabb::Aabb;
+use crate::color::Color;
 use crate::material::Material;
+use crate::matrix::Matrix4;
 use crate::ray::Ray;
-use crate::vector::Vector;
+use crate::vector::{orthonormal_sys, Vector};
 
 const EPSILON : f64 = 0.000001;
 
+/// Body placed in a scene, made by the functions in [`object_builder`](crate::object_builder).
 pub struct Object
 {
-    pub body: Box<dyn Body + Send + Sync>
+    pub(crate) body: Box<dyn Body + Send + Sync>,
+    // Cached bounds of the body, None for unbounded bodies
+    pub(crate) bounds : Option<Aabb>
 }
 
+impl Object
+{
+    pub(crate) fn new(obj_body : Box<dyn Body + Send + Sync>) -> Self
+    {
+        let obj_bounds = obj_body.bounds();
+
+        Object
+        {
+            body : obj_body,
+            bounds : obj_bounds
+        }
+    }
+}
+
+// Where a ray hit a body, carrying what the body needs to shade the point without looking for it again
+#[derive(Clone, Copy, Debug)]
+pub struct Hit
+{
+    pub distance : f64,
+    // Triangle of a mesh that was hit and the point's barycentric coordinates within it
+    pub triangle : Option<(usize, [f64; 3])>,
+    // Operand of each enclosing constructive solid geometry body the hit came from, one bit per body set for right
+    // operands, with the outermost body in the lowest bit. Bodies nested more than 64 deep lose their bits
+    pub operands : u64
+}
+
+impl Hit
+{
+    pub fn new(dist : f64) -> Self
+    {
+        Hit
+        {
+            distance : dist,
+            triangle : None,
+            operands : 0
+        }
+    }
+}
+
+// Intervals of a body that is not made up of others, whose boundaries carry nothing but their distance
+pub fn plain_intervals(spans : Vec<(f64, f64)>) -> Vec<(Hit, Hit)>
+{ return spans.into_iter().map(|(start, end)| (Hit::new(start), Hit::new(end))).collect(); }
+
+// The shading methods take the hit returned by intersect for the point they are given
 pub trait Body
 {
-    fn intersect(&self, ray : Ray) -> f64;
-    fn normal(&self, vec : Vector) -> Vector;
+    // Closest hit in front of the ray's origin, None if the ray misses
+    fn intersect(&self, ray : Ray) -> Option<Hit>;
+    fn normal(&self, vec : Vector, hit : &Hit) -> Vector;
+    // Surface coordinates of a point on the body
+    fn uv(&self, vec : Vector, hit : &Hit) -> (f64, f64);
+    // None for unbounded bodies such as planes
+    fn bounds(&self) -> Option<Aabb>;
     fn get_material(&self) -> Material;
     fn clone_self(&self) -> Box<dyn Body + Send + Sync>;
+
+    // Surface area, None for bodies that cannot be sampled as area lights
+    fn area(&self) -> Option<f64>
+    { return None; }
+
+    // Uniformly distributed point on the surface and the normal at that point
+    fn sample_surface(&self, _u1 : f64, _u2 : f64) -> Option<SurfaceSample>
+    { return None; }
+
+    // Point on the surface sampled as seen from origin and its density with respect to solid angle at origin
+    fn sample_from(&self, origin : Vector, u1 : f64, u2 : f64) -> Option<(SurfaceSample, f64)>
+    {
+        let sample = self.sample_surface(u1, u2)?;
+        let pdf = area_to_solid_angle_pdf(self.area()?, origin, sample.point, sample.normal);
+
+        if pdf <= 0.0
+        { return None; }
+
+        return Some((sample, pdf));
+    }
+
+    // Solid angle density with which sample_from produces the given point on the surface
+    fn pdf_from(&self, origin : Vector, point : Vector, normal : Vector) -> f64
+    {
+        return match self.area()
+        {
+            Some(area) => area_to_solid_angle_pdf(area, origin, point, normal),
+            None => 0.0
+        };
+    }
+
+    // Diffuse reflectance at a point on the surface
+    fn albedo(&self, vec : Vector, hit : &Hit) -> Color
+    { return self.get_material().albedo(|| self.uv(vec, hit)); }
+
+    // Whether an emissive surface emits light from its back side as well
+    fn two_sided(&self) -> bool
+    { return true; }
+
+    // Sorted, disjoint spans of the whole line through the ray (including behind its origin) lying inside the body,
+    // given by the hits entering and leaving them. None for bodies that do not enclose a volume and cannot be used
+    // in constructive solid geometry
+    fn intervals(&self, _ray : Ray) -> Option<Vec<(Hit, Hit)>>
+    { return None; }
+
+    // Closest point to a point on the surface that lies on an edge of its polygons, None for bodies without polygons
+    fn nearest_edge(&self, _point : Vector, _hit : &Hit) -> Option<Vector>
+    { return None; }
+
+    // Bounding volume hierarchy nodes intersect tests for the ray, 0 for bodies without a hierarchy
+    fn node_visits(&self, _ray : Ray) -> u64
+    { return 0; }
+}
+
+#[derive(Clone, Copy)]
+pub struct SurfaceSample
+{
+    pub point : Vector,
+    pub normal : Vector
+}
+
+// Converts a uniform density over a surface into a solid angle density seen from origin
+fn area_to_solid_angle_pdf(area : f64, origin : Vector, point : Vector, normal : Vector) -> f64
+{
+    let to_point = point - origin;
+    let sq_distance = to_point.dot_product(to_point);
+    let cos_theta = normal.dot_product(to_point).abs() / sq_distance.sqrt();
+
+    if cos_theta < 1e-8
+    { return 0.0; }
+
+    return sq_distance / (cos_theta * area);
 }
 
 #[derive(Clone)]
@@ -41,7 +168,7 @@ impl Sphere
 
 impl Body for Sphere
 {
-    fn intersect(&self, ray: Ray) -> f64
+    fn intersect(&self, ray: Ray) -> Option<Hit>
     {
         let sphere_centre = self.centre;
         let sphere_radius = self.radius;
@@ -54,7 +181,7 @@ impl Body for Sphere
         let mut discr = b * b - 4.0 * c;
 
         if discr < 0.0
-        { return 0.0;}
+        { return None; }
 
         discr = discr.sqrt();
 
@@ -62,23 +189,122 @@ impl Body for Sphere
         let result2 = -b - discr;
 
         if result2 > EPSILON
-        { return result2 / 2.0; }
+        { return Some(Hit::new(result2 / 2.0)); }
 
         else if  result1 > EPSILON
-        { return result1 / 2.0; }
+        { return Some(Hit::new(result1 / 2.0)); }
 
-        return 0.0
+        return None;
     }
 
-    fn normal(&self, vec : Vector) -> Vector
+    fn normal(&self, vec : Vector, _hit : &Hit) -> Vector
     {
         let sphere_centre = self.centre;
 
         return *(vec - sphere_centre).normalize();
     }
 
+    fn uv(&self, vec : Vector, _hit : &Hit) -> (f64, f64)
+    {
+        let dir = *(vec - self.centre).normalize();
+
+        let u = 0.5 + dir.z.atan2(dir.x) / (2.0 * std::f64::consts::PI);
+        let v = dir.y.clamp(-1.0, 1.0).acos() / std::f64::consts::PI;
+
+        return (u, v);
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    {
+        let extent = Vector { x : self.radius, y : self.radius, z : self.radius };
+
+        return Some(Aabb::new(self.centre - extent, self.centre + extent));
+    }
+
+    fn area(&self) -> Option<f64>
+    { return Some(4.0 * std::f64::consts::PI * self.radius * self.radius); }
+
+    fn sample_surface(&self, u1 : f64, u2 : f64) -> Option<SurfaceSample>
+    {
+        let z = 1.0 - 2.0 * u1;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * u2;
+
+        let dir = Vector { x : r * phi.cos(), y : r * phi.sin(), z };
+
+        return Some(SurfaceSample { point : self.centre + dir * self.radius, normal : dir });
+    }
+
+    // Samples the cone of directions subtended by the sphere instead of its whole surface
+    fn sample_from(&self, origin : Vector, u1 : f64, u2 : f64) -> Option<(SurfaceSample, f64)>
+    {
+        let to_centre = self.centre - origin;
+        let sq_distance = to_centre.dot_product(to_centre);
+
+        if sq_distance <= self.radius * self.radius
+        {
+            let sample = self.sample_surface(u1, u2)?;
+            let pdf = area_to_solid_angle_pdf(self.area()?, origin, sample.point, sample.normal);
+
+            return Some((sample, pdf));
+        }
+
+        let cos_max = (1.0 - self.radius * self.radius / sq_distance).max(0.0).sqrt();
+        let cos_theta = 1.0 - u1 + u1 * cos_max;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * u2;
+
+        let axis = to_centre * (1.0 / sq_distance.sqrt());
+        let mut axis_x = Vector::new();
+        let mut axis_y = Vector::new();
+
+        orthonormal_sys(axis, &mut axis_x, &mut axis_y);
+
+        let dir = axis_x * (sin_theta * phi.cos()) + axis_y * (sin_theta * phi.sin()) + axis * cos_theta;
+
+        // Grazing directions may miss due to rounding, fall back to the closest point on the silhouette
+        let distance = match self.intersect(Ray { origin, dest : dir })
+        {
+            Some(hit) => hit.distance,
+            None => to_centre.dot_product(dir)
+        };
+
+        let point = origin + dir * distance;
+        let normal = *(point - self.centre).normalize();
+
+        return Some((SurfaceSample { point, normal }, 1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_max))));
+    }
+
+    fn pdf_from(&self, origin : Vector, point : Vector, normal : Vector) -> f64
+    {
+        let to_centre = self.centre - origin;
+        let sq_distance = to_centre.dot_product(to_centre);
+
+        if sq_distance <= self.radius * self.radius
+        { return area_to_solid_angle_pdf(self.area().unwrap_or(0.0), origin, point, normal); }
+
+        let cos_max = (1.0 - self.radius * self.radius / sq_distance).max(0.0).sqrt();
+
+        return 1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_max));
+    }
+
+    fn intervals(&self, ray : Ray) -> Option<Vec<(Hit, Hit)>>
+    {
+        let rel_origin = ray.origin - self.centre;
+        let b = rel_origin.dot_product(ray.dest);
+        let c = rel_origin.dot_product(rel_origin) - self.radius * self.radius;
+        let discr = b * b - c;
+
+        if discr < 0.0
+        { return Some(vec![]); }
+
+        let sqrt_discr = discr.sqrt();
+
+        return Some(plain_intervals(vec![(-b - sqrt_discr, -b + sqrt_discr)]));
+    }
+
     fn get_material(&self) -> Material {
-        return self.material;
+        return self.material.clone();
     }
 
     fn clone_self(&self) -> Box<dyn Body + Send +Sync> { return Box::new(self.clone()) }
@@ -107,7 +333,7 @@ impl Plane
 }
 
 impl Body for Plane {
-    fn intersect(&self, ray: Ray) -> f64
+    fn intersect(&self, ray: Ray) -> Option<Hit>
     {
         let plane_normal = self.normal;
         let plane_d = self.d;
@@ -115,28 +341,437 @@ impl Body for Plane {
 
         if d0 != 0.0
         {
-            let t = -1.0 * ((plane_normal.dot_product(ray.origin) + plane_d) / d0);
+            let t = -((plane_normal.dot_product(ray.origin) + plane_d) / d0);
 
             if t > EPSILON
-            { return t; }
+            { return Some(Hit::new(t)); }
 
-            return 0.0;
+            return None;
         }
 
-        return 0.0
+        return None;
     }
 
-    fn normal(&self, _vec: Vector) -> Vector
+    fn normal(&self, _vec: Vector, _hit : &Hit) -> Vector
     { return self.normal; }
 
+    // Coordinates along two axes spanning the plane, repeating every unit
+    fn uv(&self, vec : Vector, _hit : &Hit) -> (f64, f64)
+    {
+        let mut axis_u = Vector::new();
+        let mut axis_v = Vector::new();
+
+        orthonormal_sys(self.normal, &mut axis_u, &mut axis_v);
+
+        return (vec.dot_product(axis_u).rem_euclid(1.0), vec.dot_product(axis_v).rem_euclid(1.0));
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    { return None; }
+
+    // The plane bounds the half-space behind its normal
+    fn intervals(&self, ray : Ray) -> Option<Vec<(Hit, Hit)>>
+    {
+        let d0 = self.normal.dot_product(ray.dest);
+        let signed_distance = self.normal.dot_product(ray.origin) + self.d;
+
+        if d0 == 0.0
+        {
+            if signed_distance < 0.0
+            { return Some(plain_intervals(vec![(f64::NEG_INFINITY, f64::INFINITY)])); }
+
+            return Some(vec![]);
+        }
+
+        let t = -signed_distance / d0;
+
+        if d0 > 0.0
+        { return Some(plain_intervals(vec![(f64::NEG_INFINITY, t)])); }
+
+        return Some(plain_intervals(vec![(t, f64::INFINITY)]));
+    }
+
     fn get_material(&self) -> Material {
-        return self.material;
+        return self.material.clone();
     }
 
     fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
 }
 
+// Box given by its minimal and maximal corner, optionally rotated around its centre
+#[derive(Clone)]
+pub struct Cuboid
+{
+    min : Vector,
+    max : Vector,
+    // Rotation from the box's local space into world space and its inverse
+    rotation : Matrix4,
+    inv_rotation : Matrix4,
+    material : Material
+}
+
+impl Cuboid
+{
+    pub fn new(min_corner : Vector, max_corner : Vector, mat : Material) -> Self
+    {
+        Cuboid
+        {
+            min : min_corner,
+            max : max_corner,
+            rotation : Matrix4::identity(),
+            inv_rotation : Matrix4::identity(),
+            material : mat
+        }
+    }
+
+    // Rotation angles are in degrees and are applied around the box's centre
+    pub fn new_rotated(min_corner : Vector, max_corner : Vector, angles : Vector, mat : Material) -> Self
+    {
+        let rot = Matrix4::rotation_degrees(angles);
+
+        Cuboid
+        {
+            min : min_corner,
+            max : max_corner,
+            rotation : rot,
+            // Rotation matrices are orthogonal
+            inv_rotation : rot.transpose(),
+            material : mat
+        }
+    }
+
+    fn centre(&self) -> Vector
+    { return (self.min + self.max) * 0.5; }
+
+    fn to_local(&self, point : Vector) -> Vector
+    {
+        let centre = self.centre();
+
+        return self.inv_rotation.transform_vector(point - centre) + centre;
+    }
+
+    // Position relative to the centre in local space, scaled so that faces lie at +-1
+    fn scaled_local(&self, point : Vector) -> [f64; 3]
+    {
+        let rel = self.to_local(point) - self.centre();
+        let half_size = (self.max - self.min) * 0.5;
+
+        return [rel.x / half_size.x, rel.y / half_size.y, rel.z / half_size.z];
+    }
+
+    // Span of the ray's line inside the box, found by slab intersection in the box's local space
+    fn slab_interval(&self, ray : Ray) -> Option<(f64, f64)>
+    {
+        let origin = self.to_local(ray.origin);
+        let dest = self.inv_rotation.transform_vector(ray.dest);
+
+        let origins = [origin.x, origin.y, origin.z];
+        let dests = [dest.x, dest.y, dest.z];
+        let mins = [self.min.x, self.min.y, self.min.z];
+        let maxs = [self.max.x, self.max.y, self.max.z];
+
+        let mut t_near = f64::NEG_INFINITY;
+        let mut t_far = f64::INFINITY;
+
+        for axis in 0..3
+        {
+            if dests[axis].abs() < EPSILON
+            {
+                // Ray is parallel to the slab and starts outside of it
+                if origins[axis] < mins[axis] || origins[axis] > maxs[axis]
+                { return None; }
+
+                continue;
+            }
+
+            let inv_dest = 1.0 / dests[axis];
+            let mut t0 = (mins[axis] - origins[axis]) * inv_dest;
+            let mut t1 = (maxs[axis] - origins[axis]) * inv_dest;
+
+            if t0 > t1
+            { std::mem::swap(&mut t0, &mut t1); }
+
+            t_near = t_near.max(t0);
+            t_far = t_far.min(t1);
+
+            if t_near > t_far
+            { return None; }
+        }
+
+        return Some((t_near, t_far));
+    }
+
+    // Axis and sign of the face the point lies on
+    fn face(&self, point : Vector) -> (usize, f64)
+    {
+        let scaled = self.scaled_local(point);
+        let mut axis = 0;
+
+        for i in 1..3
+        {
+            if scaled[i].abs() > scaled[axis].abs()
+            { axis = i; }
+        }
+
+        return (axis, scaled[axis].signum());
+    }
+}
+
+impl Body for Cuboid
+{
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        let (t_near, t_far) = self.slab_interval(ray)?;
+
+        if t_near > EPSILON
+        { return Some(Hit::new(t_near)); }
+
+        // Ray origin is inside the box
+        else if t_far > EPSILON
+        { return Some(Hit::new(t_far)); }
+
+        return None;
+    }
+
+    // Normal of the face the point lies on
+    fn normal(&self, vec : Vector, _hit : &Hit) -> Vector
+    {
+        let (axis, sign) = self.face(vec);
+        let mut local_normal = Vector::new();
+
+        match axis
+        {
+            0 => local_normal.set_vector(sign, 0.0, 0.0),
+            1 => local_normal.set_vector(0.0, sign, 0.0),
+            _ => local_normal.set_vector(0.0, 0.0, sign)
+        };
+
+        return self.rotation.transform_vector(local_normal);
+    }
+
+    // Coordinates within the face the point lies on
+    fn uv(&self, vec : Vector, _hit : &Hit) -> (f64, f64)
+    {
+        let scaled = self.scaled_local(vec);
+        let (axis, _) = self.face(vec);
+
+        let u = scaled[(axis + 1) % 3];
+        let v = scaled[(axis + 2) % 3];
+
+        return ((u + 1.0) * 0.5, (v + 1.0) * 0.5);
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    {
+        let centre = self.centre();
+        let around_centre = Matrix4::translation(centre).mul(&self.rotation).mul(&Matrix4::translation(centre * -1.0));
+
+        return Some(Aabb::new(self.min, self.max).transform(&around_centre));
+    }
+
+    fn intervals(&self, ray : Ray) -> Option<Vec<(Hit, Hit)>>
+    { return Some(plain_intervals(self.slab_interval(ray).into_iter().collect())); }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone();
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+}
+
+// Parallelogram spanned by two edges from a corner, usable as a rectangular area light
+#[derive(Clone)]
+pub struct Quad
+{
+    corner : Vector,
+    edge_u : Vector,
+    edge_v : Vector,
+    normal : Vector,
+    // Precomputed (u x v) / |u x v|^2 for planar coordinates of hit points
+    w : Vector,
+    two_sided : bool,
+    material : Material
+}
+
+impl Quad
+{
+    pub fn new(quad_corner : Vector, u : Vector, v : Vector, emits_both_sides : bool, mat : Material) -> Self
+    {
+        let cross = u.cross_product(v);
+        let mut quad_normal = cross;
+        quad_normal.normalize();
+
+        Quad
+        {
+            corner : quad_corner,
+            edge_u : u,
+            edge_v : v,
+            normal : quad_normal,
+            w : cross * (1.0 / cross.dot_product(cross)),
+            two_sided : emits_both_sides,
+            material : mat
+        }
+    }
+
+    // Coordinates of a point in the quad's plane along both edges
+    fn planar_coords(&self, point : Vector) -> (f64, f64)
+    {
+        let rel = point - self.corner;
+
+        let alpha = self.w.dot_product(rel.cross_product(self.edge_v));
+        let beta = self.w.dot_product(self.edge_u.cross_product(rel));
+
+        return (alpha, beta);
+    }
+}
+
+impl Body for Quad
+{
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        let denom = self.normal.dot_product(ray.dest);
+
+        if denom.abs() < EPSILON
+        { return None; }
+
+        let t = self.normal.dot_product(self.corner - ray.origin) / denom;
+
+        if t <= EPSILON
+        { return None; }
+
+        let (alpha, beta) = self.planar_coords(ray.origin + ray.dest * t);
+
+        if ! (0.0..=1.0).contains(&alpha) || ! (0.0..=1.0).contains(&beta)
+        { return None; }
+
+        return Some(Hit::new(t));
+    }
+
+    fn normal(&self, _vec : Vector, _hit : &Hit) -> Vector
+    { return self.normal; }
+
+    fn uv(&self, vec : Vector, _hit : &Hit) -> (f64, f64)
+    { return self.planar_coords(vec); }
+
+    fn bounds(&self) -> Option<Aabb>
+    {
+        let corners = [self.corner, self.corner + self.edge_u, self.corner + self.edge_v,
+            self.corner + self.edge_u + self.edge_v];
+
+        return Some(Aabb::from_points(&corners));
+    }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone();
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+
+    fn area(&self) -> Option<f64>
+    {
+        let cross = self.edge_u.cross_product(self.edge_v);
+
+        return Some(cross.dot_product(cross).sqrt());
+    }
+
+    fn sample_surface(&self, u1 : f64, u2 : f64) -> Option<SurfaceSample>
+    {
+        let point = self.corner + self.edge_u * u1 + self.edge_v * u2;
+
+        return Some(SurfaceSample { point, normal : self.normal });
+    }
+
+    fn two_sided(&self) -> bool
+    { return self.two_sided; }
+}
+
 impl Clone for Box<dyn Body>
 {
     fn clone(&self) -> Self { return self.clone_self() }
 }
+
+impl Clone for Box<dyn Body + Send + Sync>
+{
+    fn clone(&self) -> Self { return self.clone_self() }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::material::MatType;
+    use super::*;
+
+    fn vector(x : f64, y : f64, z : f64) -> Vector
+    { return Vector { x, y, z }; }
+
+    fn ray(origin : Vector, dest : Vector) -> Ray
+    { return Ray { origin, dest }; }
+
+    fn assert_close(actual : Vector, expected : Vector)
+    {
+        let error = actual - expected;
+
+        assert!(error.dot_product(error) < 1e-18, "{:?} is not {:?}", actual, expected);
+    }
+
+    // Distance and normal of the closest hit
+    fn hit(body : &dyn Body, ray : Ray) -> (f64, Vector)
+    {
+        let hit = body.intersect(ray).unwrap();
+
+        return (hit.distance, body.normal(ray.origin + ray.dest * hit.distance, &hit));
+    }
+
+    fn white() -> Material
+    { return Material::new(0xFFFFFF, MatType::Diffuse, 0.0); }
+
+    #[test]
+    fn box_slabs()
+    {
+        let cuboid = Cuboid::new(vector(1.0, 1.0, 1.0), vector(3.0, 2.0, 4.0), white());
+
+        let (distance, normal) = hit(&cuboid, ray(vector(0.0, 1.5, 2.0), vector(1.0, 0.0, 0.0)));
+        assert_eq!(distance, 1.0);
+        assert_close(normal, vector(-1.0, 0.0, 0.0));
+
+        // From inside the box the far face is hit
+        let (distance, normal) = hit(&cuboid, ray(vector(2.0, 1.5, 2.5), vector(0.0, 1.0, 0.0)));
+        assert_eq!(distance, 0.5);
+        assert_close(normal, vector(0.0, 1.0, 0.0));
+
+        // Parallel to the y slab but outside of it
+        assert!(cuboid.intersect(ray(vector(0.0, 5.0, 2.0), vector(1.0, 0.0, 0.0))).is_none());
+        // Pointing away from the box
+        assert!(cuboid.intersect(ray(vector(0.0, 1.5, 2.0), vector(-1.0, 0.0, 0.0))).is_none());
+
+        // The whole line through the box, including behind the origin
+        let spans : Vec<(f64, f64)> = cuboid.intervals(ray(vector(2.0, 1.5, 2.0), vector(0.0, 0.0, 1.0))).unwrap().iter()
+            .map(|(start, end)| (start.distance, end.distance)).collect();
+        assert_eq!(spans, vec![(-1.0, 2.0)]);
+    }
+
+    #[test]
+    fn oriented_box()
+    {
+        // Long along x before a quarter turn around z, so along y afterwards
+        let turned = Cuboid::new_rotated(vector(-2.0, -1.0, -1.0), vector(2.0, 1.0, 1.0), vector(0.0, 0.0, 90.0), white());
+
+        let (distance, normal) = hit(&turned, ray(vector(0.0, -5.0, 0.0), vector(0.0, 1.0, 0.0)));
+        assert!((distance - 3.0).abs() < 1e-9);
+        assert_close(normal, vector(0.0, -1.0, 0.0));
+
+        let (distance, normal) = hit(&turned, ray(vector(-5.0, 0.0, 0.0), vector(1.0, 0.0, 0.0)));
+        assert!((distance - 4.0).abs() < 1e-9);
+        assert_close(normal, vector(-1.0, 0.0, 0.0));
+
+        // An eighth turn around y puts an edge of the cube in the way, half a diagonal from the centre
+        let cube = Cuboid::new_rotated(vector(-1.0, -1.0, -1.0), vector(1.0, 1.0, 1.0), vector(0.0, 45.0, 0.0), white());
+        let (distance, _) = hit(&cube, ray(vector(-5.0, 0.0, 0.0), vector(1.0, 0.0, 0.0)));
+        assert!((distance - (5.0 - 2.0_f64.sqrt())).abs() < 1e-9);
+
+        // Just above the edge the ray meets a face tilted by 45 degrees
+        let (_, normal) = hit(&cube, ray(vector(-5.0, 0.0, 0.1), vector(1.0, 0.0, 0.0)));
+        let half = 0.5_f64.sqrt();
+        assert!((normal.x + half).abs() < 1e-9 && normal.y.abs() < 1e-9 && (normal.z.abs() - half).abs() < 1e-9, "{:?}", normal);
+    }
+}
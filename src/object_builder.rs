@@ -1,30 +1,137 @@
+//! Functions making the objects a [`Scene`](crate::Scene) is built from.
+
+use std::sync::Arc;
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::csg::{Csg, CsgOperation};
+use crate::instance::Instance;
 use crate::material::{Material, MatType};
-use crate::object::{Object, Plane, Sphere};
+use crate::matrix::Matrix4;
+use crate::mesh::{Mesh, MeshData};
+use crate::object::Body;
+use crate::object::{Cuboid, Object, Plane, Quad, Sphere};
+use crate::quadric::{Cone, Cylinder, Disk, Torus};
+use crate::sdf::{Sdf, SdfNode};
 use crate::vector::Vector;
 
-pub fn sphere_builder(centre : Vector, radius : f64, material_type : MatType, hex_color : i32) -> Object 
+/// Sphere around `centre`.
+pub fn sphere_builder(centre : Vector, radius : f64, material : Material) -> Object 
 {
-    let material = Material::new(hex_color, material_type, 0.0);
-
     let sphere = Sphere::new(centre , radius, material);
 
-    return Object{ body: Box::new(sphere) };
+    return Object::new(Box::new(sphere));
 }
 
-pub fn plane_builder(normal : Vector, d : f64, material_type : MatType, hex_color : i32) -> Object
+/// Infinite plane of the points `p` with `normal · p + d = 0`.
+pub fn plane_builder(normal : Vector, d : f64, material : Material) -> Object
 {
-    let material = Material::new(hex_color, material_type, 0.0);
-
     let plane = Plane::new(normal, d, material);
 
-    return Object{ body : Box::new(plane) };    
+    return Object::new(Box::new(plane));    
 }
 
+/// Black sphere emitting `emission` from its whole surface.
 pub fn sphere_light_builder(centre : Vector, radius : f64, emission : f64) -> Object
 {
     let material = Material::new(0x000000, MatType::Diffuse, emission);
 
     let sphere = Sphere::new(centre, radius, material);
 
-    return Object{ body: Box::new(sphere) };
-}
\ No newline at end of file
+    return Object::new(Box::new(sphere));
+}
+
+/// Box between the corners `min` and `max`, rotated around its centre by `rotation` given as angles
+/// around x, y and z in degrees.
+pub fn box_builder(min : Vector, max : Vector, rotation : Option<Vector>, material : Material) -> Object
+{
+    let cuboid = match rotation
+    {
+        Some(angles) => Cuboid::new_rotated(min, max, angles, material),
+        None => Cuboid::new(min, max, material)
+    };
+
+    return Object::new(Box::new(cuboid));
+}
+
+/// Cylinder with both ends capped, standing on the centre of its base along `axis`.
+pub fn cylinder_builder(base : Vector, axis : Vector, radius : f64, height : f64, material : Material) -> Object
+{
+    let cylinder = Cylinder::new(base, axis, radius, height, material);
+
+    return Object::new(Box::new(cylinder));
+}
+
+/// Cone with a capped base, its apex lies `height` units from the centre of the base along `axis`.
+pub fn cone_builder(base : Vector, axis : Vector, radius : f64, height : f64, material : Material) -> Object
+{
+    let cone = Cone::new(base, axis, radius, height, material);
+
+    return Object::new(Box::new(cone));
+}
+
+/// Flat disk facing along `normal`.
+pub fn disk_builder(centre : Vector, normal : Vector, radius : f64, material : Material) -> Object
+{
+    let disk = Disk::new(centre, normal, radius, material);
+
+    return Object::new(Box::new(disk));
+}
+
+/// Torus in the plane perpendicular to `axis`, `major_radius` is the distance from the centre to the
+/// middle of the tube and `minor_radius` the radius of the tube.
+pub fn torus_builder(centre : Vector, axis : Vector, major_radius : f64, minor_radius : f64, material : Material) -> Object
+{
+    let torus = Torus::new(centre, axis, major_radius, minor_radius, material);
+
+    return Object::new(Box::new(torus));
+}
+
+/// Parallelogram spanned by `edge_u` and `edge_v` from `corner`. As a light it emits towards
+/// `edge_u × edge_v` only, unless `two_sided` is set.
+pub fn quad_builder(corner : Vector, edge_u : Vector, edge_v : Vector, two_sided : bool, material : Material) -> Object
+{
+    let quad = Quad::new(corner, edge_u, edge_v, two_sided, material);
+
+    return Object::new(Box::new(quad));
+}
+
+/// Triangle mesh whose triangles index into `vertices`. The optional per vertex `normals` shade it
+/// smoothly, `uvs` place the material's texture and `colors` multiply the material's colour.
+pub fn mesh_builder(vertices : Vec<Vector>, triangles : Vec<[usize; 3]>, normals : Option<Vec<Vector>>, uvs : Option<Vec<[f64; 2]>>,
+    colors : Option<Vec<Color>>, material : Material) -> Object
+{
+    let mesh = Mesh::new(Arc::new(MeshData::new(vertices, triangles, normals, uvs, colors)), material);
+
+    return Object::new(Box::new(mesh));
+}
+
+/// Copy of a shared body placed by `transform`, using `material` in place of the body's own if given.
+/// Returns `None` if the transform cannot be inverted.
+pub(crate) fn instance_builder(body : Arc<dyn Body + Send + Sync>, transform : Matrix4, material : Option<Material>) -> Option<Object>
+{
+    let instance = Instance::new(body, transform, material)?;
+
+    return Some(Object::new(Box::new(instance)));
+}
+
+/// Boolean combination of two solids, which must enclose a volume. The result takes the left
+/// operand's material unless `material` is given.
+pub fn csg_builder(operation : CsgOperation, left : Object, right : Object, material : Option<Material>) -> Object
+{
+    let mat = material.unwrap_or_else(|| left.body.get_material());
+    let csg = Csg::new(operation, left.body, right.body, mat);
+
+    return Object::new(Box::new(csg));
+}
+
+/// Surface of a signed distance function, found by stepping `step_scale` times the distance at a
+/// time. Values below 1 are needed for functions such as twists that overestimate distances. Bounds
+/// are derived from the function unless given, returns `None` for unbounded functions such as
+/// repetitions without `bounds`.
+pub(crate) fn sdf_builder(root : SdfNode, bounds : Option<Aabb>, step_scale : f64, material : Material) -> Option<Object>
+{
+    let sdf_bounds = bounds.or(root.bounds())?;
+    let sdf = Sdf::new(root, sdf_bounds, step_scale, material);
+
+    return Some(Object::new(Box::new(sdf)));
+}
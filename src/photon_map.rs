@@ -0,0 +1,206 @@
+use rayon::prelude::*;
+use std::f64::consts::{FRAC_1_PI, PI};
+use crate::color::Color;
+use crate::lights::sample_emission;
+use crate::material::MatType;
+use crate::random;
+use crate::render::{refractive_brdf, specular_brdf};
+use crate::render_settings::RenderSettings;
+use crate::scene::Scene;
+use crate::vector::Vector;
+
+// Light arriving at a diffuse surface after one or more specular bounces
+#[derive(Clone, Copy)]
+pub struct Photon
+{
+    pub position : Vector,
+    // Direction the photon travelled in
+    pub incoming : Vector,
+    // Flux carried by the photon
+    pub power : Color
+}
+
+fn axis_value(vec : Vector, axis : usize) -> f64
+{
+    return match axis
+    {
+        0 => vec.x,
+        1 => vec.y,
+        _ => vec.z
+    };
+}
+
+// Photons stored as a balanced kd-tree, the median of every range is the node splitting it
+pub struct PhotonMap
+{
+    photons : Vec<Photon>,
+    // Split axis of the node at the same index
+    axes : Vec<usize>,
+    radius : f64
+}
+
+impl PhotonMap
+{
+    pub fn new(mut map_photons : Vec<Photon>, gather_radius : f64) -> Self
+    {
+        let mut map_axes = vec![0; map_photons.len()];
+
+        PhotonMap::build(&mut map_photons, &mut map_axes);
+
+        PhotonMap
+        {
+            photons : map_photons,
+            axes : map_axes,
+            radius : gather_radius
+        }
+    }
+
+    // Splits each range at its median along the axis it extends furthest in
+    fn build(photons : &mut [Photon], axes : &mut [usize])
+    {
+        if photons.is_empty()
+        { return; }
+
+        let mut min = photons[0].position;
+        let mut max = photons[0].position;
+
+        for photon in photons.iter()
+        {
+            let p = photon.position;
+
+            min = Vector { x : min.x.min(p.x), y : min.y.min(p.y), z : min.z.min(p.z) };
+            max = Vector { x : max.x.max(p.x), y : max.y.max(p.y), z : max.z.max(p.z) };
+        }
+
+        let extent = max - min;
+        let axis =
+            if extent.x >= extent.y && extent.x >= extent.z { 0 }
+            else if extent.y >= extent.z { 1 }
+            else { 2 };
+
+        let mid = photons.len() / 2;
+
+        photons.select_nth_unstable_by(mid, |a, b| axis_value(a.position, axis).total_cmp(&axis_value(b.position, axis)));
+        axes[mid] = axis;
+
+        let (left_photons, right_photons) = photons.split_at_mut(mid);
+        let (left_axes, right_axes) = axes.split_at_mut(mid);
+
+        PhotonMap::build(left_photons, left_axes);
+        PhotonMap::build(&mut right_photons[1..], &mut right_axes[1..]);
+    }
+
+    pub fn len(&self) -> usize
+    { return self.photons.len(); }
+
+    // Calls found for every photon within the gather radius of point
+    fn gather(&self, start : usize, end : usize, point : Vector, found : &mut dyn FnMut(&Photon))
+    {
+        if start >= end
+        { return; }
+
+        let mid = start + (end - start) / 2;
+        let photon = &self.photons[mid];
+        let offset = point - photon.position;
+
+        if offset.dot_product(offset) <= self.radius * self.radius
+        { found(photon); }
+
+        let axis = self.axes[mid];
+        let delta = axis_value(point, axis) - axis_value(photon.position, axis);
+
+        // Visit the side containing the point first, the other only if the gather sphere reaches across the split
+        let (near, far) =
+            if delta <= 0.0
+            { ((start, mid), (mid + 1, end)) }
+            else
+            { ((mid + 1, end), (start, mid)) };
+
+        self.gather(near.0, near.1, point, found);
+
+        if delta.abs() <= self.radius
+        { self.gather(far.0, far.1, point, found); }
+    }
+
+    // Radiance reflected by a diffuse surface from the photons around point, facing_normal points to the side being shaded
+    pub fn radiance(&self, point : Vector, facing_normal : Vector, albedo : Color) -> Color
+    {
+        let mut flux = Color::new_rgb(0.0, 0.0, 0.0);
+
+        self.gather(0, self.photons.len(), point, &mut |photon|
+        {
+            if photon.incoming.dot_product(facing_normal) < 0.0
+            { flux = flux + photon.power; }
+        });
+
+        return albedo.mul_by_color(flux) * (FRAC_1_PI / (PI * self.radius * self.radius));
+    }
+}
+
+// Follows a photon from a light through specular bounces, returning it where it lands on a diffuse surface
+fn trace_photon(scene : &Scene, photon_count : u32, max_bounces : u32) -> Option<Photon>
+{
+    let sample = sample_emission(scene)?;
+    let emission = sample.object.body.get_material().emission;
+    let cos_theta = sample.normal.dot_product(sample.ray.dest).abs();
+
+    let power = Color::new_rgb(1.0, 1.0, 1.0) * (emission * cos_theta / (sample.pdf_pos * sample.pdf_dir * photon_count as f64));
+    let mut ray = sample.ray;
+    let mut specular_bounces = 0;
+
+    // Photons are traced before the render starts, its progress doesn't count their rays
+    let mut rays = 0;
+
+    while specular_bounces <= max_bounces
+    {
+        let (object, hit_point, hit) = scene.next_surface(ray, &mut rays)?;
+        let normal = object.body.normal(hit_point, &hit);
+        let material = object.body.get_material();
+
+        ray.origin = hit_point;
+
+        match material.mat_type
+        {
+            // Light reaching diffuse surfaces directly is left to the integrator
+            MatType::Diffuse =>
+            {
+                if specular_bounces == 0
+                { return None; }
+
+                return Some(Photon { position : hit_point, incoming : ray.dest, power });
+            }
+
+            MatType::Specular => specular_brdf(normal, &mut ray),
+
+            MatType::Refractive => refractive_brdf(normal, &mut ray, material.ior.nominal()),
+
+            // Skipped by Scene::next_surface
+            MatType::Interface => {}
+        }
+
+        specular_bounces += 1;
+    }
+
+    return None;
+}
+
+// Stream of random numbers used by photons when rendering with a seed, pixels use the low streams
+const PHOTON_STREAM : u64 = u64::MAX;
+
+// Caustic photon map from the photons of the settings emitted by the scene's lights
+pub fn trace_caustics(scene : &Scene, settings : &RenderSettings) -> PhotonMap
+{
+    let photon_count = settings.caustic_photons;
+
+    let photons = (0..photon_count)
+        .into_par_iter()
+        .filter_map(|i|
+        {
+            random::reseed(settings.seed, PHOTON_STREAM, i as u64);
+
+            trace_photon(scene, photon_count, settings.max_bounces)
+        })
+        .collect();
+
+    return PhotonMap::new(photons, settings.caustic_radius);
+}
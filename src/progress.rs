@@ -0,0 +1,209 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crossterm::{cursor, queue, terminal};
+
+// Width of the terminal progress bar in characters
+const BAR_WIDTH : usize = 30;
+// Time between redraws of the progress bar and between lines when output is redirected
+const BAR_INTERVAL : Duration = Duration::from_millis(100);
+const LINE_INTERVAL : Duration = Duration::from_secs(10);
+
+/// State of a running render.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress
+{
+    /// Camera samples traced so far, every pass traces one sample for each pixel.
+    pub samples_done : u64,
+    /// Camera samples the render traces in total, an estimate for time limited renders.
+    pub samples_total : u64,
+    /// Finished passes, the samples per pixel of the image so far.
+    pub passes_done : u32,
+    /// Rays intersected with the scene so far, including shadow rays.
+    pub rays : u64,
+    /// Budget of a time limited render, which ends when it runs out instead of after a number of samples.
+    pub time_limit : Option<Duration>,
+    /// Time since the render started.
+    pub elapsed : Duration
+}
+
+impl Progress
+{
+    /// Part of the render that is done, between 0 and 1.
+    pub fn fraction(&self) -> f64
+    {
+        if let Some(limit) = self.time_limit
+        { return (self.elapsed.as_secs_f64() / limit.as_secs_f64()).min(1.0); }
+
+        if self.samples_total == 0
+        { return 1.0; }
+
+        return self.samples_done as f64 / self.samples_total as f64;
+    }
+
+    /// Time left if the remaining samples take as long as the ones that are done, None before anything is done.
+    pub fn eta(&self) -> Option<Duration>
+    {
+        if let Some(limit) = self.time_limit
+        { return Some(limit.saturating_sub(self.elapsed)); }
+
+        if self.samples_done == 0
+        { return None; }
+
+        let remaining = (self.samples_total - self.samples_done) as f64 / self.samples_done as f64;
+
+        return Some(self.elapsed.mul_f64(remaining));
+    }
+
+    /// Camera samples traced per second so far.
+    pub fn samples_per_second(&self) -> f64
+    { return self.samples_done as f64 / self.elapsed.as_secs_f64().max(1e-9); }
+
+    /// Rays intersected per second so far.
+    pub fn rays_per_second(&self) -> f64
+    { return self.rays as f64 / self.elapsed.as_secs_f64().max(1e-9); }
+}
+
+/// Receives the progress of a render, see [`render_with_progress`](crate::render_with_progress). Reports come from the
+/// thread driving the render, in order.
+pub trait ProgressReporter : Sync
+{
+    /// Called whenever part of the image is finished.
+    fn update(&self, progress : &Progress);
+
+    /// Called once after the last update.
+    fn finish(&self, _progress : &Progress) {}
+
+    /// Notes about the render, like the size of the caustic photon map.
+    fn message(&self, _text : &str) {}
+}
+
+/// Ignores all progress.
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress
+{
+    fn update(&self, _progress : &Progress) {}
+}
+
+/// Reports to stdout, as a progress bar redrawn in place on terminals and as a line every few seconds
+/// when output is redirected.
+pub struct ConsoleProgress
+{
+    terminal : bool,
+    last_report : Mutex<Option<Instant>>
+}
+
+impl ConsoleProgress
+{
+    /// Reporter that draws a progress bar if stdout is a terminal.
+    pub fn new() -> Self
+    {
+        ConsoleProgress
+        {
+            terminal : io::stdout().is_terminal(),
+            last_report : Mutex::new(None)
+        }
+    }
+
+    fn line(progress : &Progress) -> String
+    {
+        let eta = match progress.eta()
+        {
+            Some(eta) => format_duration(eta),
+            None => "-".to_string()
+        };
+
+        return format!("{:5.1}%  {} spp  ETA {}  {} samples/s  {} rays/s", progress.fraction() * 100.0, progress.passes_done, eta,
+            format_rate(progress.samples_per_second()), format_rate(progress.rays_per_second()));
+    }
+
+    fn draw_bar(progress : &Progress)
+    {
+        let filled = ((progress.fraction() * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+        let bar = format!("[{}{}] {}", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), ConsoleProgress::line(progress));
+        let mut stdout = io::stdout();
+
+        // Progress is best effort, a failed redraw is skipped
+        let _ = queue!(stdout, cursor::MoveToColumn(0), terminal::Clear(terminal::ClearType::CurrentLine));
+        let _ = write!(stdout, "{}", bar);
+        let _ = stdout.flush();
+    }
+}
+
+impl Default for ConsoleProgress
+{
+    fn default() -> Self
+    { ConsoleProgress::new() }
+}
+
+impl ProgressReporter for ConsoleProgress
+{
+    fn update(&self, progress : &Progress)
+    {
+        let mut last_report = self.last_report.lock().unwrap();
+        let interval = if self.terminal { BAR_INTERVAL } else { LINE_INTERVAL };
+
+        if last_report.is_some_and(|last| last.elapsed() < interval)
+        { return; }
+
+        *last_report = Some(Instant::now());
+
+        if self.terminal
+        { ConsoleProgress::draw_bar(progress); }
+        else
+        { println!("Rendering {}", ConsoleProgress::line(progress)); }
+    }
+
+    fn finish(&self, progress : &Progress)
+    {
+        if self.terminal
+        {
+            ConsoleProgress::draw_bar(progress);
+            println!();
+        }
+        else
+        {
+            println!("Rendered {} spp in {}  {} samples/s  {} rays/s", progress.passes_done, format_duration(progress.elapsed),
+                format_rate(progress.samples_per_second()), format_rate(progress.rays_per_second()));
+        }
+    }
+
+    fn message(&self, text : &str)
+    {
+        // The bar is drawn again by the next update
+        if self.terminal
+        {
+            let mut stdout = io::stdout();
+            let _ = queue!(stdout, cursor::MoveToColumn(0), terminal::Clear(terminal::ClearType::CurrentLine));
+        }
+
+        println!("{}", text);
+    }
+}
+
+// 1h 02m 03s, 2m 03s or 3s
+pub fn format_duration(duration : Duration) -> String
+{
+    let seconds = duration.as_secs();
+
+    if seconds >= 3600
+    { return format!("{}h {:02}m {:02}s", seconds / 3600, seconds / 60 % 60, seconds % 60); }
+
+    if seconds >= 60
+    { return format!("{}m {:02}s", seconds / 60, seconds % 60); }
+
+    return format!("{}s", seconds);
+}
+
+// Rates with a metric suffix, 12.3M
+fn format_rate(rate : f64) -> String
+{
+    return match rate
+    {
+        r if r >= 1e9 => format!("{:.1}G", r / 1e9),
+        r if r >= 1e6 => format!("{:.1}M", r / 1e6),
+        r if r >= 1e3 => format!("{:.1}k", r / 1e3),
+        r => format!("{:.0}", r)
+    };
+}
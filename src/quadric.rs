@@ -0,0 +1,784 @@
+use crate::aabb::Aabb;
+use crate::csg::interval_intersection;
+use crate::material::Material;
+use crate::object::{plain_intervals, Body, Hit};
+use crate::ray::Ray;
+use crate::vector::{orthonormal_sys, Vector};
+
+const EPSILON : f64 = 0.000001;
+const TWO_PI : f64 = 2.0 * std::f64::consts::PI;
+
+// Orthonormal frame in which a quadric has its axis along z
+#[derive(Clone)]
+struct Frame
+{
+    origin : Vector,
+    u : Vector,
+    v : Vector,
+    w : Vector
+}
+
+impl Frame
+{
+    fn new(frame_origin : Vector, mut axis : Vector) -> Self
+    {
+        axis.normalize();
+
+        let mut frame_u = Vector::new();
+        let mut frame_v = Vector::new();
+
+        orthonormal_sys(axis, &mut frame_u, &mut frame_v);
+
+        Frame
+        {
+            origin : frame_origin,
+            u : frame_u,
+            v : frame_v,
+            w : axis
+        }
+    }
+
+    fn to_local_dir(&self, dir : Vector) -> Vector
+    {
+        Vector
+        {
+            x : dir.dot_product(self.u),
+            y : dir.dot_product(self.v),
+            z : dir.dot_product(self.w)
+        }
+    }
+
+    fn to_local(&self, point : Vector) -> Vector
+    { return self.to_local_dir(point - self.origin); }
+
+    fn to_local_ray(&self, ray : Ray) -> Ray
+    {
+        Ray
+        {
+            origin : self.to_local(ray.origin),
+            dest : self.to_local_dir(ray.dest)
+        }
+    }
+
+    fn to_world_dir(&self, dir : Vector) -> Vector
+    { return self.u * dir.x + self.v * dir.y + self.w * dir.z; }
+
+    // Bounds of a circle of the given radius swept along the frame's axis from 0 to height
+    fn swept_disk_bounds(&self, radius : f64, height : f64) -> Aabb
+    {
+        let axis = [self.w.x, self.w.y, self.w.z];
+        let extent = axis.map(|a| radius * (1.0 - a * a).max(0.0).sqrt());
+        let extent = Vector { x : extent[0], y : extent[1], z : extent[2] };
+
+        let start = self.origin;
+        let end = self.origin + self.w * height;
+        let bounds = Aabb::from_points(&[start, end]);
+
+        return Aabb::new(bounds.min - extent, bounds.max + extent);
+    }
+}
+
+// Angle around the local z axis mapped to [0, 1]
+fn azimuth(local : Vector) -> f64
+{ return 0.5 + local.y.atan2(local.x) / TWO_PI; }
+
+// Intersection with the z = height plane inside the given radius
+fn intersect_cap(local_ray : Ray, height : f64, radius : f64) -> Option<f64>
+{
+    if local_ray.dest.z.abs() < EPSILON
+    { return None; }
+
+    let t = (height - local_ray.origin.z) / local_ray.dest.z;
+    let point = local_ray.origin + local_ray.dest * t;
+
+    if t > EPSILON && point.x * point.x + point.y * point.y <= radius * radius
+    { return Some(t); }
+
+    return None;
+}
+
+fn nearest(candidates : &[Option<f64>]) -> Option<Hit>
+{
+    let nearest = candidates.iter()
+        .flatten()
+        .filter(|t| **t > EPSILON)
+        .fold(f64::INFINITY, |acc, t| acc.min(*t));
+
+    if nearest.is_finite()
+    { return Some(Hit::new(nearest)); }
+
+    return None;
+}
+
+// Span of the local ray's line between the planes z = low and z = high
+fn slab_interval(local_ray : Ray, low : f64, high : f64) -> Vec<(f64, f64)>
+{
+    let o = local_ray.origin.z;
+    let d = local_ray.dest.z;
+
+    if d.abs() < 1e-12
+    {
+        if o >= low && o <= high
+        { return vec![(f64::NEG_INFINITY, f64::INFINITY)]; }
+
+        return vec![];
+    }
+
+    let t0 = (low - o) / d;
+    let t1 = (high - o) / d;
+
+    return vec![(t0.min(t1), t0.max(t1))];
+}
+
+// Spans where a * t^2 + b * t + c <= 0
+fn quadratic_inside(a : f64, b : f64, c : f64) -> Vec<(f64, f64)>
+{
+    if a.abs() < 1e-12
+    {
+        if b.abs() < 1e-12
+        {
+            if c <= 0.0
+            { return vec![(f64::NEG_INFINITY, f64::INFINITY)]; }
+
+            return vec![];
+        }
+
+        let t = -c / b;
+
+        if b > 0.0
+        { return vec![(f64::NEG_INFINITY, t)]; }
+
+        return vec![(t, f64::INFINITY)];
+    }
+
+    let roots = solve_quadratic(a, b, c);
+
+    if roots.len() < 2
+    {
+        // Parabola does not cross zero or only touches it
+        if a > 0.0
+        { return vec![]; }
+
+        return vec![(f64::NEG_INFINITY, f64::INFINITY)];
+    }
+
+    if a > 0.0
+    { return vec![(roots[0], roots[1])]; }
+
+    return vec![(f64::NEG_INFINITY, roots[0]), (roots[1], f64::INFINITY)];
+}
+
+// Real roots of a * x^2 + b * x + c in ascending order
+fn solve_quadratic(a : f64, b : f64, c : f64) -> Vec<f64>
+{
+    if a.abs() < 1e-12
+    {
+        if b.abs() < 1e-12
+        { return vec![]; }
+
+        return vec![-c / b];
+    }
+
+    let discr = b * b - 4.0 * a * c;
+
+    if discr < 0.0
+    { return vec![]; }
+
+    // Numerically stable form avoiding cancellation
+    let q = -0.5 * (b + b.signum() * discr.sqrt());
+
+    if q == 0.0
+    { return vec![0.0]; }
+
+    let mut roots = vec![q / a, c / q];
+    roots.sort_by(|x, y| x.total_cmp(y));
+
+    return roots;
+}
+
+// Real roots of x^3 + a * x^2 + b * x + c
+fn solve_cubic(a : f64, b : f64, c : f64) -> Vec<f64>
+{
+    // Substitute x = y - a / 3 to get y^3 + p * y + q
+    let sq_a = a * a;
+    let p = (3.0 * b - sq_a) / 9.0;
+    let q = (2.0 * a * sq_a - 9.0 * a * b + 27.0 * c) / 54.0;
+    let p_cube = p * p * p;
+    let discr = q * q + p_cube;
+    let shift = a / 3.0;
+
+    if discr.abs() < 1e-14
+    {
+        if q.abs() < 1e-14
+        { return vec![-shift]; }
+
+        let u = (-q).cbrt();
+
+        return vec![2.0 * u - shift, -u - shift];
+    }
+
+    if discr < 0.0
+    {
+        // Three real roots
+        let phi = (-q / (-p_cube).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+
+        return vec![
+            t * phi.cos() - shift,
+            -t * (phi + std::f64::consts::PI / 3.0).cos() - shift,
+            -t * (phi - std::f64::consts::PI / 3.0).cos() - shift
+        ];
+    }
+
+    let sqrt_discr = discr.sqrt();
+    let u = (sqrt_discr - q).cbrt();
+    let v = -(sqrt_discr + q).cbrt();
+
+    return vec![u + v - shift];
+}
+
+// Real roots of c4 * x^4 + c3 * x^3 + c2 * x^2 + c1 * x + c0 using Ferrari's method
+fn solve_quartic(c4 : f64, c3 : f64, c2 : f64, c1 : f64, c0 : f64) -> Vec<f64>
+{
+    let a = c3 / c4;
+    let b = c2 / c4;
+    let c = c1 / c4;
+    let d = c0 / c4;
+
+    // Substitute x = y - a / 4 to get y^4 + p * y^2 + q * y + r
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = 1.0 / 8.0 * sq_a * a - 0.5 * a * b + c;
+    let r = -3.0 / 256.0 * sq_a * sq_a + 1.0 / 16.0 * sq_a * b - 0.25 * a * c + d;
+
+    let mut roots : Vec<f64>;
+
+    if r.abs() < 1e-14
+    {
+        // y * (y^3 + p * y + q) = 0
+        roots = solve_cubic(0.0, p, q);
+        roots.push(0.0);
+    }
+    else
+    {
+        // One real root of the resolvent cubic
+        let z = solve_cubic(-0.5 * p, -r, 0.5 * r * p - 0.125 * q * q)[0];
+
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+
+        if u < -1e-12 || v < -1e-12
+        { return vec![]; }
+
+        let u = u.max(0.0).sqrt();
+        let v = if q < 0.0 { -(v.max(0.0).sqrt()) } else { v.max(0.0).sqrt() };
+
+        roots = solve_quadratic(1.0, v, z - u);
+        roots.extend(solve_quadratic(1.0, -v, z + u));
+    }
+
+    let shift = 0.25 * a;
+
+    // Polish the roots with Newton's method to counter the loss of precision
+    return roots.iter().map(|root|
+    {
+        let mut x = root - shift;
+
+        for _ in 0..2
+        {
+            let value = (((c4 * x + c3) * x + c2) * x + c1) * x + c0;
+            let derivative = ((4.0 * c4 * x + 3.0 * c3) * x + 2.0 * c2) * x + c1;
+
+            if derivative.abs() > 1e-12
+            { x -= value / derivative; }
+        }
+
+        x
+    }).collect();
+}
+
+// Cylinder with both ends capped, standing on its base centre along the axis
+#[derive(Clone)]
+pub struct Cylinder
+{
+    frame : Frame,
+    radius : f64,
+    height : f64,
+    material : Material
+}
+
+impl Cylinder
+{
+    pub fn new(base : Vector, axis : Vector, rad : f64, h : f64, mat : Material) -> Self
+    {
+        Cylinder
+        {
+            frame : Frame::new(base, axis),
+            radius : rad,
+            height : h,
+            material : mat
+        }
+    }
+}
+
+impl Body for Cylinder
+{
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        let local_ray = self.frame.to_local_ray(ray);
+        let o = local_ray.origin;
+        let d = local_ray.dest;
+
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+
+        let mut candidates : Vec<Option<f64>> = solve_quadratic(a, b, c).into_iter()
+            .filter(|t| (0.0..=self.height).contains(&(o.z + d.z * t)))
+            .map(Some)
+            .collect();
+
+        candidates.push(intersect_cap(local_ray, 0.0, self.radius));
+        candidates.push(intersect_cap(local_ray, self.height, self.radius));
+
+        return nearest(&candidates);
+    }
+
+    fn intervals(&self, ray : Ray) -> Option<Vec<(Hit, Hit)>>
+    {
+        let local_ray = self.frame.to_local_ray(ray);
+        let o = local_ray.origin;
+        let d = local_ray.dest;
+
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+
+        return Some(interval_intersection(&plain_intervals(quadratic_inside(a, b, c)),
+            &plain_intervals(slab_interval(local_ray, 0.0, self.height))));
+    }
+
+    fn normal(&self, vec : Vector, _hit : &Hit) -> Vector
+    {
+        let local = self.frame.to_local(vec);
+        let dist_side = ((local.x * local.x + local.y * local.y).sqrt() - self.radius).abs();
+        let dist_bottom = local.z.abs();
+        let dist_top = (local.z - self.height).abs();
+
+        if dist_bottom < dist_side && dist_bottom <= dist_top
+        { return self.frame.w * -1.0; }
+
+        if dist_top < dist_side
+        { return self.frame.w; }
+
+        let mut radial = Vector { x : local.x, y : local.y, z : 0.0 };
+
+        return self.frame.to_world_dir(*radial.normalize());
+    }
+
+    // Azimuth and height along the side, caps use the distance from the axis
+    fn uv(&self, vec : Vector, _hit : &Hit) -> (f64, f64)
+    {
+        let local = self.frame.to_local(vec);
+        let dist_axis = (local.x * local.x + local.y * local.y).sqrt();
+
+        if dist_axis < self.radius - EPSILON
+        { return (azimuth(local), dist_axis / self.radius); }
+
+        return (azimuth(local), local.z / self.height);
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    { return Some(self.frame.swept_disk_bounds(self.radius, self.height)); }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone();
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+}
+
+// Cone with a capped base, the apex lies height units along the axis
+#[derive(Clone)]
+pub struct Cone
+{
+    frame : Frame,
+    radius : f64,
+    height : f64,
+    material : Material
+}
+
+impl Cone
+{
+    pub fn new(base : Vector, axis : Vector, rad : f64, h : f64, mat : Material) -> Self
+    {
+        Cone
+        {
+            frame : Frame::new(base, axis),
+            radius : rad,
+            height : h,
+            material : mat
+        }
+    }
+}
+
+impl Body for Cone
+{
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        let local_ray = self.frame.to_local_ray(ray);
+        let o = local_ray.origin;
+        let d = local_ray.dest;
+
+        // Radius shrinks linearly as k * (height - z)
+        let k = self.radius / self.height;
+        let sq_k = k * k;
+        let w = self.height - o.z;
+
+        let a = d.x * d.x + d.y * d.y - sq_k * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + sq_k * w * d.z);
+        let c = o.x * o.x + o.y * o.y - sq_k * w * w;
+
+        let mut candidates : Vec<Option<f64>> = solve_quadratic(a, b, c).into_iter()
+            .filter(|t| (0.0..=self.height).contains(&(o.z + d.z * t)))
+            .map(Some)
+            .collect();
+
+        candidates.push(intersect_cap(local_ray, 0.0, self.radius));
+
+        return nearest(&candidates);
+    }
+
+    // Within the slab below the apex the cone is where the quadratic is not positive
+    fn intervals(&self, ray : Ray) -> Option<Vec<(Hit, Hit)>>
+    {
+        let local_ray = self.frame.to_local_ray(ray);
+        let o = local_ray.origin;
+        let d = local_ray.dest;
+
+        let k = self.radius / self.height;
+        let sq_k = k * k;
+        let w = self.height - o.z;
+
+        let a = d.x * d.x + d.y * d.y - sq_k * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + sq_k * w * d.z);
+        let c = o.x * o.x + o.y * o.y - sq_k * w * w;
+
+        return Some(interval_intersection(&plain_intervals(quadratic_inside(a, b, c)),
+            &plain_intervals(slab_interval(local_ray, 0.0, self.height))));
+    }
+
+    fn normal(&self, vec : Vector, _hit : &Hit) -> Vector
+    {
+        let local = self.frame.to_local(vec);
+        let k = self.radius / self.height;
+        let dist_axis = (local.x * local.x + local.y * local.y).sqrt();
+        let dist_side = (dist_axis - k * (self.height - local.z)).abs();
+
+        if local.z.abs() < dist_side
+        { return self.frame.w * -1.0; }
+
+        let mut side_normal = Vector { x : local.x, y : local.y, z : k * dist_axis };
+
+        return self.frame.to_world_dir(*side_normal.normalize());
+    }
+
+    fn uv(&self, vec : Vector, _hit : &Hit) -> (f64, f64)
+    {
+        let local = self.frame.to_local(vec);
+
+        if local.z.abs() < EPSILON
+        { return (azimuth(local), (local.x * local.x + local.y * local.y).sqrt() / self.radius); }
+
+        return (azimuth(local), local.z / self.height);
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    { return Some(self.frame.swept_disk_bounds(self.radius, self.height)); }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone();
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+}
+
+// Flat circular disk facing along its normal
+#[derive(Clone)]
+pub struct Disk
+{
+    frame : Frame,
+    radius : f64,
+    material : Material
+}
+
+impl Disk
+{
+    pub fn new(centre : Vector, normal : Vector, rad : f64, mat : Material) -> Self
+    {
+        Disk
+        {
+            frame : Frame::new(centre, normal),
+            radius : rad,
+            material : mat
+        }
+    }
+}
+
+impl Body for Disk
+{
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        let local_ray = self.frame.to_local_ray(ray);
+
+        return nearest(&[intersect_cap(local_ray, 0.0, self.radius)]);
+    }
+
+    fn normal(&self, _vec : Vector, _hit : &Hit) -> Vector
+    { return self.frame.w; }
+
+    fn uv(&self, vec : Vector, _hit : &Hit) -> (f64, f64)
+    {
+        let local = self.frame.to_local(vec);
+
+        return (azimuth(local), (local.x * local.x + local.y * local.y).sqrt() / self.radius);
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    { return Some(self.frame.swept_disk_bounds(self.radius, 0.0)); }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone();
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+}
+
+// Torus lying in the plane perpendicular to its axis
+#[derive(Clone)]
+pub struct Torus
+{
+    frame : Frame,
+    // Distance from the centre to the middle of the tube
+    major_radius : f64,
+    // Radius of the tube
+    minor_radius : f64,
+    material : Material
+}
+
+impl Torus
+{
+    pub fn new(centre : Vector, axis : Vector, major : f64, minor : f64, mat : Material) -> Self
+    {
+        Torus
+        {
+            frame : Frame::new(centre, axis),
+            major_radius : major,
+            minor_radius : minor,
+            material : mat
+        }
+    }
+}
+
+impl Torus
+{
+    // Sorted distances along the ray's line at which it crosses the surface
+    fn roots(&self, ray : Ray) -> Vec<f64>
+    {
+        let local_ray = self.frame.to_local_ray(ray);
+        let bound_radius = self.major_radius + self.minor_radius;
+
+        // Start from the bounding sphere to keep the quartic coefficients small
+        let b = local_ray.origin.dot_product(local_ray.dest);
+        let c = local_ray.origin.dot_product(local_ray.origin) - bound_radius * bound_radius;
+        let discr = b * b - c;
+
+        if discr < 0.0
+        { return vec![]; }
+
+        let offset = -b - discr.sqrt();
+        let o = local_ray.origin + local_ray.dest * offset;
+        let d = local_ray.dest;
+
+        let sq_major = self.major_radius * self.major_radius;
+        let n = o.dot_product(d);
+        let k = o.dot_product(o) + sq_major - self.minor_radius * self.minor_radius;
+
+        let mut roots : Vec<f64> = solve_quartic(
+            1.0,
+            4.0 * n,
+            2.0 * k + 4.0 * n * n - 4.0 * sq_major * (d.x * d.x + d.y * d.y),
+            4.0 * n * k - 8.0 * sq_major * (o.x * d.x + o.y * d.y),
+            k * k - 4.0 * sq_major * (o.x * o.x + o.y * o.y))
+            .into_iter()
+            .map(|t| t + offset)
+            .collect();
+
+        roots.sort_by(|x, y| x.total_cmp(y));
+
+        return roots;
+    }
+}
+
+impl Body for Torus
+{
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        let candidates : Vec<Option<f64>> = self.roots(ray).into_iter().map(Some).collect();
+
+        return nearest(&candidates);
+    }
+
+    // Roots come in entry and exit pairs, an odd count means rounding lost a grazing root
+    fn intervals(&self, ray : Ray) -> Option<Vec<(Hit, Hit)>>
+    {
+        let roots = self.roots(ray);
+
+        if ! roots.len().is_multiple_of(2)
+        { return Some(vec![]); }
+
+        return Some(plain_intervals(roots.chunks(2).map(|pair| (pair[0], pair[1])).collect()));
+    }
+
+    fn normal(&self, vec : Vector, _hit : &Hit) -> Vector
+    {
+        let local = self.frame.to_local(vec);
+
+        // Direction from the closest point on the tube's centre circle
+        let mut ring_dir = Vector { x : local.x, y : local.y, z : 0.0 };
+        ring_dir.normalize();
+
+        let mut tube_normal = local - ring_dir * self.major_radius;
+
+        return self.frame.to_world_dir(*tube_normal.normalize());
+    }
+
+    // Angle around the axis and angle around the tube
+    fn uv(&self, vec : Vector, _hit : &Hit) -> (f64, f64)
+    {
+        let local = self.frame.to_local(vec);
+        let dist_axis = (local.x * local.x + local.y * local.y).sqrt();
+        let tube_angle = local.z.atan2(dist_axis - self.major_radius);
+
+        return (azimuth(local), 0.5 + tube_angle / TWO_PI);
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    {
+        let axis = [self.frame.w.x, self.frame.w.y, self.frame.w.z];
+        let extent = axis.map(|a| self.major_radius * (1.0 - a * a).max(0.0).sqrt() + self.minor_radius);
+        let extent = Vector { x : extent[0], y : extent[1], z : extent[2] };
+
+        return Some(Aabb::new(self.frame.origin - extent, self.frame.origin + extent));
+    }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone();
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::material::MatType;
+    use super::*;
+
+    fn vector(x : f64, y : f64, z : f64) -> Vector
+    { return Vector { x, y, z }; }
+
+    fn white() -> Material
+    { return Material::new(0xFFFFFF, MatType::Diffuse, 0.0); }
+
+    // Distance and normal of the closest hit of the ray from origin along dest
+    fn hit(body : &dyn Body, origin : Vector, dest : Vector) -> (f64, Vector)
+    {
+        let ray = Ray { origin, dest };
+        let hit = body.intersect(ray).unwrap();
+
+        return (hit.distance, body.normal(origin + dest * hit.distance, &hit));
+    }
+
+    fn assert_hit(actual : (f64, Vector), distance : f64, normal : Vector)
+    {
+        let error = actual.1 - normal;
+
+        assert!((actual.0 - distance).abs() < 1e-9, "distance {} is not {}", actual.0, distance);
+        assert!(error.dot_product(error) < 1e-18, "normal {:?} is not {:?}", actual.1, normal);
+    }
+
+    #[test]
+    fn cylinder_side_and_caps()
+    {
+        let up = vector(0.0, 1.0, 0.0);
+        let cylinder = Cylinder::new(vector(0.0, 0.0, 0.0), up, 1.0, 2.0, white());
+
+        assert_hit(hit(&cylinder, vector(-5.0, 1.0, 0.0), vector(1.0, 0.0, 0.0)), 4.0, vector(-1.0, 0.0, 0.0));
+        assert_hit(hit(&cylinder, vector(0.5, 5.0, 0.0), up * -1.0), 3.0, up);
+        assert_hit(hit(&cylinder, vector(0.5, -5.0, 0.0), up), 5.0, up * -1.0);
+
+        // From inside the top cap is hit
+        assert_hit(hit(&cylinder, vector(0.0, 1.0, 0.0), up), 1.0, up);
+
+        // Above the top cap and beside the side
+        assert!(cylinder.intersect(Ray { origin : vector(-5.0, 2.5, 0.0), dest : vector(1.0, 0.0, 0.0) }).is_none());
+        assert!(cylinder.intersect(Ray { origin : vector(1.5, -5.0, 0.0), dest : up }).is_none());
+    }
+
+    #[test]
+    fn cone_side_and_base()
+    {
+        // Radius 1 at the base, shrinking to the apex at a height of 2
+        let up = vector(0.0, 1.0, 0.0);
+        let cone = Cone::new(vector(0.0, 0.0, 0.0), up, 1.0, 2.0, white());
+
+        let slant = 1.25_f64.sqrt();
+        assert_hit(hit(&cone, vector(-5.0, 1.0, 0.0), vector(1.0, 0.0, 0.0)), 4.5, vector(-1.0 / slant, 0.5 / slant, 0.0));
+        assert_hit(hit(&cone, vector(0.2, -5.0, 0.0), up), 5.0, up * -1.0);
+
+        // Above the apex
+        assert!(cone.intersect(Ray { origin : vector(-5.0, 2.5, 0.0), dest : vector(1.0, 0.0, 0.0) }).is_none());
+    }
+
+    #[test]
+    fn quartic_roots()
+    {
+        let sorted = |mut roots : Vec<f64>| { roots.sort_by(|x, y| x.total_cmp(y)); roots };
+        let assert_roots = |roots : Vec<f64>, expected : &[f64]|
+        {
+            assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+            assert!(roots.iter().zip(expected).all(|(root, expected)| (root - expected).abs() < 1e-6), "{:?}", roots);
+        };
+
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(sorted(solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0)), &[1.0, 2.0, 3.0, 4.0]);
+        // 2 (x + 3)(x - 0.5)(x^2 + 1), two roots are complex
+        assert_roots(sorted(solve_quartic(2.0, 5.0, -1.0, 5.0, -3.0)), &[-3.0, 0.5]);
+        // x^4 + 1 has no real roots
+        assert_roots(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+        // x (x - 1)(x + 1)(x - 2), with a root at 0
+        assert_roots(sorted(solve_quartic(1.0, -2.0, -1.0, 2.0, 0.0)), &[-1.0, 0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn torus_through_both_sides_of_the_tube()
+    {
+        // Ring of radius 2 around the y axis with a tube of radius 0.5
+        let up = vector(0.0, 1.0, 0.0);
+        let torus = Torus::new(vector(0.0, 0.0, 0.0), up, 2.0, 0.5, white());
+        let along_x = Ray { origin : vector(-5.0, 0.0, 0.0), dest : vector(1.0, 0.0, 0.0) };
+
+        assert_hit(hit(&torus, along_x.origin, along_x.dest), 2.5, vector(-1.0, 0.0, 0.0));
+
+        let spans : Vec<(f64, f64)> = torus.intervals(along_x).unwrap().iter().map(|(start, end)| (start.distance, end.distance)).collect();
+        let expected = [(2.5, 3.5), (6.5, 7.5)];
+        assert_eq!(spans.len(), 2);
+        assert!(spans.iter().zip(expected).all(|(span, expected)| (span.0 - expected.0).abs() < 1e-9 && (span.1 - expected.1).abs() < 1e-9),
+            "{:?}", spans);
+
+        // Top of the tube, and through the hole without touching it
+        assert_hit(hit(&torus, vector(-2.0, 5.0, 0.0), up * -1.0), 4.5, up);
+        assert!(torus.intersect(Ray { origin : vector(0.0, -5.0, 0.0), dest : up }).is_none());
+    }
+}
@@ -1,9 +1,35 @@
-use crate::rand::distributions::Distribution;
+use std::cell::RefCell;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+thread_local!
+{
+    // Generator of the work item the thread is on when rendering with a seed, None draws from the thread's own generator
+    static SEEDED : RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
 
 pub fn gen_num() -> f64
 {
-    let distribution = rand::distributions::Uniform::<f64>::from(0.0..1.0);
-    let mut rng = rand::thread_rng();
+    return SEEDED.with(|seeded| match seeded.borrow_mut().as_mut()
+    {
+        Some(rng) => rng.gen_range(0.0..1.0),
+        None => rand::thread_rng().gen_range(0.0..1.0)
+    });
+}
+
+// Seeds the calling thread's numbers for one work item, so results don't depend on which thread picks it up.
+// Different kinds of work use different streams so their items don't share numbers.
+pub fn reseed(seed : Option<u64>, stream : u64, item : u64)
+{
+    // splitmix64 finalizer spreads neighbouring items over unrelated generators
+    let generator = seed.map(|seed|
+    {
+        let mut z = seed ^ stream.wrapping_mul(0xD6E8_FEB8_6659_FD93) ^ item.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+        StdRng::seed_from_u64(z ^ (z >> 31))
+    });
 
-    return distribution.sample(& mut rng)
+    SEEDED.with(|seeded| *seeded.borrow_mut() = generator);
 }
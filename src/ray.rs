@@ -1,6 +1,6 @@
 use crate::vector::Vector;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub struct Ray
 {
     pub origin : Vector,
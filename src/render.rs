@@ -1,13 +1,72 @@
 use rayon::prelude::*;
+use std::io;
+use std::ops::Range;
 use std::time::Instant;
-use std::io::{self};
 use crate::ray::Ray;
 use crate::scene::Scene;
 use crate::{Color, Image, MatType, random, Vector};
 use crate::vector::orthonormal_sys;
-use crossterm::{execute, cursor, terminal};
+use crate::lights::{emitted, light_pdf, power_heuristic, sample_light};
+use crate::medium::Medium;
+use crate::spectrum::Wavelengths;
+use crate::bdpt::{trace_bidirectional, SplatBuffer};
+use crate::photon_map::{trace_caustics, PhotonMap};
+use crate::debug::{trace_debug, DebugContext};
+use crate::render_settings::{CropWindow, RenderSettings};
+use crate::progress::{NoProgress, Progress, ProgressReporter};
+use crate::cancel::CancelToken;
+use std::f64::consts::FRAC_1_PI;
+
+/// Algorithm computing the value of each pixel. The debug views show properties of the scene instead of
+/// radiance and are not scaled by the camera's exposure.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Integrator
+{
+    /// Unidirectional path tracing with next event estimation.
+    Path,
+    /// Bidirectional path tracing, traced in RGB without participating media.
+    Bidirectional,
+    /// Debug view of the normal of the first surface seen, mapped from [-1, 1] to [0, 1].
+    Normals,
+    /// Debug view of the colour of the first surface seen, including its texture.
+    Albedo,
+    /// Debug view of the distance to the first surface seen, normalized by the scene's extent.
+    Depth,
+    /// Debug view of ambient occlusion within the settings' `ao_radius`.
+    AmbientOcclusion,
+    /// Debug view of polygon edges over surfaces shaded by the angle they are seen at.
+    Wireframe,
+    /// Debug heatmap of the bounces paths of the path integrator make.
+    PathLength,
+    /// Debug heatmap of the bounding volume hierarchy nodes the camera rays visit.
+    NodeVisits
+}
+
+impl Integrator
+{
+    /// Integrator named like in scene settings and on the command line: `path`, `bdpt`, `normals`,
+    /// `albedo`, `depth`, `ao`, `wireframe`, `path_length` or `bvh_visits`.
+    pub fn from_name(name : &str) -> Option<Integrator>
+    {
+        return match name
+        {
+            "path" => Some(Integrator::Path),
+            "bdpt" => Some(Integrator::Bidirectional),
+            "normals" => Some(Integrator::Normals),
+            "albedo" => Some(Integrator::Albedo),
+            "depth" => Some(Integrator::Depth),
+            "ao" => Some(Integrator::AmbientOcclusion),
+            "wireframe" => Some(Integrator::Wireframe),
+            "path_length" => Some(Integrator::PathLength),
+            "bvh_visits" => Some(Integrator::NodeVisits),
+            _ => None
+        };
+    }
 
-const REFRACTION_INDEX : f64 = 1.52;
+    // Debug views show values directly instead of radiance
+    pub(crate) fn is_debug(&self) -> bool
+    { return ! matches!(self, Integrator::Path | Integrator::Bidirectional); }
+}
 
 // Code for both sampling methods based on www.rorydriscoll.com/2009/01/07/better-sampling/
 
@@ -35,13 +94,13 @@ pub fn cosine_weighted_hemisphere_sample(u1 : f64, u2 : f64) -> Vector
     
     Vector
     {
-        x: x,
-        y: y,
+        x,
+        y,
         z: (1.0 - u1).max(0.0).sqrt()
     }
 }
 
-fn diffuse_brdf(surface_normal : Vector, ray : &mut Ray) -> f64
+pub fn diffuse_brdf(surface_normal : Vector, ray : &mut Ray)
 {
     let mut rot_x = Vector::new();
     let mut rot_y = Vector::new();
@@ -62,13 +121,9 @@ fn diffuse_brdf(surface_normal : Vector, ray : &mut Ray) -> f64
     rot_dir.z = Vector::new().set_vector(rot_x.z, rot_y.z, surface_normal.z).dot_product(sampled_dir);
 
     ray.dest = rot_dir;
-
-    let cos_theta = ray.dest.dot_product(surface_normal);
-
-    return  cos_theta;
 }
 
-fn specular_brdf(surface_normal : Vector, ray : &mut Ray)
+pub fn specular_brdf(surface_normal : Vector, ray : &mut Ray)
 {
     let cos_theta = ray.dest.dot_product(surface_normal);
     
@@ -76,9 +131,9 @@ fn specular_brdf(surface_normal : Vector, ray : &mut Ray)
     ray.dest.normalize();
 }
 
-fn refractive_brdf(mut surface_normal : Vector, ray : &mut Ray)
+pub fn refractive_brdf(mut surface_normal : Vector, ray : &mut Ray, ior : f64)
 {
-    let mut n = REFRACTION_INDEX;
+    let mut n = ior;
     let mut r0 = (1.0 - n) / (1.0 + n);
 
     r0 = r0 * r0;
@@ -91,7 +146,7 @@ fn refractive_brdf(mut surface_normal : Vector, ray : &mut Ray)
 
     n = 1.0 / n;
     
-    let cos_theta1 = surface_normal.dot_product(ray.dest) * -1.0;
+    let cos_theta1 = -surface_normal.dot_product(ray.dest);
     let cos_theta2 = 1.0 - n * n * (1.0 - cos_theta1 * cos_theta1);
     
     // Refraction probabilaty via Shlick's approximation
@@ -113,142 +168,521 @@ fn refractive_brdf(mut surface_normal : Vector, ray : &mut Ray)
     }
 }
 
-fn trace(scene : &Scene, mut ray: Ray, depth : i32, mut color: &mut Color)
+// Colours are upsampled to the path's wavelengths in spectral mode
+fn path_color(color : Color, wavelengths : Option<Wavelengths>) -> Color
 {
-    // Russian roulette 
-    let mut rr_factor = 1.0;
-    
-    // Prevent early path termination
-    if depth >= 5
+    return match wavelengths
     {
-        let rr_stop = 0.1;
+        Some(w) => w.upsample(color),
+        None => color
+    };
+}
 
-        // End recursion
-        if random::gen_num() <= rr_stop
-        { return; }
-        
-        rr_factor = 1.0 / (1.0 - rr_stop);
-    }
+// In spectral mode the channels of the returned colour hold radiance at the given wavelengths.
+// With a caustic map, light reaching diffuse surfaces through specular bounces is taken from it instead.
+// The rays cast are added to rays.
+fn trace(scene : &Scene, settings : &RenderSettings, mut ray : Ray, mut wavelengths : Option<Wavelengths>,
+    caustics : Option<&PhotonMap>, rays : &mut u64) -> Color
+{
+    let mut color = Color::new_rgb(0.0, 0.0, 0.0);
+    let mut throughput = Color::new_rgb(1.0, 1.0, 1.0);
+    let mut bounce = 0;
 
-    // Find intercestion
-    let intersection = scene.intersect(ray);
+    // Camera rays start outside of all bodies
+    let mut medium = scene.atmosphere.as_ref().map(|m| m.at_wavelengths(wavelengths));
 
-    // End recursion if no intersection is found
-    if ! intersection.found
-    { return; }
+    // Solid angle density of the last diffuse or medium bounce, None for camera rays and specular bounces
+    let mut bsdf_pdf : Option<f64> = None;
 
-    // Calculate hit point of a ray and an objecet
-    let hit_point = ray.origin + ray.dest * intersection.distance;
-    let surface_normal = intersection.object.body.normal(hit_point);
+    // Where the path last scattered, medium boundaries are passed without scattering
+    let mut last_vertex = ray.origin;
 
-    // Hit point becomes new rays origin
-    ray.origin = hit_point;
+    // The last vertex that was not specular is a diffuse surface, so a light hit now is a caustic
+    let mut after_diffuse = false;
 
-    let emission = intersection.object.body.get_material().emission;
-    
-    // Add emmission of an object to accumulated color 
-    color.r = color.r + (emission * rr_factor);
-    color.g = color.g + (emission * rr_factor);
-    color.b = color.b + (emission * rr_factor);
+    loop
+    {
+        // Find intercestion
+        *rays += 1;
+        let intersection = scene.intersect(ray);
 
+        // Free flight through the medium up to the next surface
+        let mut scattering : Option<(Medium, Vector)> = None;
 
-    // Diffuse BRDF
-    if intersection.object.body.get_material().mat_type == MatType::Diffuse
-    {
-        let cos_theta = diffuse_brdf(surface_normal, &mut ray);
-        let mut tmp = Color::new_rgb(0.0,0.0,0.0);
+        if let Some(m) = &medium
+        {
+            let sample = m.sample_distance(ray, intersection.hit.distance);
 
-        trace(scene, ray, depth + 1, &mut tmp);
+            throughput = throughput.mul_by_color(sample.weight);
 
-        let object_color = intersection.object.body.get_material().color;
-        tmp = tmp.mul_by_color(object_color);
+            if sample.scattered
+            { scattering = Some((m.clone(), ray.origin + ray.dest * sample.distance)); }
+        }
 
-        color.r = color.r + (tmp.r * cos_theta * rr_factor);
-        color.g = color.g + (tmp.g * cos_theta * rr_factor);
-        color.b = color.b + (tmp.b * cos_theta * rr_factor);
-    }
+        if let Some((m, point)) = scattering
+        {
+            if bounce >= settings.max_bounces
+            { break; }
 
-    // Specular BRDF
-    if intersection.object.body.get_material().mat_type == MatType::Specular
-    {
-        specular_brdf(surface_normal, &mut ray);
+            // Next event estimation with the phase function in place of the BRDF
+            if let Some(light) = sample_light(scene, point, medium.clone(), wavelengths, rays)
+            {
+                let phase = m.phase(ray.dest.dot_product(light.dir));
+                let weight = power_heuristic(light.pdf, phase);
+                let direct = light.emission * phase / light.pdf * weight;
+
+                color = color + throughput.mul_by_color(light.transmittance) * direct;
+            }
+
+            let incoming = ray.dest;
+
+            ray.origin = point;
+            ray.dest = m.sample_phase(incoming);
+            after_diffuse = false;
+            bsdf_pdf = Some(m.phase(incoming.dot_product(ray.dest)));
+        }
+        else
+        {
+            // End path if no intersection is found
+            let object = match intersection.object
+            {
+                Some(object) => object,
+                None => break
+            };
+
+            // Calculate hit point of a ray and an objecet
+            let hit_point = ray.origin + ray.dest * intersection.hit.distance;
+            let surface_normal = object.body.normal(hit_point, &intersection.hit);
+            let material = object.body.get_material();
 
-        let mut tmp = Color::new_rgb(0.0,0.0,0.0);
+            let emission = emitted(object.body.as_ref(), surface_normal, ray.dest);
 
-        trace(scene, ray, depth + 1, &mut tmp);
+            // Caustics are already counted by the photon map
+            let caustic = caustics.is_some() && after_diffuse && bsdf_pdf.is_none();
 
-        color.r = color.r + tmp.r * rr_factor;
-        color.g = color.g + tmp.g * rr_factor;
-        color.b = color.b + tmp.b * rr_factor;
+            // Add emmission of an object to accumulated color, weighted against light sampling after diffuse bounces
+            if emission > 0.0 && ! caustic
+            {
+                let weight = match bsdf_pdf
+                {
+                    Some(pdf) =>
+                    {
+                        let light_pdf = light_pdf(scene, object, last_vertex, hit_point, surface_normal);
+
+                        power_heuristic(pdf, light_pdf)
+                    }
+                    None => 1.0
+                };
+
+                color = color + throughput * (emission * weight);
+            }
+
+            // Hit point becomes new rays origin
+            ray.origin = hit_point;
+
+            // Medium boundaries are crossed without counting as a bounce
+            if material.mat_type == MatType::Interface
+            {
+                medium = scene.medium_across(object, surface_normal, ray.dest, wavelengths);
+                continue;
+            }
+
+            if bounce >= settings.max_bounces
+            { break; }
+
+            match material.mat_type
+            {
+                // Cosine weighted sampling cancels out the cosine term and the 1 / pi of the BRDF
+                MatType::Diffuse =>
+                {
+                    // Sample the hemisphere on the side the ray arrived from, closed bodies can be hit from inside
+                    let facing_normal =
+                        if surface_normal.dot_product(ray.dest) > 0.0
+                        { surface_normal * -1.0 }
+                        else
+                        { surface_normal };
+
+                    let surface_albedo = object.body.albedo(hit_point, &intersection.hit);
+                    let albedo = path_color(surface_albedo, wavelengths);
+
+                    // Next event estimation towards a sampled point on an area light
+                    if let Some(light) = sample_light(scene, hit_point, medium.clone(), wavelengths, rays)
+                    {
+                        let cos_theta = facing_normal.dot_product(light.dir);
+
+                        if cos_theta > 0.0
+                        {
+                            let weight = power_heuristic(light.pdf, cos_theta * FRAC_1_PI);
+                            let direct = light.emission * cos_theta * FRAC_1_PI / light.pdf * weight;
+
+                            color = color + throughput.mul_by_color(albedo).mul_by_color(light.transmittance) * direct;
+                        }
+                    }
+
+                    if let Some(map) = caustics
+                    {
+                        let caustic = path_color(map.radiance(hit_point, facing_normal, surface_albedo), wavelengths);
+
+                        color = color + throughput.mul_by_color(caustic);
+                    }
+
+                    diffuse_brdf(facing_normal, &mut ray);
+                    throughput = throughput.mul_by_color(albedo);
+                    after_diffuse = true;
+                    bsdf_pdf = Some(facing_normal.dot_product(ray.dest).max(0.0) * FRAC_1_PI);
+                }
+
+                MatType::Specular =>
+                {
+                    specular_brdf(surface_normal, &mut ray);
+                    bsdf_pdf = None;
+                }
+
+                MatType::Refractive =>
+                {
+                    let incoming = ray.dest;
+
+                    let ior = match wavelengths
+                    {
+                        Some(w) => material.ior.at(w.hero()),
+                        None => material.ior.nominal()
+                    };
+
+                    refractive_brdf(surface_normal, &mut ray, ior);
+                    bsdf_pdf = None;
+
+                    // The direction is only valid for the hero wavelength when the index varies with wavelength
+                    if let Some(w) = wavelengths.as_mut()
+                    {
+                        if material.ior.is_dispersive()
+                        { throughput = w.keep_hero(throughput); }
+                    }
+
+                    // A transmitted ray keeps the sign of its direction relative to the normal
+                    if incoming.dot_product(surface_normal) * ray.dest.dot_product(surface_normal) > 0.0
+                    { medium = scene.medium_across(object, surface_normal, ray.dest, wavelengths); }
+                }
+
+                MatType::Interface => {}
+            }
+        }
+
+        last_vertex = ray.origin;
+        bounce += 1;
+
+        // Russian roulette based on path throughput
+        if bounce >= settings.min_bounces
+        {
+            let survival_prob = throughput.max_component().min(0.95);
+
+            if survival_prob <= 0.0 || random::gen_num() >= survival_prob
+            { break; }
+
+            throughput = throughput * (1.0 / survival_prob);
+        }
     }
 
-    // Refraractive BRDF
-    if intersection.object.body.get_material().mat_type == MatType::Refractive
+    return color;
+}
+
+// Rows and columns of the image buffer inside the crop window, the buffer holds the bottom row first
+fn crop_ranges(img : &Image, crop : Option<CropWindow>) -> (Range<usize>, Range<usize>)
+{
+    let (width, height) = (img.width as usize, img.height as usize);
+
+    return match crop
     {
-        refractive_brdf(surface_normal, &mut ray);
+        Some(window) =>
+        {
+            let x0 = (window.x as usize).min(width);
+            let x1 = (window.x as usize + window.width as usize).min(width);
+            let y0 = (window.y as usize).min(height);
+            let y1 = (window.y as usize + window.height as usize).min(height);
 
-        let mut tmp = Color::new_rgb(0.0, 0.0, 0.0);
+            (height - y1..height - y0, x0..x1)
+        }
 
-        trace(scene, ray, depth + 1, &mut tmp);
+        None => (0..height, 0..width)
+    };
+}
 
-        color.r = color.r + (tmp.r * rr_factor);
-        color.g = color.g + (tmp.g * rr_factor);
-        color.b = color.b + (tmp.b * rr_factor);
-    }
+/// Renders the scene through its camera and returns the image. Writing the image to
+/// `settings.outputs` is left to the caller, see [`Image::write`]. Bidirectional path tracing renders in
+/// RGB and without participating media whatever the scene and settings ask for, [`crate::validate_settings`]
+/// reports such combinations. Fails if the render threads can't be started.
+pub fn render(scene : &Scene, settings : &RenderSettings) -> io::Result<Image>
+{ return render_with_progress(scene, settings, &NoProgress, &CancelToken::new()); }
+
+/// Like [`render`], reporting progress after every row of the image. Cancelling the token stops the
+/// render after the pass it is on, the returned image then holds the passes that were finished.
+pub fn render_with_progress(scene : &Scene, settings : &RenderSettings, reporter : &dyn ProgressReporter,
+    cancel : &CancelToken) -> io::Result<Image>
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(settings.threads)
+        .build()
+        .map_err(|err| io::Error::other(format!("Starting render threads failed: {}", err)))?;
+
+    return Ok(pool.install(|| render_pixels(scene, settings, reporter, cancel)));
 }
 
-// Attempt at clearing the terminal on both platforms
-fn clear_terminal()
+// Another pass fits when the average pass so far still fits in the time left
+fn pass_fits(settings : &RenderSettings, passes_done : u32, start : Instant, passes_start : Instant) -> bool
 {
-    if let Err(err) = execute!(
-        io::stdout(),
-        terminal::Clear(terminal::ClearType::All),
-        cursor::MoveTo(0, 0)
-    )
+    return match settings.time_limit
     {
-        eprintln!("Clearing of terminal error {}", err);
-    }
+        Some(limit) => passes_done == 0 || start.elapsed() + passes_start.elapsed() / passes_done <= limit,
+        None => passes_done < settings.spp
+    };
 }
 
-pub fn render(mut img: Image, image_name : &String, scene : &Scene, spp : u32)
+// Renders passes of one sample per pixel until spp samples are done or the time limit is reached,
+// cancelling finishes the current pass
+fn render_pixels(scene : &Scene, settings : &RenderSettings, reporter : &dyn ProgressReporter, cancel : &CancelToken) -> Image
 {
-    let benchmark = Instant::now();
+    let start = Instant::now();
+    let mut img = Image::new(scene.camera.img_width, scene.camera.img_height);
+    let splats = SplatBuffer::new(img.width, img.height);
+
+    let caustics =
+        if settings.caustic_photons > 0 && settings.integrator == Integrator::Path
+        {
+            let map = trace_caustics(scene, settings);
+
+            reporter.message(&format!("Stored {} caustic photons", map.len()));
+
+            Some(map)
+        }
+        else
+        { None };
+
+    let debug_context = DebugContext::new(scene);
 
-    for row in 0..(img.height) as usize
+    // Debug views are shown as they are, radiance is scaled down
+    let exposure = if settings.integrator.is_debug() { 1.0 } else { 0.25 };
+
+    let (rows, cols) = crop_ranges(&img, settings.crop);
+    let width = img.width as usize;
+    let pass_samples = (rows.len() * cols.len()) as u64;
+
+    let mut progress = Progress
     {
-        println!("{}% Done", row as f64 / img.height as f64 * 100.0);
-        
-        clear_terminal();
+        samples_done : 0,
+        // Time limited renders don't know their sample count, it grows with every pass
+        samples_total : pass_samples * if settings.time_limit.is_some() { 1 } else { settings.spp as u64 },
+        passes_done : 0,
+        rays : 0,
+        time_limit : settings.time_limit,
+        elapsed : start.elapsed()
+    };
+
+    let passes_start = Instant::now();
+
+    while ! cancel.is_cancelled() && pass_fits(settings, progress.passes_done, start, passes_start)
+    {
+        let pass = progress.passes_done;
 
-        img.buffer[row].par_iter_mut().enumerate().for_each(|(pixel_num, pixel)|
+        for row in rows.clone()
         {
-            for _i in 0..spp
+            let rays : u64 = img.buffer[row][cols.clone()].par_iter_mut().enumerate().map(|(i, pixel)|
             {
-                let u = pixel_num as f64 + random::gen_num();
-                let v = row as f64 + random::gen_num();
-                let mut clr = Color::new_rgb(0.0, 0.0, 0.0);
+                let pixel_num = cols.start + i;
+
+                random::reseed(settings.seed, pass as u64, (row * width + pixel_num) as u64);
+
+                let (su, sv) = settings.sampler.sample(pass, settings.spp);
+                let (dx, dy) = settings.filter.offset(su, sv);
+                let u = pixel_num as f64 + 0.5 + dx;
+                let v = row as f64 + 0.5 + dy;
                 let ray = scene.camera.get_ray(u, v);
 
-                trace(&scene, ray, 0, &mut clr);
+                // Counted per pixel and summed over the row for the progress report
+                let mut rays = 0;
 
-                pixel.r = pixel.r + clr.r * (1.0 / spp as f64) * 0.25;
-                pixel.g = pixel.g + clr.g * (1.0 / spp as f64) * 0.25;
-                pixel.b = pixel.b + clr.b * (1.0 / spp as f64) * 0.25;
-            }
-        });
+                let clr = match settings.integrator
+                {
+                    Integrator::Path =>
+                    {
+                        if settings.spectral
+                        {
+                            let wavelengths = Wavelengths::sample(random::gen_num());
+
+                            wavelengths.to_rgb(trace(scene, settings, ray, Some(wavelengths), caustics.as_ref(), &mut rays))
+                        }
+                        else
+                        { trace(scene, settings, ray, None, caustics.as_ref(), &mut rays) }
+                    }
+
+                    Integrator::Bidirectional => trace_bidirectional(scene, settings, ray, &splats, &mut rays),
+
+                    debug_view => trace_debug(scene, settings, ray, debug_view, &debug_context, &mut rays)
+                };
+
+                *pixel = *pixel + clr;
+
+                rays
+            }).sum();
+
+            progress.samples_done += cols.len() as u64;
+            progress.rays += rays;
+            progress.elapsed = start.elapsed();
+
+            reporter.update(&progress);
+        }
+
+        progress.passes_done += 1;
+
+        if settings.time_limit.is_some()
+        { progress.samples_total += pass_samples; }
     }
 
-    let elapsed = benchmark.elapsed();
+    // Pixels hold sums over the finished passes, light tracing splats add one light subpath per camera sample
+    let scale = if progress.passes_done > 0 { exposure / progress.passes_done as f64 } else { 0.0 };
 
-    println!("Render took {} minutes and {} seconds", elapsed.as_secs() / 60, elapsed.as_secs() % 60);
-    println!("Render finished");
+    for row in rows
+    {
+        for pixel_num in cols.clone()
+        {
+            let pixel = &mut img.buffer[row][pixel_num];
+
+            *pixel = (*pixel + splats.get(pixel_num, row)) * scale;
+        }
+    }
 
-    let result = img.write_to_ppm(&image_name);
+    // Whatever ended it, the render is complete now
+    progress.samples_total = progress.samples_done;
+    progress.time_limit = None;
+    progress.elapsed = start.elapsed();
+    reporter.finish(&progress);
 
-    match result
+    return img;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::Arc;
+    use crate::aabb::Aabb;
+    use crate::camera::Camera;
+    use crate::material::Material;
+    use crate::object::{Object, Quad, Sphere};
+    use crate::voxel_grid::VoxelGrid;
+    use super::*;
+
+    // Radiance of the furnace's walls, which the exposure scales to 1
+    const WALL_EMISSION : f64 = 4.0;
+
+    fn vector(x : f64, y : f64, z : f64) -> Vector
+    { return Vector { x, y, z }; }
+
+    fn white() -> Material
+    { return Material::new(0xFFFFFF, MatType::Diffuse, 0.0); }
+
+    // Inside a cube of black walls emitting on both sides, whatever does not absorb light is seen with the
+    // walls' radiance in every direction
+    fn furnace() -> Scene
     {
-        Ok(_ret_val) => println!("Writing to file was successful"),
-        Err(_ret_val) => panic!("An error occured while writing to file!")
+        let camera = Camera::new(vector(0.0, 0.0, -1.0), vector(0.0, 0.0, 0.5), vector(0.0, 1.0, 0.0), 90.0, 4, 4);
+        let mut scene = Scene::new(camera);
+        let wall = Material::new(0x000000, MatType::Diffuse, WALL_EMISSION);
+        let corner = vector(-1.0, -1.0, -1.0);
+        let (x, y, z) = (vector(2.0, 0.0, 0.0), vector(0.0, 2.0, 0.0), vector(0.0, 0.0, 2.0));
+
+        for (u, v, across) in [(y, z, x), (x, z, y), (x, y, z)]
+        {
+            scene.add(Object::new(Box::new(Quad::new(corner, u, v, true, wall.clone()))));
+            scene.add(Object::new(Box::new(Quad::new(corner + across, u, v, true, wall.clone()))));
+        }
+
+        return scene;
+    }
+
+    fn seeded(spp : u32) -> RenderSettings
+    {
+        let mut settings = RenderSettings::new(spp);
+
+        settings.seed = Some(7);
+        settings.threads = 1;
+
+        return settings;
     }
 
-}
\ No newline at end of file
+    // Mean of every channel of every pixel must be within tolerance of 1
+    fn assert_furnace(scene : &Scene, settings : &RenderSettings, tolerance : f64)
+    {
+        let img = render(scene, settings).unwrap();
+        let pixels = img.buffer.iter().flatten();
+        let count = (img.width * img.height) as f64;
+        let sum = pixels.fold(Color::new_rgb(0.0, 0.0, 0.0), |acc, pixel| acc + *pixel);
+
+        for (channel, value) in [("red", sum.r), ("green", sum.g), ("blue", sum.b)]
+        { assert!((value / count - 1.0).abs() < tolerance, "{} is {} instead of 1", channel, value / count); }
+    }
+
+    #[test]
+    fn furnace_with_fog()
+    {
+        let mut scene = furnace();
+
+        // Scattering differs per channel, so the channel that samples the free flight matters
+        scene.atmosphere = Some(Medium::new(Color::new_rgb(0.0, 0.0, 0.0), Color::new_rgb(1.0, 1.5, 0.75), 0.6));
+        scene.add(Object::new(Box::new(Sphere::new(vector(0.0, 0.0, -0.5), 0.3, white()))));
+
+        assert_furnace(&scene, &seeded(1024), 0.05);
+
+        // Neither does a glass sphere filled with fog
+        let mut glass = Material::new(0xFFFFFF, MatType::Refractive, 0.0);
+        glass.medium = Some(Medium::new(Color::new_rgb(0.0, 0.0, 0.0), Color::new_rgb(3.0, 3.0, 3.0), -0.3));
+
+        let mut scene = furnace();
+        scene.add(Object::new(Box::new(Sphere::new(vector(0.0, 0.0, -0.4), 0.5, glass))));
+
+        assert_furnace(&scene, &seeded(1024), 0.05);
+    }
+
+    #[test]
+    fn furnace_with_smoke()
+    {
+        let mut scene = furnace();
+        let densities = vec![0.0, 1.0, 2.0, 4.0, 0.5, 3.0, 1.5, 0.0];
+        let grid = VoxelGrid::new([2, 2, 2], densities, Aabb::new(vector(-0.8, -0.8, -0.9), vector(0.8, 0.8, 0.3)));
+
+        // Camera paths cross the grid by delta tracking, shadow rays by ratio tracking
+        let mut smoke = Medium::new(Color::new_rgb(0.0, 0.0, 0.0), Color::new_rgb(1.0, 1.5, 0.75), 0.3);
+        smoke.density = Some(Arc::new(grid));
+        scene.atmosphere = Some(smoke);
+
+        assert_furnace(&scene, &seeded(1024), 0.05);
+    }
+
+    #[test]
+    fn furnace_with_bidirectional_paths()
+    {
+        let mut scene = furnace();
+        let mut settings = seeded(1024);
+
+        settings.integrator = Integrator::Bidirectional;
+
+        // Light subpaths reach the camera through the spheres and are splatted onto the image
+        scene.add(Object::new(Box::new(Sphere::new(vector(-0.3, 0.0, -0.5), 0.3, white()))));
+        scene.add(Object::new(Box::new(Sphere::new(vector(0.4, 0.1, -0.4), 0.25, Material::new(0xFFFFFF, MatType::Specular, 0.0)))));
+        scene.add(Object::new(Box::new(Sphere::new(vector(0.1, -0.4, -0.3), 0.2, Material::new(0xFFFFFF, MatType::Refractive, 0.0)))));
+
+        assert_furnace(&scene, &settings, 0.05);
+    }
+
+    #[test]
+    fn furnace_with_caustic_photons()
+    {
+        let mut scene = furnace();
+        let mut settings = seeded(1024);
+
+        settings.caustic_photons = 80000;
+
+        // Most light reaching the diffuse quad passes the glass sphere in front of it, which only the photon map counts
+        scene.add(Object::new(Box::new(Quad::new(vector(-0.9, -0.9, -0.9), vector(1.8, 0.0, 0.0), vector(0.0, 1.8, 0.0), false, white()))));
+        scene.add(Object::new(Box::new(Sphere::new(vector(0.0, 0.0, -0.4), 0.45, Material::new(0xFFFFFF, MatType::Refractive, 0.0)))));
+
+        assert_furnace(&scene, &settings, 0.05);
+    }
+}
@@ -0,0 +1,184 @@
+use std::f64::consts::PI;
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::random;
+use crate::render::Integrator;
+
+pub const DEFAULT_SPP : u32 = 16;
+pub const DEFAULT_MIN_BOUNCES : u32 = 5;
+pub const DEFAULT_MAX_BOUNCES : u32 = 64;
+pub const DEFAULT_CAUSTIC_RADIUS : f64 = 0.05;
+pub const DEFAULT_AO_RADIUS : f64 = 1.0;
+
+// Standard deviation of the gaussian filter in pixels
+const GAUSSIAN_SIGMA : f64 = 0.5;
+
+/// How the positions of a pixel's samples are chosen.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sampler
+{
+    /// Uniformly random positions.
+    Independent,
+    /// One jittered sample per cell of a grid over the pixel, samples beyond the largest square grid are random.
+    Stratified
+}
+
+impl Sampler
+{
+    /// Sampler named like in scene settings and on the command line: `independent` or `stratified`.
+    pub fn from_name(name : &str) -> Option<Sampler>
+    {
+        return match name
+        {
+            "independent" => Some(Sampler::Independent),
+            "stratified" => Some(Sampler::Stratified),
+            _ => None
+        };
+    }
+
+    // Position in [0, 1)^2 of the index-th of spp samples
+    pub(crate) fn sample(&self, index : u32, spp : u32) -> (f64, f64)
+    {
+        let cells = (spp as f64).sqrt() as u32;
+
+        if *self == Sampler::Stratified && index < cells * cells
+        {
+            let u = ((index % cells) as f64 + random::gen_num()) / cells as f64;
+            let v = ((index / cells) as f64 + random::gen_num()) / cells as f64;
+
+            return (u, v);
+        }
+
+        return (random::gen_num(), random::gen_num());
+    }
+}
+
+/// Pixel reconstruction filter, samples are placed with the filter's density so every sample has the same weight.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter
+{
+    /// Uniform over the pixel.
+    Box,
+    /// Triangle falling off to zero one pixel away from the centre.
+    Tent,
+    /// Gaussian with a standard deviation of half a pixel.
+    Gaussian
+}
+
+impl Filter
+{
+    /// Filter named like in scene settings and on the command line: `box`, `tent` or `gaussian`.
+    pub fn from_name(name : &str) -> Option<Filter>
+    {
+        return match name
+        {
+            "box" => Some(Filter::Box),
+            "tent" => Some(Filter::Tent),
+            "gaussian" => Some(Filter::Gaussian),
+            _ => None
+        };
+    }
+
+    // Offset from the pixel centre for a sample position in [0, 1)^2
+    pub(crate) fn offset(&self, u : f64, v : f64) -> (f64, f64)
+    {
+        let tent = |x : f64| if x < 0.5 { (2.0 * x).sqrt() - 1.0 } else { 1.0 - (2.0 - 2.0 * x).sqrt() };
+
+        return match self
+        {
+            Filter::Box => (u - 0.5, v - 0.5),
+            Filter::Tent => (tent(u), tent(v)),
+            // Box-Muller transform
+            Filter::Gaussian =>
+            {
+                let r = GAUSSIAN_SIGMA * (-2.0 * (1.0 - u).ln()).sqrt();
+
+                (r * (2.0 * PI * v).cos(), r * (2.0 * PI * v).sin())
+            }
+        };
+    }
+}
+
+/// Part of the image that is rendered, in pixels from the top left corner of the written image.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CropWindow
+{
+    /// Left edge.
+    pub x : u32,
+    /// Top edge.
+    pub y : u32,
+    /// Width in pixels.
+    pub width : u32,
+    /// Height in pixels.
+    pub height : u32
+}
+
+/// Everything about a render that isn't part of the scene itself. Scene files can set these and
+/// the command line overrides them.
+#[derive(Clone, Debug)]
+pub struct RenderSettings
+{
+    /// Samples per pixel.
+    pub spp : u32,
+    /// Renders as many samples per pixel as fit in the time instead of spp.
+    pub time_limit : Option<Duration>,
+    /// Number of bounces before Russian roulette may terminate a path.
+    pub min_bounces : u32,
+    /// Hard limit on path length.
+    pub max_bounces : u32,
+    /// Algorithm computing the pixels.
+    pub integrator : Integrator,
+    /// Traces sampled wavelengths instead of RGB.
+    pub spectral : bool,
+    /// How sample positions within a pixel are chosen.
+    pub sampler : Sampler,
+    /// How samples are weighted around the pixel centre.
+    pub filter : Filter,
+    /// Makes renders repeatable, None draws fresh random numbers every time.
+    /// Light tracing of the bidirectional integrator may still differ in the last bits between runs.
+    pub seed : Option<u64>,
+    /// Worker threads, 0 uses every core.
+    pub threads : usize,
+    /// Pixels outside of the window are left black, None renders the whole image.
+    pub crop : Option<CropWindow>,
+    /// Files the finished image is written to, the format follows the extension.
+    pub outputs : Vec<PathBuf>,
+    /// Photons emitted for the caustic photon map of the path integrator, 0 disables it.
+    pub caustic_photons : u32,
+    /// Radius around a shaded point within which caustic photons are gathered.
+    pub caustic_radius : f64,
+    /// Distance within which surfaces occlude in the ambient occlusion view.
+    pub ao_radius : f64
+}
+
+impl RenderSettings
+{
+    /// Path tracing with `spp` samples per pixel and the defaults for everything else.
+    pub fn new(spp : u32) -> Self
+    {
+        RenderSettings
+        {
+            spp,
+            time_limit : None,
+            min_bounces : DEFAULT_MIN_BOUNCES,
+            max_bounces : DEFAULT_MAX_BOUNCES,
+            integrator : Integrator::Path,
+            spectral : false,
+            sampler : Sampler::Independent,
+            filter : Filter::Box,
+            seed : None,
+            threads : 0,
+            crop : None,
+            outputs : Vec::new(),
+            caustic_photons : 0,
+            caustic_radius : DEFAULT_CAUSTIC_RADIUS,
+            ao_radius : DEFAULT_AO_RADIUS
+        }
+    }
+}
+
+impl Default for RenderSettings
+{
+    fn default() -> Self
+    { RenderSettings::new(DEFAULT_SPP) }
+}
@@ -1,56 +1,173 @@
 use crate::camera::Camera;
+use crate::color::Color;
+use crate::material::MatType;
+use crate::medium::Medium;
 use crate::object::*;  
 use crate::ray::Ray;
+use crate::spectrum::Wavelengths;
+use crate::vector::Vector;
 
+/// Objects seen through a camera.
 pub struct Scene
 {
-    pub objects : Vec<Object>,
-    pub camera : Camera
+    pub(crate) objects : Vec<Object>,
+    /// Camera the scene is rendered through.
+    pub camera : Camera,
+    // Indices of emissive objects that can be sampled as area lights
+    pub(crate) lights : Vec<usize>,
+    /// Medium filling the space outside of all bodies, None for vacuum.
+    pub atmosphere : Option<Medium>
 }
 
 pub struct Intersection<'a>
 {
-    pub object : &'a Object,
-    pub distance : f64,
-    pub found : bool
+    // Closest object the ray hits, None if it hits nothing
+    pub object : Option<&'a Object>,
+    // Where the object was hit, at an infinite distance if nothing is hit
+    pub hit : Hit
 }
 
 impl<'a> Intersection<'a>
 {
-    pub fn new(obj : &'a Object, dist : f64, _found : bool) -> Self
+    pub fn new(obj : Option<&'a Object>, obj_hit : Hit) -> Self
     {
         Intersection
         {
             object: obj,
-            distance: dist,
-            found: _found
+            hit: obj_hit
         }
     }
 }
 
 impl Scene
 {
+    /// Empty scene in vacuum seen through the camera.
+    pub fn new(cam : Camera) -> Self
+    {
+        Scene
+        {
+            objects : vec![],
+            camera : cam,
+            lights : vec![],
+            atmosphere : None
+        }
+    }
+
+    /// Adds an object to the scene. Emissive objects whose surface can be sampled are lit towards
+    /// directly as area lights, other emissive objects are only found by paths hitting them.
     pub fn add(&mut self, obj : Object)
     {
+        if obj.body.get_material().emission > 0.0 && obj.body.area().is_some()
+        { self.lights.push(self.objects.len()); }
+
         self.objects.push(obj);
     }
 
-    //
-    pub fn intersect(&self, ray : Ray) -> Intersection
+    // Closest object the ray hits
+    pub(crate) fn intersect(&self, ray : Ray) -> Intersection<'_>
+    { return self.closest(ray, |body| body.intersect(ray)); }
+
+    // Bounding volume hierarchy nodes the bodies test while intersect looks for the closest hit, the scene's own
+    // list of objects is not a hierarchy and is left out
+    pub(crate) fn node_visits(&self, ray : Ray) -> u64
+    {
+        let mut visits = 0;
+
+        self.closest(ray, |body|
+        {
+            visits += body.node_visits(ray);
+            return body.intersect(ray);
+        });
+
+        return visits;
+    }
+
+    fn closest<F>(&self, ray : Ray, mut intersect_body : F) -> Intersection<'_>
+        where F : FnMut(&(dyn Body + Send + Sync)) -> Option<Hit>
     {
-        let mut closest_intersect = Intersection::new(&self.objects[0], f64::INFINITY, false);
+        let mut closest_intersect = Intersection::new(None, Hit::new(f64::INFINITY));
+
         for object in self.objects.iter()
         {
-            let distance = object.body.intersect(ray);
+            // Skip bodies whose bounds the ray misses or enters behind the closest hit
+            if let Some(bounds) = &object.bounds
+            {
+                if ! bounds.hit(ray, closest_intersect.hit.distance)
+                { continue; }
+            }
 
-            if distance > f64::EPSILON && distance < closest_intersect.distance
+            if let Some(hit) = intersect_body(object.body.as_ref())
             {
-                closest_intersect.distance = distance;
-                closest_intersect.object = object;
-                closest_intersect.found = true;
+                if hit.distance > f64::EPSILON && hit.distance < closest_intersect.hit.distance
+                { closest_intersect = Intersection::new(Some(object), hit); }
             }
         }
 
         return closest_intersect;
     }
-}
\ No newline at end of file
+
+    // Medium a ray travelling along dir is in after crossing the object's surface, media do not nest
+    pub(crate) fn medium_across(&self, object : &Object, normal : Vector, dir : Vector, wavelengths : Option<Wavelengths>) -> Option<Medium>
+    {
+        let medium =
+            if normal.dot_product(dir) < 0.0
+            { object.body.get_material().medium }
+            else
+            { self.atmosphere.clone() };
+
+        return medium.map(|m| m.at_wavelengths(wavelengths));
+    }
+
+    // First surface along the ray that is not a medium boundary, the point where the ray hits it and the hit itself.
+    // Adds the rays cast to find it to rays
+    pub(crate) fn next_surface(&self, mut ray : Ray, rays : &mut u64) -> Option<(&Object, Vector, Hit)>
+    {
+        loop
+        {
+            *rays += 1;
+            let intersection = self.intersect(ray);
+            let object = intersection.object?;
+            let hit_point = ray.origin + ray.dest * intersection.hit.distance;
+
+            if object.body.get_material().mat_type != MatType::Interface
+            { return Some((object, hit_point, intersection.hit)); }
+
+            ray.origin = hit_point;
+        }
+    }
+
+    // Fraction of light passing along the ray for distance, None if a surface other than a medium boundary blocks it.
+    // Adds the rays cast to rays
+    pub(crate) fn transmittance(&self, mut ray : Ray, distance : f64, mut medium : Option<Medium>, wavelengths : Option<Wavelengths>,
+        rays : &mut u64) -> Option<Color>
+    {
+        let mut result = Color::new_rgb(1.0, 1.0, 1.0);
+        let mut remaining = distance;
+
+        loop
+        {
+            *rays += 1;
+            let intersection = self.intersect(ray);
+            let segment = intersection.hit.distance.min(remaining);
+
+            if let Some(m) = &medium
+            { result = result.mul_by_color(m.transmittance(ray, segment)); }
+
+            let object = match intersection.object
+            {
+                Some(object) if intersection.hit.distance < remaining => object,
+                _ => return Some(result)
+            };
+
+            if object.body.get_material().mat_type != MatType::Interface
+            { return None; }
+
+            let hit_point = ray.origin + ray.dest * intersection.hit.distance;
+            let normal = object.body.normal(hit_point, &intersection.hit);
+
+            medium = self.medium_across(object, normal, ray.dest, wavelengths);
+            ray.origin = hit_point;
+            remaining -= intersection.hit.distance;
+        }
+    }
+}
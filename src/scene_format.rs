@@ -0,0 +1,199 @@
+use std::fs;
+use std::path::Path;
+use serde::Serialize;
+use serde_json::Value;
+use crate::gltf_import::read_gltf;
+use crate::json_utils::Config;
+use crate::mesh_import::{read_ply, read_stl};
+use crate::scene_validation::SceneError;
+
+/// Formats scene files can be written in, all describe the same objects and fields.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SceneFormat
+{
+    /// `.json` scene files.
+    Json,
+    /// `.toml` scene files.
+    Toml,
+    /// `.yaml` and `.yml` scene files.
+    Yaml,
+    /// `.gltf` and `.glb` models, which are imported as objects and can only be read.
+    Gltf,
+    /// `.ply` meshes, which can only be read.
+    Ply,
+    /// `.stl` meshes, which can only be read.
+    Stl
+}
+
+impl SceneFormat
+{
+    /// Format of a file told apart by its extension.
+    pub fn from_path(path : &Path) -> Option<SceneFormat>
+    {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        return match extension.as_str()
+        {
+            "json" => Some(SceneFormat::Json),
+            "toml" => Some(SceneFormat::Toml),
+            "yaml" | "yml" => Some(SceneFormat::Yaml),
+            "gltf" | "glb" => Some(SceneFormat::Gltf),
+            "ply" => Some(SceneFormat::Ply),
+            "stl" => Some(SceneFormat::Stl),
+            _ => None
+        };
+    }
+
+    /// Whether the format holds models that can only be imported.
+    pub fn is_model(&self) -> bool
+    { return matches!(self, SceneFormat::Gltf | SceneFormat::Ply | SceneFormat::Stl); }
+
+    fn of_file(path : &Path) -> Result<SceneFormat, SceneError>
+    {
+        match SceneFormat::from_path(path)
+        {
+            Some(format) => Ok(format),
+            None => Err(SceneError::UnknownFormat(path.to_path_buf()))
+        }
+    }
+}
+
+/// Parses a scene file in the format its extension names.
+pub fn read_scene_file(path : &Path) -> Result<Config, SceneError>
+{
+    let format = SceneFormat::of_file(path)?;
+
+    match format
+    {
+        SceneFormat::Gltf => return read_gltf(path),
+        SceneFormat::Ply => return read_ply(path),
+        SceneFormat::Stl => return read_stl(path),
+        _ => {}
+    }
+
+    let text = fs::read_to_string(path)?;
+
+    let config : Config = match format
+    {
+        SceneFormat::Json => serde_json::from_str(&text)?,
+        SceneFormat::Toml => toml::from_str(&text)?,
+        SceneFormat::Yaml => serde_yaml::from_str(&text)?,
+        _ => unreachable!()
+    };
+
+    return Ok(config);
+}
+
+// Indented by three spaces like the scenes shipped in the scenes folder
+fn to_json(scene : &Value) -> Result<String, serde_json::Error>
+{
+    let mut buffer = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"   ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+
+    scene.serialize(&mut serializer)?;
+
+    return Ok(String::from_utf8_lossy(&buffer).into_owned());
+}
+
+// Unset fields serialize as null, leaving them out reads back the same and keeps files short
+fn remove_nulls(value : &mut Value)
+{
+    match value
+    {
+        Value::Object(map) =>
+        {
+            map.retain(|_, field| !field.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+fn write_value(scene : &Value, path : &Path) -> Result<(), SceneError>
+{
+    let text = match SceneFormat::of_file(path)?
+    {
+        SceneFormat::Json => to_json(scene).map_err(|err| SceneError::Write(Box::new(err)))?,
+        SceneFormat::Toml => toml::to_string(scene).map_err(|err| SceneError::Write(Box::new(err)))?,
+        SceneFormat::Yaml => serde_yaml::to_string(scene).map_err(|err| SceneError::Write(Box::new(err)))?,
+        _ => return Err(SceneError::Write("glTF, PLY and STL models can only be read".into()))
+    };
+
+    fs::write(path, text)?;
+
+    return Ok(());
+}
+
+/// Writes a scene description in the format of the path's extension, reading it back gives the same description.
+pub fn write_scene_file(config : &Config, path : &Path) -> Result<(), SceneError>
+{
+    let mut scene = serde_json::to_value(config).map_err(|err| SceneError::Write(Box::new(err)))?;
+    remove_nulls(&mut scene);
+
+    return write_value(&scene, path);
+}
+
+/// Rewrites a scene file in the format of the output's extension, keeping fields in their order.
+/// Models are imported and written as the objects they turn into.
+pub fn convert_scene_file(input : &Path, output : &Path) -> Result<(), SceneError>
+{
+    let input_format = SceneFormat::of_file(input)?;
+
+    if input_format.is_model()
+    { return write_scene_file(&read_scene_file(input)?, output); }
+
+    let text = fs::read_to_string(input)?;
+
+    let scene : Value = match input_format
+    {
+        SceneFormat::Json => serde_json::from_str(&text)?,
+        SceneFormat::Toml => toml::from_str(&text)?,
+        SceneFormat::Yaml => serde_yaml::from_str(&text)?,
+        _ => unreachable!()
+    };
+
+    // Only scene descriptions are converted, anything else is reported like a scene that fails to load
+    serde_json::from_value::<Config>(scene.clone())?;
+
+    return write_value(&scene, output);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::path::PathBuf;
+    use crate::json_utils::get_scene_from_json;
+    use super::*;
+
+    #[test]
+    fn scene_files_round_trip()
+    {
+        let scenes = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        let folder = std::env::temp_dir().join(format!("path_tracer_round_trip_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+
+        let mut paths : Vec<PathBuf> = fs::read_dir(&scenes).unwrap().map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json")).collect();
+        paths.sort();
+
+        for path in paths
+        {
+            let config = read_scene_file(&path).unwrap();
+
+            for extension in ["json", "toml", "yaml"]
+            {
+                let copy = folder.join(path.file_stem().unwrap()).with_extension(extension);
+
+                write_scene_file(&config, &copy).unwrap();
+                assert_eq!(read_scene_file(&copy).unwrap(), config, "{} as {}", path.display(), extension);
+            }
+
+            // The written scene loads like the original, with its files found next to the original
+            get_scene_from_json(&folder.join(path.file_name().unwrap()), std::slice::from_ref(&scenes)).unwrap();
+        }
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,656 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::json_utils::{array_to_vector, Config, JsonMaterial, JsonMaterialRef, JsonMedium, JsonObject, MaterialLibrary};
+use crate::matrix::Matrix4;
+use crate::scene_format::read_scene_file;
+use crate::scene_validation::{SceneError, SceneIssue};
+use crate::texture::model_image;
+use crate::vector::Vector;
+
+// Where an object of the expanded scene was written
+#[derive(Clone)]
+pub struct ObjectOrigin
+{
+    // Included file relative to the scene's folder, None for the scene itself
+    pub file : Option<String>,
+    pub index : usize
+}
+
+// Scene with all include directives replaced by the objects they refer to
+pub struct ExpandedScene
+{
+    pub materials : MaterialLibrary,
+    pub objects : Vec<JsonObject>,
+    // Origin of the object at the same index
+    pub origins : Vec<ObjectOrigin>,
+    // Referenced files that were not found, reported with the places they were looked for
+    pub missing : HashSet<String>,
+    // Problems with the include directives themselves and the files they refer to
+    pub issues : Vec<SceneIssue>
+}
+
+// Transform of an include directive
+struct Placement
+{
+    translate : [f64; 3],
+    rotate : [f64; 3],
+    scale : [f64; 3],
+    matrix : Matrix4,
+    // Transforms normals, the inverse transpose of matrix
+    normal_matrix : Matrix4,
+    // Scale factor if the transform preserves shapes, spheres and quadrics are then transformed directly
+    similarity : Option<f64>
+}
+
+impl Placement
+{
+    fn new(translate : Option<[f64; 3]>, rotate : Option<[f64; 3]>, scale : Option<[f64; 3]>) -> Option<Self>
+    {
+        if translate.is_none() && rotate.is_none() && scale.is_none()
+        { return None; }
+
+        let place_translate = translate.unwrap_or([0.0, 0.0, 0.0]);
+        let place_rotate = rotate.unwrap_or([0.0, 0.0, 0.0]);
+        let place_scale = scale.unwrap_or([1.0, 1.0, 1.0]);
+
+        let matrix = Matrix4::from_transform(array_to_vector(place_translate), array_to_vector(place_rotate),
+            array_to_vector(place_scale));
+
+        // A singular transform is left to instance validation, which reports the scale
+        let normal_matrix = matrix.inverse().unwrap_or(Matrix4::identity()).transpose();
+
+        let uniform = place_scale[0].abs();
+        let similarity =
+            if place_scale.iter().all(|s| s.abs() == uniform)
+            { Some(uniform) }
+            else
+            { None };
+
+        Some(Placement
+        {
+            translate : place_translate,
+            rotate : place_rotate,
+            scale : place_scale,
+            matrix,
+            normal_matrix,
+            similarity
+        })
+    }
+
+    fn point(&self, point : [f64; 3]) -> [f64; 3]
+    { return vector_to_array(self.matrix.transform_point(array_to_vector(point))); }
+
+    fn vector(&self, vec : [f64; 3]) -> [f64; 3]
+    { return vector_to_array(self.matrix.transform_vector(array_to_vector(vec))); }
+
+    fn normal(&self, normal : [f64; 3]) -> [f64; 3]
+    {
+        let mut transformed = self.normal_matrix.transform_vector(array_to_vector(normal));
+        transformed.normalize();
+
+        return vector_to_array(transformed);
+    }
+}
+
+fn vector_to_array(vec : Vector) -> [f64; 3]
+{ return [vec.x, vec.y, vec.z]; }
+
+// Places a file referenced by a scene is looked for, first relative to the folder of the referencing file and then
+// in the search paths
+fn asset_candidates(name : &str, folder : &Path, search_paths : &[PathBuf]) -> Vec<PathBuf>
+{
+    let path = Path::new(name);
+
+    if path.is_absolute()
+    { return vec![path.to_path_buf()]; }
+
+    return std::iter::once(folder)
+        .chain(search_paths.iter().map(|p| p.as_path()))
+        .map(|base| base.join(path))
+        .collect();
+}
+
+// Finds a file referenced by a scene in the first of its candidate places that holds it
+pub fn find_asset(name : &str, folder : &Path, search_paths : &[PathBuf]) -> Option<PathBuf>
+{ return asset_candidates(name, folder, search_paths).into_iter().find(|candidate| candidate.is_file()); }
+
+// Field of a scene file that refers to another file
+struct Reference
+{
+    file : Option<String>,
+    object : Option<usize>,
+    field : String
+}
+
+impl Reference
+{
+    fn nested(&self, field : &str) -> Reference
+    {
+        let path = if self.field.is_empty() { field.to_string() } else { format!("{}.{}", self.field, field) };
+
+        return Reference { file : self.file.clone(), object : self.object, field : path };
+    }
+}
+
+// Replaces the material of an included object by the include's override
+fn override_material(object : JsonObject, reference : &JsonMaterialRef) -> JsonObject
+{
+    let mut overridden = object;
+
+    match &mut overridden
+    {
+        JsonObject::JsonPlane { material, .. } | JsonObject::JsonSphere { material, .. } |
+        JsonObject::JsonBox { material, .. } | JsonObject::JsonCylinder { material, .. } |
+        JsonObject::JsonCone { material, .. } | JsonObject::JsonDisk { material, .. } |
+        JsonObject::JsonTorus { material, .. } | JsonObject::JsonQuad { material, .. } |
+        JsonObject::JsonMesh { material, .. } | JsonObject::JsonSdf { material, .. } => *material = reference.clone(),
+
+        JsonObject::JsonCsg { material, .. } | JsonObject::JsonInstance { material, .. } |
+        JsonObject::JsonInclude { material, .. } => *material = Some(reference.clone()),
+
+        JsonObject::JsonPrototype { .. } | JsonObject::JsonLight { .. } | JsonObject::JsonCamera { .. } |
+        JsonObject::JsonAtmosphere { .. } | JsonObject::JsonSettings { .. } => {}
+    }
+
+    return overridden;
+}
+
+struct Expander<'a>
+{
+    search_paths : &'a [PathBuf],
+    // Folder of the main scene, included files are named relative to it
+    root : PathBuf,
+    // Files being expanded, an include of one of them would never end
+    stack : Vec<PathBuf>,
+    // File each prototype was defined in, a file included repeatedly defines its prototypes once
+    prototype_files : HashMap<String, PathBuf>,
+    // File each material was defined in, names are shared by all files so two files can't both define one
+    material_files : HashMap<String, PathBuf>,
+    // Objects wrapped into generated prototypes so far
+    wrapped : usize,
+    // Referenced files that were not found, as they are named in the expanded scene
+    missing : HashSet<String>,
+    issues : Vec<SceneIssue>
+}
+
+impl<'a> Expander<'a>
+{
+    fn label(&self, path : &Path) -> String
+    {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+
+        return relative.to_string_lossy().into_owned();
+    }
+
+    // Places an object with the include's transform, shapes that can't be transformed directly become instances
+    fn place(&mut self, object : JsonObject, placement : &Placement) -> Vec<JsonObject>
+    {
+        let placed = match (object, placement.similarity)
+        {
+            (JsonObject::JsonSphere { center, radius, material }, Some(s)) =>
+                JsonObject::JsonSphere { center : center.map(|c| placement.point(c)), radius : radius.map(|r| r * s), material },
+
+            (JsonObject::JsonLight { center, radius, emission }, Some(s)) =>
+                JsonObject::JsonLight { center : center.map(|c| placement.point(c)), radius : radius.map(|r| r * s), emission },
+
+            (JsonObject::JsonDisk { center, normal, radius, material }, Some(s)) => JsonObject::JsonDisk
+            {
+                center : center.map(|c| placement.point(c)),
+                normal : normal.map(|n| placement.normal(n)),
+                radius : radius.map(|r| r * s),
+                material
+            },
+
+            (JsonObject::JsonCylinder { base, axis, radius, height, material }, Some(s)) => JsonObject::JsonCylinder
+            {
+                base : base.map(|b| placement.point(b)),
+                axis : axis.map(|a| placement.vector(a)),
+                radius : radius.map(|r| r * s),
+                height : height.map(|h| h * s),
+                material
+            },
+
+            (JsonObject::JsonCone { base, axis, radius, height, material }, Some(s)) => JsonObject::JsonCone
+            {
+                base : base.map(|b| placement.point(b)),
+                axis : axis.map(|a| placement.vector(a)),
+                radius : radius.map(|r| r * s),
+                height : height.map(|h| h * s),
+                material
+            },
+
+            (JsonObject::JsonTorus { center, axis, major_radius, minor_radius, material }, Some(s)) => JsonObject::JsonTorus
+            {
+                center : center.map(|c| placement.point(c)),
+                axis : axis.map(|a| placement.vector(a)),
+                major_radius : major_radius.map(|r| r * s),
+                minor_radius : minor_radius.map(|r| r * s),
+                material
+            },
+
+            // The plane holds the points p with normal . p + d = 0
+            (JsonObject::JsonPlane { normal : Some(normal), d : Some(d), material }, Some(_)) =>
+            {
+                let plane_normal = array_to_vector(normal);
+                let on_plane = plane_normal * (-d / plane_normal.dot_product(plane_normal));
+
+                let moved_normal = array_to_vector(placement.normal(normal));
+                let moved_point = placement.matrix.transform_point(on_plane);
+
+                JsonObject::JsonPlane
+                {
+                    normal : Some(vector_to_array(moved_normal)),
+                    d : Some(-moved_normal.dot_product(moved_point)),
+                    material
+                }
+            }
+
+            (JsonObject::JsonQuad { corner, u, v, two_sided, material }, _) => JsonObject::JsonQuad
+            {
+                corner : corner.map(|c| placement.point(c)),
+                u : u.map(|e| placement.vector(e)),
+                v : v.map(|e| placement.vector(e)),
+                two_sided,
+                material
+            },
+
+            (JsonObject::JsonMesh { vertices, triangles, normals, uvs, colors, material }, _) => JsonObject::JsonMesh
+            {
+                vertices : vertices.into_iter().map(|v| placement.point(v)).collect(),
+                triangles,
+                normals : normals.map(|n| n.into_iter().map(|n| placement.normal(n)).collect()),
+                uvs,
+                colors,
+                material
+            },
+
+            (JsonObject::JsonCamera { look_from, look_at, vup, fov, resolution }, _) => JsonObject::JsonCamera
+            {
+                look_from : look_from.map(|p| placement.point(p)),
+                look_at : look_at.map(|p| placement.point(p)),
+                vup : vup.map(|v| placement.vector(v)),
+                fov,
+                resolution
+            },
+
+            // Prototypes are placed by their instances, the rest has no position
+            (object @ (JsonObject::JsonPrototype { .. } | JsonObject::JsonInclude { .. } |
+                JsonObject::JsonAtmosphere { .. } | JsonObject::JsonSettings { .. }), _) => object,
+
+            (object, _) =>
+            {
+                let name = format!("include#{}", self.wrapped);
+                self.wrapped += 1;
+
+                let prototype = JsonObject::JsonPrototype { name : name.clone(), object : Box::new(object) };
+                let instance = JsonObject::JsonInstance
+                {
+                    prototype : name,
+                    translate : Some(placement.translate),
+                    rotate : Some(placement.rotate),
+                    scale : Some(placement.scale),
+                    material : None
+                };
+
+                return vec![prototype, instance];
+            }
+        };
+
+        return vec![placed];
+    }
+
+    // Where a file that was not found has been looked for and through which includes the reference was reached
+    fn not_found(&self, name : &str, folder : &Path) -> String
+    {
+        let tried : Vec<String> = asset_candidates(name, folder, self.search_paths).iter()
+            .map(|candidate| candidate.to_string_lossy().into_owned()).collect();
+        let chain : Vec<String> = self.stack.iter().map(|file| self.label(file)).collect();
+
+        return format!("could not find {}, tried {} (include chain {})", name, tried.join(", "), chain.join(" > "));
+    }
+
+    // Points a referenced file at where it was found. Files that are not found are reported here and pointed at
+    // the first place they were looked for, so they are neither searched for again nor read from the working
+    // directory
+    fn resolve_file(&mut self, name : &mut String, folder : &Path, at : &Reference)
+    {
+        if let Some(found) = find_asset(name, folder, self.search_paths)
+        {
+            *name = found.to_string_lossy().into_owned();
+            return;
+        }
+
+        let reason = self.not_found(name, folder);
+        self.issues.push(SceneIssue { file : at.file.clone(), object : at.object, field : at.field.clone(), reason });
+
+        let expected = asset_candidates(name, folder, self.search_paths).swap_remove(0);
+        *name = expected.to_string_lossy().into_owned();
+        self.missing.insert(name.clone());
+    }
+
+    fn resolve_medium(&mut self, medium : &mut JsonMedium, folder : &Path, at : &Reference)
+    {
+        if let Some(grid) = &mut medium.density
+        { self.resolve_file(&mut grid.file, folder, &at.nested("density.file")); }
+    }
+
+    fn resolve_material(&mut self, material : &mut JsonMaterial, folder : &Path, at : &Reference)
+    {
+        if let Some(medium) = &mut material.medium
+        { self.resolve_medium(medium, folder, &at.nested("medium")); }
+
+        // Images inside glTF files keep their index after the file name
+        if let Some(texture) = &mut material.texture
+        {
+            let (mut file, image) = match model_image(texture)
+            {
+                Some((file, image)) => (file.to_string(), format!("#{}", image)),
+                None => (texture.clone(), String::new())
+            };
+
+            self.resolve_file(&mut file, folder, &at.nested("texture"));
+            *texture = format!("{}{}", file, image);
+        }
+    }
+
+    // Resolves the files an object refers to relative to the folder of the file it was written in
+    fn resolve_assets(&mut self, object : &mut JsonObject, folder : &Path, at : &Reference)
+    {
+        match object
+        {
+            JsonObject::JsonPlane { material, .. } | JsonObject::JsonSphere { material, .. } |
+            JsonObject::JsonBox { material, .. } | JsonObject::JsonCylinder { material, .. } |
+            JsonObject::JsonCone { material, .. } | JsonObject::JsonDisk { material, .. } |
+            JsonObject::JsonTorus { material, .. } | JsonObject::JsonQuad { material, .. } |
+            JsonObject::JsonMesh { material, .. } | JsonObject::JsonSdf { material, .. } =>
+            {
+                if let JsonMaterialRef::Inline(inline) = material
+                { self.resolve_material(inline, folder, &at.nested("material")); }
+            }
+
+            JsonObject::JsonCsg { left, right, material, .. } =>
+            {
+                self.resolve_assets(left, folder, &at.nested("left"));
+                self.resolve_assets(right, folder, &at.nested("right"));
+
+                if let Some(JsonMaterialRef::Inline(inline)) = material
+                { self.resolve_material(inline, folder, &at.nested("material")); }
+            }
+
+            JsonObject::JsonInstance { material, .. } | JsonObject::JsonInclude { material, .. } =>
+            {
+                if let Some(JsonMaterialRef::Inline(inline)) = material
+                { self.resolve_material(inline, folder, &at.nested("material")); }
+            }
+
+            JsonObject::JsonPrototype { object, .. } => self.resolve_assets(object, folder, &at.nested("object")),
+
+            JsonObject::JsonAtmosphere { medium } => self.resolve_medium(medium, folder, &at.nested("medium")),
+
+            JsonObject::JsonLight { .. } | JsonObject::JsonCamera { .. } | JsonObject::JsonSettings { .. } => {}
+        }
+    }
+
+    // Reads and expands the file an include directive refers to
+    fn include(&mut self, file : &str, folder : &Path) -> Result<ExpandedScene, String>
+    {
+        let path = match find_asset(file, folder, self.search_paths)
+        {
+            Some(path) => path,
+            None => return Err(self.not_found(file, folder))
+        };
+
+        let canonical = match fs::canonicalize(&path)
+        {
+            Ok(canonical) => canonical,
+            Err(err) => return Err(format!("could not read {}: {}", file, err))
+        };
+
+        if self.stack.contains(&canonical)
+        { return Err(format!("{} includes itself", self.label(&canonical))); }
+
+        let config = match read_scene_file(&canonical)
+        {
+            Ok(config) => config,
+            Err(SceneError::Parse(err)) => return Err(format!("could not parse {}: {}", self.label(&canonical), err)),
+            Err(err) => return Err(format!("could not read {}: {}", self.label(&canonical), err))
+        };
+
+        let label = self.label(&canonical);
+
+        return Ok(self.expand(&canonical, Some(label), config));
+    }
+
+    // Replaces the includes of a parsed file and collects its materials with the included ones
+    fn expand(&mut self, path : &Path, label : Option<String>, mut config : Config) -> ExpandedScene
+    {
+        self.stack.push(path.to_path_buf());
+
+        let folder = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut expanded = ExpandedScene
+        {
+            materials : MaterialLibrary::new(),
+            objects : Vec::new(),
+            origins : Vec::new(),
+            missing : HashSet::new(),
+            issues : Vec::new()
+        };
+
+        for (name, material) in config.materials.iter_mut()
+        {
+            let at = Reference { file : label.clone(), object : None, field : format!("materials.{}", name) };
+            self.resolve_material(material, &folder, &at);
+        }
+
+        for (index, object) in config.objects.iter_mut().enumerate()
+        { self.resolve_assets(object, &folder, &Reference { file : label.clone(), object : Some(index), field : String::new() }); }
+
+        for (index, object) in config.objects.into_iter().enumerate()
+        {
+            let origin = ObjectOrigin { file : label.clone(), index };
+
+            match object
+            {
+                JsonObject::JsonInclude { file, translate, rotate, scale, material } =>
+                {
+                    let included = match self.include(&file, &folder)
+                    {
+                        Ok(included) => included,
+                        Err(reason) =>
+                        {
+                            self.issues.push(SceneIssue { file : label.clone(), object : Some(index), field : "file".to_string(), reason });
+                            continue;
+                        }
+                    };
+
+                    let placement = Placement::new(translate, rotate, scale);
+
+                    expanded.materials.extend(included.materials);
+
+                    for (object, object_origin) in included.objects.into_iter().zip(included.origins)
+                    {
+                        let overridden = match &material
+                        {
+                            Some(reference) => override_material(object, reference),
+                            None => object
+                        };
+
+                        let placed = match &placement
+                        {
+                            Some(placement) => self.place(overridden, placement),
+                            None => vec![overridden]
+                        };
+
+                        for object in placed
+                        {
+                            expanded.objects.push(object);
+                            expanded.origins.push(object_origin.clone());
+                        }
+                    }
+                }
+
+                JsonObject::JsonPrototype { ref name, .. } =>
+                {
+                    // Defined by an earlier include of the same file
+                    if self.prototype_files.get(name).is_some_and(|defined_in| defined_in == path)
+                    { continue; }
+
+                    self.prototype_files.entry(name.clone()).or_insert(path.to_path_buf());
+
+                    expanded.objects.push(object);
+                    expanded.origins.push(origin);
+                }
+
+                _ =>
+                {
+                    expanded.objects.push(object);
+                    expanded.origins.push(origin);
+                }
+            }
+        }
+
+        for (name, material) in config.materials
+        {
+            let defined_in = self.material_files.entry(name.clone()).or_insert(path.to_path_buf()).clone();
+
+            if defined_in != path
+            {
+                let reason = format!("is also defined in {}", self.label(&defined_in));
+                self.issues.push(SceneIssue { file : label.clone(), object : None, field : format!("materials.{}", name), reason });
+                continue;
+            }
+
+            expanded.materials.insert(name, material);
+        }
+
+        self.stack.pop();
+
+        return expanded;
+    }
+}
+
+// Reads a scene and the files it includes, referenced files are searched next to the file containing the reference
+// and then in search_paths
+pub fn expand_scene(path : &Path, search_paths : &[PathBuf]) -> Result<ExpandedScene, SceneError>
+{
+    let canonical = fs::canonicalize(path)?;
+    let config = read_scene_file(&canonical)?;
+
+    let mut expander = Expander
+    {
+        search_paths,
+        root : canonical.parent().unwrap_or(Path::new("")).to_path_buf(),
+        stack : Vec::new(),
+        prototype_files : HashMap::new(),
+        material_files : HashMap::new(),
+        wrapped : 0,
+        missing : HashSet::new(),
+        issues : Vec::new()
+    };
+
+    let mut expanded = expander.expand(&canonical, None, config);
+    expanded.missing = expander.missing;
+    expanded.issues = expander.issues;
+
+    return Ok(expanded);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::json_utils::get_scene_from_json;
+    use super::*;
+
+    const CAMERA : &str = r#"{ "type" : "camera", "look_from" : [0, 0, -5], "look_at" : [0, 0, 0], "vup" : [0, 1, 0], "fov" : 60,
+        "resolution" : { "width" : 64, "height" : 48 } }"#;
+
+    // Folder of scene files that is removed again when dropped
+    struct Files(PathBuf);
+
+    impl Files
+    {
+        fn new(name : &str, files : &[(&str, String)]) -> Self
+        {
+            let folder = std::env::temp_dir().join(format!("path_tracer_{}_{}", name, std::process::id()));
+
+            for (file, text) in files
+            {
+                let path = folder.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, text).unwrap();
+            }
+
+            return Files(folder);
+        }
+    }
+
+    impl Drop for Files
+    {
+        fn drop(&mut self)
+        { let _ = fs::remove_dir_all(&self.0); }
+    }
+
+    fn part(color : &str) -> String
+    {
+        return format!(r##"{{ "materials" : {{ "red" : {{ "type" : "diffuse", "color" : "{}" }} }},
+            "objects" : [ {{ "type" : "sphere", "center" : [0, 0, 0], "radius" : 1, "material" : "red" }} ] }}"##, color);
+    }
+
+    fn scene(includes : &[&str]) -> String
+    {
+        let objects : Vec<String> = includes.iter().map(|file| format!(r#"{{ "type" : "include", "file" : "{}" }}"#, file)).collect();
+
+        return format!(r#"{{ "objects" : [ {}, {} ] }}"#, CAMERA, objects.join(", "));
+    }
+
+    #[test]
+    fn material_defined_by_two_files()
+    {
+        let files = Files::new("material_conflict", &[("scene.json", scene(&["a.json", "parts/b.json"])), ("a.json", part("#FF0000")),
+            ("parts/b.json", part("#FF0001"))]);
+
+        let expanded = expand_scene(&files.0.join("scene.json"), &[]).unwrap();
+        let issues : Vec<(Option<String>, String, String)> = expanded.issues.into_iter()
+            .map(|issue| (issue.file, issue.field, issue.reason)).collect();
+
+        assert_eq!(issues, vec![(Some("parts/b.json".to_string()), "materials.red".to_string(), "is also defined in a.json".to_string())]);
+        assert_eq!(expanded.materials["red"].color.as_deref(), Some("#FF0000"));
+    }
+
+    #[test]
+    fn file_included_twice_defines_its_materials_once()
+    {
+        let files = Files::new("material_repeat", &[("scene.json", scene(&["a.json", "a.json"])), ("a.json", part("#FF0000"))]);
+
+        let expanded = expand_scene(&files.0.join("scene.json"), &[]).unwrap();
+
+        assert!(expanded.issues.is_empty());
+        assert_eq!(expanded.objects.len(), 3);
+    }
+
+    #[test]
+    fn missing_asset_is_reported_with_where_it_was_looked_for()
+    {
+        // Tests run in the crate's folder, where the grid exists but the scene does not look
+        let fog = r##"{ "objects" : [ { "type" : "sphere", "center" : [0, 0, 0], "radius" : 1, "material" : { "type" : "interface",
+            "color" : "#FFFFFF", "medium" : { "absorption" : [0, 0, 0], "scattering" : [1, 1, 1],
+            "density" : { "file" : "scenes/smoke.vgrid", "min" : [-1, -1, -1], "max" : [1, 1, 1] } } } } ] }"##;
+        let files = Files::new("missing_asset", &[("scene.json", scene(&["parts/fog.json"])), ("parts/fog.json", fog.to_string())]);
+        let search = files.0.join("assets");
+
+        let error = get_scene_from_json(&files.0.join("scene.json"), std::slice::from_ref(&search)).err().unwrap();
+        let issues = match error
+        {
+            SceneError::Invalid(issues) => issues,
+            other => panic!("unexpected error {}", other)
+        };
+
+        let tried = format!("{}, {}", files.0.join("parts/scenes/smoke.vgrid").display(), search.join("scenes/smoke.vgrid").display());
+        let reason = format!("could not find scenes/smoke.vgrid, tried {} (include chain scene.json > parts/fog.json)", tried);
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].file.as_deref(), issues[0].object), (Some("parts/fog.json"), Some(0)));
+        assert_eq!((issues[0].field.as_str(), issues[0].reason.as_str()), ("material.medium.density.file", reason.as_str()));
+    }
+}
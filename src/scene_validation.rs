@@ -0,0 +1,1024 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+use crate::aabb::Aabb;
+use crate::color::string_to_hex_int;
+use crate::json_utils::{array_to_vector, json_to_sdf_node, resolve_material, JsonBounds, JsonIor, JsonIorModel, JsonMaterial, JsonMaterialRef, JsonMedium, JsonObject,
+    JsonSdfNode, MaterialLibrary};
+use crate::image::{Image, OUTPUT_EXTENSIONS};
+use crate::matrix::Matrix4;
+use crate::render::Integrator;
+use crate::render_settings::{Filter, RenderSettings, Sampler};
+use crate::scene::Scene;
+use crate::spectrum::{LAMBDA_MAX, LAMBDA_MIN};
+use crate::texture::{model_image, TextureCache};
+use crate::voxel_grid::GridCache;
+
+const MATERIAL_TYPES : [&str; 4] = ["diffuse", "specular", "refractive", "interface"];
+const CSG_OPERATIONS : [&str; 3] = ["union", "intersection", "difference"];
+
+const BDPT_SPECTRAL : &str = "bdpt renders in RGB, use the path integrator for spectral renders";
+const CAUSTICS_INTEGRATOR : &str = "only the path integrator uses caustic photons";
+// Photons are traced in RGB at the nominal index of refraction like RGB paths, so dispersion only differs
+// in spectral renders, and pass through media unchanged
+const CAUSTICS_SPECTRAL : &str = "caustic photons are traced in RGB without dispersion, they can't be combined with spectral renders";
+const CAUSTICS_MEDIA : &str = "caustic photons ignore participating media, remove them or the media";
+
+// Distance in square micrometres a Sellmeier resonance keeps from the squared sampled wavelengths
+const RESONANCE_MARGIN : f64 = 0.01;
+
+// Files a scene refers to, loaded while it is validated so broken ones are reported with the scene. The
+// scene is then built from these, and they are dropped with it
+#[derive(Default)]
+pub struct SceneAssets
+{
+    pub grids : GridCache,
+    pub textures : TextureCache,
+    // Files that were not found where they were looked for, which has been reported already
+    pub missing : HashSet<String>
+}
+
+/// Problem with a single field of a scene file or of the render settings.
+#[derive(Debug)]
+pub struct SceneIssue
+{
+    /// Included file the object was written in, None for the scene itself.
+    pub file : Option<String>,
+    /// Index into the file's objects, None for problems with the scene as a whole.
+    pub object : Option<usize>,
+    /// Path to the field, nested fields are separated by dots.
+    pub field : String,
+    /// What is wrong with the field.
+    pub reason : String
+}
+
+impl fmt::Display for SceneIssue
+{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+    {
+        if let Some(file) = &self.file
+        { write!(f, "{}, ", file)?; }
+
+        match self.object
+        {
+            Some(index) => write!(f, "object {}, field '{}': {}", index, self.field, self.reason),
+            None => write!(f, "field '{}': {}", self.field, self.reason)
+        }
+    }
+}
+
+/// Why a scene file could not be loaded, converted or written.
+#[derive(Debug)]
+pub enum SceneError
+{
+    /// Reading or writing the file failed.
+    Io(std::io::Error),
+    /// Extension that doesn't name a scene format.
+    UnknownFormat(PathBuf),
+    /// The file is not a well formed scene description.
+    Parse(Box<dyn std::error::Error + Send + Sync>),
+    /// The description could not be written in the requested format.
+    Write(Box<dyn std::error::Error + Send + Sync>),
+    /// The description is well formed but can't be rendered, with every problem found.
+    Invalid(Vec<SceneIssue>)
+}
+
+impl fmt::Display for SceneError
+{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            SceneError::Io(err) => write!(f, "Could not read scene: {}", err),
+            SceneError::UnknownFormat(path) =>
+                write!(f, "Unknown scene format of {}, use .json, .toml, .yaml, .yml, .gltf, .glb, .ply or .stl", path.display()),
+            SceneError::Parse(err) => write!(f, "Could not parse scene: {}", err),
+            SceneError::Write(err) => write!(f, "Could not write scene: {}", err),
+            SceneError::Invalid(issues) =>
+            {
+                write!(f, "Scene has {} problem(s):", issues.len())?;
+
+                for issue in issues
+                { write!(f, "\n  {}", issue)?; }
+
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError
+{
+    fn from(err : std::io::Error) -> Self
+    { return SceneError::Io(err); }
+}
+
+impl From<serde_json::Error> for SceneError
+{
+    fn from(err : serde_json::Error) -> Self
+    { return SceneError::Parse(Box::new(err)); }
+}
+
+impl From<toml::de::Error> for SceneError
+{
+    fn from(err : toml::de::Error) -> Self
+    { return SceneError::Parse(Box::new(err)); }
+}
+
+impl From<serde_yaml::Error> for SceneError
+{
+    fn from(err : serde_yaml::Error) -> Self
+    { return SceneError::Parse(Box::new(err)); }
+}
+
+impl From<gltf::Error> for SceneError
+{
+    fn from(err : gltf::Error) -> Self
+    { return SceneError::Parse(Box::new(err)); }
+}
+
+fn length(vec : [f64; 3]) -> f64
+{ return (vec[0] * vec[0] + vec[1] * vec[1] + vec[2] * vec[2]).sqrt(); }
+
+fn cross(a : [f64; 3], b : [f64; 3]) -> [f64; 3]
+{ return [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]; }
+
+struct Validator<'a>
+{
+    issues : Vec<SceneIssue>,
+    object : Option<usize>,
+    // Field path of the nested object being checked, such as "left."
+    prefix : String,
+    // Prototypes instances may refer to, inside a prototype only the ones defined before it
+    prototypes : HashSet<String>,
+    // Prototypes whose object encloses a volume, so that instances of them can be CSG operands
+    solid_prototypes : HashSet<String>,
+    materials : &'a MaterialLibrary,
+    assets : &'a mut SceneAssets
+}
+
+impl<'a> Validator<'a>
+{
+    fn report(&mut self, field : &str, reason : &str)
+    {
+        self.issues.push(SceneIssue
+        {
+            file : None,
+            object : self.object,
+            field : format!("{}{}", self.prefix, field),
+            reason : reason.to_string()
+        });
+    }
+
+    fn required<T : Copy>(&mut self, field : &str, value : Option<T>) -> Option<T>
+    {
+        if value.is_none()
+        { self.report(field, "is required"); }
+
+        return value;
+    }
+
+    fn positive(&mut self, field : &str, value : Option<f64>)
+    {
+        if let Some(number) = self.required(field, value)
+        {
+            if number <= 0.0
+            { self.report(field, "must be positive"); }
+        }
+    }
+
+    fn direction(&mut self, field : &str, value : Option<[f64; 3]>)
+    {
+        if let Some(vec) = self.required(field, value)
+        {
+            if length(vec) == 0.0
+            { self.report(field, "must not be a zero vector"); }
+        }
+    }
+
+    fn bounds(&mut self, field : &str, min : [f64; 3], max : [f64; 3])
+    {
+        if (0..3).any(|axis| min[axis] >= max[axis])
+        { self.report(field, "min must be below max on every axis"); }
+    }
+
+    // Runs check with field names of a nested object prefixed by field
+    fn nested(&mut self, field : &str, check : impl FnOnce(&mut Self))
+    {
+        let outer = self.prefix.clone();
+        self.prefix = format!("{}{}.", outer, field);
+
+        check(self);
+
+        self.prefix = outer;
+    }
+
+    fn medium(&mut self, medium : &JsonMedium)
+    {
+        if medium.absorption.iter().any(|c| *c < 0.0)
+        { self.report("absorption", "must not be negative"); }
+
+        if medium.scattering.iter().any(|c| *c < 0.0)
+        { self.report("scattering", "must not be negative"); }
+
+        if let Some(anisotropy) = medium.anisotropy
+        {
+            if anisotropy <= -1.0 || anisotropy >= 1.0
+            { self.report("anisotropy", "must be between -1 and 1"); }
+        }
+
+        if let Some(grid) = &medium.density
+        {
+            self.nested("density", |v|
+            {
+                v.bounds("max", grid.min, grid.max);
+
+                let bounds = Aabb::new(array_to_vector(grid.min), array_to_vector(grid.max));
+
+                if !v.assets.missing.contains(&grid.file)
+                {
+                    if let Err(err) = v.assets.grids.load(&grid.file, bounds)
+                    { v.report("file", &format!("could not load {}: {}", grid.file, err)); }
+                }
+            });
+        }
+    }
+
+    // Checks the fields a material block sets itself, inherited ones are checked where they are set
+    fn material_fields(&mut self, material : &JsonMaterial)
+    {
+        if let Some(material_type) = &material.material_type
+        {
+            if !MATERIAL_TYPES.contains(&material_type.as_str())
+            { self.report("type", &format!("unknown material type '{}'", material_type)); }
+        }
+
+        if let Some(color) = &material.color
+        {
+            if string_to_hex_int(color).is_none()
+            { self.report("color", &format!("'{}' is not a hexadecimal colour", color)); }
+        }
+
+        if let Some(emission) = material.emission
+        {
+            if emission < 0.0
+            { self.report("emission", "must not be negative"); }
+        }
+
+        match &material.ior
+        {
+            Some(JsonIor::Constant(ior)) if *ior <= 0.0 => self.report("ior", "must be positive"),
+
+            Some(JsonIor::Model(model)) => self.ior_model(model),
+
+            _ => {}
+        }
+
+        if let Some(medium) = &material.medium
+        { self.nested("medium", |v| v.medium(medium)); }
+
+        if let Some(texture) = &material.texture
+        {
+            let file = model_image(texture).map_or(texture.as_str(), |(model, _)| model);
+
+            if !self.assets.missing.contains(file)
+            {
+                if let Err(err) = self.assets.textures.load(texture)
+                { self.report("texture", &format!("could not load '{}': {}", texture, err)); }
+            }
+        }
+    }
+
+    // Dispersion models must give a finite index of at least 1 at every wavelength spectral renders sample
+    fn ior_model(&mut self, model : &JsonIorModel)
+    {
+        let issues = self.issues.len();
+
+        match model
+        {
+            JsonIorModel::Cauchy { a, b } =>
+            {
+                for (field, value) in [("ior.a", a), ("ior.b", b)]
+                {
+                    if !value.is_finite()
+                    { self.report(field, "must be finite"); }
+                }
+            }
+
+            JsonIorModel::Sellmeier { b, c } =>
+            {
+                if b.iter().any(|value| !value.is_finite())
+                { self.report("ior.b", "must be finite"); }
+
+                if c.iter().any(|value| !value.is_finite())
+                { self.report("ior.c", "must be finite"); }
+
+                // The index has a pole at each resonance, which must keep clear of the sampled wavelengths
+                let nearest = [LAMBDA_MIN, LAMBDA_MAX].map(|nm| (nm * 0.001).powi(2));
+
+                for resonance in c.iter().filter(|value| value.is_finite())
+                {
+                    if *resonance > nearest[0] - RESONANCE_MARGIN && *resonance < nearest[1] + RESONANCE_MARGIN
+                    {
+                        self.report("ior.c", &format!("{} puts a resonance within the rendered wavelengths {} to {} nm", resonance,
+                            LAMBDA_MIN, LAMBDA_MAX));
+                    }
+                }
+            }
+        }
+
+        // The index can only be evaluated with usable coefficients
+        if self.issues.len() > issues
+        { return; }
+
+        let below_one = (LAMBDA_MIN as u32..=LAMBDA_MAX as u32).find(|nm|
+        {
+            let sq_lambda = (*nm as f64 * 0.001).powi(2);
+
+            return match model
+            {
+                JsonIorModel::Cauchy { a, b } => a + b / sq_lambda < 1.0,
+                JsonIorModel::Sellmeier { b, c } => (0..3).map(|i| b[i] * sq_lambda / (sq_lambda - c[i])).sum::<f64>() < 0.0
+            };
+        });
+
+        if let Some(nm) = below_one
+        { self.report("ior", &format!("is below 1 at {} nm", nm)); }
+    }
+
+    fn material(&mut self, field : &str, reference : &JsonMaterialRef)
+    {
+        let resolved = resolve_material(reference, self.materials);
+
+        match reference
+        {
+            JsonMaterialRef::Named(_) =>
+            {
+                if let Err(reason) = &resolved
+                { self.report(field, reason); }
+            }
+
+            JsonMaterialRef::Inline(material) => self.nested(field, |v|
+            {
+                v.material_fields(material);
+
+                if let Err(reason) = &resolved
+                { v.report("inherits", reason); }
+            })
+        }
+
+        if let Ok(material) = resolved
+        {
+            self.nested(field, |v|
+            {
+                v.required("type", material.material_type.as_ref());
+                v.required("color", material.color.as_ref());
+            });
+        }
+    }
+
+    fn sdf_node(&mut self, node : &JsonSdfNode)
+    {
+        match node
+        {
+            JsonSdfNode::Sphere { radius, .. } => self.positive("radius", Some(*radius)),
+
+            JsonSdfNode::Box { size, .. } =>
+            {
+                if size.iter().any(|s| *s < 0.0)
+                { self.report("size", "must not be negative"); }
+            }
+
+            JsonSdfNode::Torus { major_radius, minor_radius, .. } =>
+            {
+                self.positive("major_radius", Some(*major_radius));
+                self.positive("minor_radius", Some(*minor_radius));
+            }
+
+            JsonSdfNode::Cylinder { radius, height, .. } =>
+            {
+                self.positive("radius", Some(*radius));
+                self.positive("height", Some(*height));
+            }
+
+            JsonSdfNode::Mandelbulb { .. } => {}
+
+            JsonSdfNode::Union { left, right } | JsonSdfNode::Intersection { left, right } |
+            JsonSdfNode::Difference { left, right } | JsonSdfNode::SmoothUnion { left, right, .. } =>
+            {
+                self.nested("left", |v| v.sdf_node(left));
+                self.nested("right", |v| v.sdf_node(right));
+            }
+
+            JsonSdfNode::Repeat { child, .. } | JsonSdfNode::Twist { child, .. } | JsonSdfNode::Translate { child, .. } =>
+                self.nested("child", |v| v.sdf_node(child))
+        }
+    }
+
+    fn sdf_bounds(&mut self, root : &JsonSdfNode, bounds : &Option<JsonBounds>)
+    {
+        match bounds
+        {
+            Some(bounds) => self.nested("bounds", |v| v.bounds("max", bounds.min, bounds.max)),
+            None =>
+            {
+                if json_to_sdf_node(root).bounds().is_none()
+                { self.report("bounds", "is required because the distance function is unbounded"); }
+            }
+        }
+    }
+
+    // Checks an entry that is built into a renderable object
+    fn object(&mut self, object : &JsonObject)
+    {
+        match object
+        {
+            JsonObject::JsonPlane { normal, d, material } =>
+            {
+                self.direction("normal", *normal);
+                self.required("d", *d);
+                self.material("material", material);
+            }
+
+            JsonObject::JsonSphere { center, radius, material } =>
+            {
+                self.required("center", *center);
+                self.positive("radius", *radius);
+                self.material("material", material);
+            }
+
+            JsonObject::JsonBox { min, max, material, .. } =>
+            {
+                let min = self.required("min", *min);
+                let max = self.required("max", *max);
+
+                if let (Some(min), Some(max)) = (min, max)
+                { self.bounds("max", min, max); }
+
+                self.material("material", material);
+            }
+
+            JsonObject::JsonCylinder { base, axis, radius, height, material } |
+            JsonObject::JsonCone { base, axis, radius, height, material } =>
+            {
+                self.required("base", *base);
+                self.direction("axis", *axis);
+                self.positive("radius", *radius);
+                self.positive("height", *height);
+                self.material("material", material);
+            }
+
+            JsonObject::JsonDisk { center, normal, radius, material } =>
+            {
+                self.required("center", *center);
+                self.direction("normal", *normal);
+                self.positive("radius", *radius);
+                self.material("material", material);
+            }
+
+            JsonObject::JsonTorus { center, axis, major_radius, minor_radius, material } =>
+            {
+                self.required("center", *center);
+                self.direction("axis", *axis);
+                self.positive("major_radius", *major_radius);
+                self.positive("minor_radius", *minor_radius);
+                self.material("material", material);
+            }
+
+            JsonObject::JsonQuad { corner, u, v, material, .. } =>
+            {
+                self.required("corner", *corner);
+                let u = self.required("u", *u);
+                let v = self.required("v", *v);
+
+                if let (Some(u), Some(v)) = (u, v)
+                {
+                    if length(cross(u, v)) == 0.0
+                    { self.report("v", "must not be parallel to u"); }
+                }
+
+                self.material("material", material);
+            }
+
+            JsonObject::JsonMesh { vertices, triangles, normals, uvs, colors, material } =>
+            {
+                if triangles.is_empty()
+                { self.report("triangles", "mesh has no triangles"); }
+
+                if let Some(index) = triangles.iter().flatten().find(|i| **i >= vertices.len())
+                { self.report("triangles", &format!("index {} is out of range for {} vertices", index, vertices.len())); }
+
+                if let Some(normals) = normals
+                {
+                    if normals.len() != vertices.len()
+                    { self.report("normals", &format!("has {} entries for {} vertices", normals.len(), vertices.len())); }
+                }
+
+                if let Some(uvs) = uvs
+                {
+                    if uvs.len() != vertices.len()
+                    { self.report("uvs", &format!("has {} entries for {} vertices", uvs.len(), vertices.len())); }
+                }
+
+                if let Some(colors) = colors
+                {
+                    if colors.len() != vertices.len()
+                    { self.report("colors", &format!("has {} entries for {} vertices", colors.len(), vertices.len())); }
+
+                    if colors.iter().flatten().any(|c| *c < 0.0)
+                    { self.report("colors", "must not be negative"); }
+                }
+
+                self.material("material", material);
+            }
+
+            JsonObject::JsonSdf { root, bounds, step_scale, material } =>
+            {
+                self.nested("root", |v| v.sdf_node(root));
+                self.sdf_bounds(root, bounds);
+
+                if step_scale.is_some()
+                { self.positive("step_scale", *step_scale); }
+
+                self.material("material", material);
+            }
+
+            JsonObject::JsonCsg { operation, left, right, material } =>
+            {
+                if !CSG_OPERATIONS.contains(&operation.as_str())
+                { self.report("operation", &format!("unknown csg operation '{}'", operation)); }
+
+                self.nested("left", |v| v.csg_operand(left));
+                self.nested("right", |v| v.csg_operand(right));
+
+                if let Some(material) = material
+                { self.material("material", material); }
+            }
+
+            JsonObject::JsonInstance { prototype, translate, rotate, scale, material } =>
+            {
+                if !self.prototypes.contains(prototype)
+                { self.report("prototype", &format!("unknown prototype '{}'", prototype)); }
+
+                // Same transform and threshold as the instance that is built
+                let transform = Matrix4::from_transform(array_to_vector(translate.unwrap_or([0.0, 0.0, 0.0])),
+                    array_to_vector(rotate.unwrap_or([0.0, 0.0, 0.0])), array_to_vector(scale.unwrap_or([1.0, 1.0, 1.0])));
+
+                if transform.inverse().is_none()
+                { self.report("scale", "is too close to zero on some axis to invert the transform"); }
+
+                if let Some(material) = material
+                { self.material("material", material); }
+            }
+
+            JsonObject::JsonLight { center, radius, emission } =>
+            {
+                self.required("center", *center);
+                self.positive("radius", *radius);
+                self.positive("emission", *emission);
+            }
+
+            JsonObject::JsonPrototype { .. } | JsonObject::JsonInclude { .. } | JsonObject::JsonCamera { .. } |
+            JsonObject::JsonAtmosphere { .. } | JsonObject::JsonSettings { .. } => {}
+        }
+    }
+
+    fn operand(&mut self, object : &JsonObject)
+    {
+        match object
+        {
+            JsonObject::JsonPrototype { .. } | JsonObject::JsonInclude { .. } | JsonObject::JsonCamera { .. } |
+            JsonObject::JsonAtmosphere { .. } | JsonObject::JsonSettings { .. } =>
+                self.report("type", "must be a renderable object"),
+
+            _ => self.object(object)
+        }
+    }
+
+    // Bodies without a surface on both sides have no inside to combine
+    fn encloses_volume(&self, object : &JsonObject) -> bool
+    {
+        match object
+        {
+            JsonObject::JsonPlane { .. } | JsonObject::JsonSphere { .. } | JsonObject::JsonBox { .. } |
+            JsonObject::JsonCylinder { .. } | JsonObject::JsonCone { .. } | JsonObject::JsonTorus { .. } |
+            JsonObject::JsonCsg { .. } | JsonObject::JsonLight { .. } => true,
+
+            // Unknown prototypes are reported by the instance itself
+            JsonObject::JsonInstance { prototype, .. } =>
+                !self.prototypes.contains(prototype) || self.solid_prototypes.contains(prototype),
+
+            _ => false
+        }
+    }
+
+    fn csg_operand(&mut self, object : &JsonObject)
+    {
+        self.operand(object);
+
+        // Entries that are not objects have been reported by operand
+        let is_object = !matches!(object, JsonObject::JsonPrototype { .. } | JsonObject::JsonInclude { .. } |
+            JsonObject::JsonCamera { .. } | JsonObject::JsonAtmosphere { .. } | JsonObject::JsonSettings { .. });
+
+        if is_object && !self.encloses_volume(object)
+        { self.report("type", "must enclose a volume, disks, quads, meshes, distance functions and instances of them can't be combined"); }
+    }
+
+    fn camera(&mut self, look_from : Option<[f64; 3]>, look_at : Option<[f64; 3]>, vup : Option<[f64; 3]>, fov : Option<f64>,
+        width : u32, height : u32)
+    {
+        let look_from = self.required("look_from", look_from);
+        let look_at = self.required("look_at", look_at);
+        let vup = self.required("vup", vup);
+
+        if let Some(fov) = self.required("fov", fov)
+        {
+            if fov <= 0.0 || fov >= 180.0
+            { self.report("fov", "must be between 0 and 180 degrees"); }
+        }
+
+        if width == 0
+        { self.report("resolution.width", "must be positive"); }
+
+        if height == 0
+        { self.report("resolution.height", "must be positive"); }
+
+        if let (Some(from), Some(at)) = (look_from, look_at)
+        {
+            let view = [at[0] - from[0], at[1] - from[1], at[2] - from[2]];
+
+            if length(view) == 0.0
+            { self.report("look_at", "must differ from look_from"); }
+            else if let Some(up) = vup
+            {
+                if length(cross(view, up)) == 0.0
+                { self.report("vup", "must not be parallel to the view direction"); }
+            }
+        }
+    }
+
+    fn settings(&mut self, object : &JsonObject)
+    {
+        if let JsonObject::JsonSettings { spp, time_limit, min_bounces, max_bounces, spectral, integrator, sampler, filter, crop,
+            outputs, caustic_photons, caustic_radius, ao_radius, .. } = object
+        {
+            if *spp == Some(0)
+            { self.report("spp", "must be at least 1"); }
+
+            if time_limit.is_some()
+            { self.positive("time_limit", *time_limit); }
+
+            if let (Some(min), Some(max)) = (min_bounces, max_bounces)
+            {
+                if min > max
+                { self.report("min_bounces", "must not exceed max_bounces"); }
+            }
+
+            if let Some(name) = integrator
+            {
+                if Integrator::from_name(name).is_none()
+                { self.report("integrator", &format!("unknown integrator '{}'", name)); }
+
+                if name == "bdpt" && *spectral == Some(true)
+                { self.report("spectral", BDPT_SPECTRAL); }
+            }
+
+            if let Some(name) = sampler
+            {
+                if Sampler::from_name(name).is_none()
+                { self.report("sampler", &format!("unknown sampler '{}', use independent or stratified", name)); }
+            }
+
+            if let Some(name) = filter
+            {
+                if Filter::from_name(name).is_none()
+                { self.report("filter", &format!("unknown filter '{}', use box, tent or gaussian", name)); }
+            }
+
+            if let Some([_, _, width, height]) = crop
+            {
+                if *width == 0 || *height == 0
+                { self.report("crop", "width and height must be at least 1"); }
+            }
+
+            for path in outputs.iter().flatten()
+            {
+                if ! Image::supports_output(path)
+                { self.report("outputs", &format!("'{}' needs one of the extensions .{}", path.display(), OUTPUT_EXTENSIONS.join(", ."))); }
+            }
+
+            if caustic_photons.unwrap_or(0) > 0
+            {
+                if integrator.as_ref().is_some_and(|name| name != "path" && Integrator::from_name(name).is_some())
+                { self.report("caustic_photons", CAUSTICS_INTEGRATOR); }
+
+                if *spectral == Some(true)
+                { self.report("caustic_photons", CAUSTICS_SPECTRAL); }
+            }
+
+            if caustic_radius.is_some()
+            { self.positive("caustic_radius", *caustic_radius); }
+
+            if ao_radius.is_some()
+            { self.positive("ao_radius", *ao_radius); }
+        }
+    }
+}
+
+// Problem with the render settings as a whole
+fn settings_issue(field : &str, reason : &str) -> SceneIssue
+{
+    return SceneIssue
+    {
+        file : None,
+        object : None,
+        field : field.to_string(),
+        reason : reason.to_string()
+    };
+}
+
+/// Reports settings the integrators can't render the scene with, such as bidirectional path tracing of
+/// a scene with participating media. Scene files are checked when they are loaded, settings changed
+/// afterwards should be checked again before rendering.
+pub fn validate_settings(scene : &Scene, settings : &RenderSettings) -> Vec<SceneIssue>
+{
+    let mut issues = Vec::new();
+    let has_media = scene.atmosphere.is_some() || scene.objects.iter().any(|object| object.body.get_material().medium.is_some());
+
+    if settings.integrator == Integrator::Bidirectional
+    {
+        if settings.spectral
+        { issues.push(settings_issue("spectral", BDPT_SPECTRAL)); }
+
+        if has_media
+        { issues.push(settings_issue("integrator", "bdpt ignores participating media, use the path integrator for scenes with media")); }
+    }
+
+    if settings.caustic_photons > 0
+    {
+        if settings.integrator != Integrator::Path
+        { issues.push(settings_issue("caustic_photons", CAUSTICS_INTEGRATOR)); }
+
+        if settings.spectral
+        { issues.push(settings_issue("caustic_photons", CAUSTICS_SPECTRAL)); }
+
+        if has_media
+        { issues.push(settings_issue("caustic_photons", CAUSTICS_MEDIA)); }
+    }
+
+    return issues;
+}
+
+// Collects every problem of a scene's objects and materials, loading the files they refer to into assets
+pub fn validate_scene(objects : &[JsonObject], materials : &MaterialLibrary, assets : &mut SceneAssets) -> Vec<SceneIssue>
+{
+    let mut validator = Validator
+    {
+        issues : Vec::new(),
+        object : None,
+        prefix : String::new(),
+        prototypes : HashSet::new(),
+        solid_prototypes : HashSet::new(),
+        materials,
+        assets
+    };
+
+    let mut names : Vec<&String> = materials.keys().collect();
+    names.sort();
+
+    for name in names
+    {
+        let material = &materials[name];
+
+        validator.nested("materials", |v| v.nested(name, |v|
+        {
+            v.material_fields(material);
+
+            if let Err(reason) = resolve_material(&JsonMaterialRef::Inline(Box::new(material.clone())), v.materials)
+            { v.report("inherits", &reason); }
+        }));
+    }
+
+    let all_prototypes : HashSet<String> = objects.iter()
+        .filter_map(|object| match object
+        {
+            JsonObject::JsonPrototype { name, .. } => Some(name.clone()),
+            _ => None
+        })
+        .collect();
+
+    // Objects may instance prototypes defined after them, so which ones enclose a volume is known up front
+    validator.prototypes = all_prototypes.clone();
+
+    for object in objects
+    {
+        if let JsonObject::JsonPrototype { name, object } = object
+        {
+            if validator.encloses_volume(object)
+            { validator.solid_prototypes.insert(name.clone()); }
+        }
+    }
+
+    let mut defined_prototypes = HashSet::new();
+    let mut has_camera = false;
+    let mut has_objects = false;
+
+    for (index, object) in objects.iter().enumerate()
+    {
+        validator.object = Some(index);
+
+        match object
+        {
+            JsonObject::JsonPrototype { name, object } =>
+            {
+                // Prototypes are built in order and can only instance the ones before them
+                validator.prototypes = defined_prototypes.clone();
+                validator.nested("object", |v| v.operand(object));
+
+                if !defined_prototypes.insert(name.clone())
+                { validator.report("name", &format!("prototype '{}' is defined more than once", name)); }
+            }
+
+            JsonObject::JsonCamera { look_from, look_at, vup, fov, resolution } =>
+            {
+                has_camera = true;
+                validator.camera(*look_from, *look_at, *vup, *fov, resolution.width, resolution.height);
+            }
+
+            JsonObject::JsonAtmosphere { medium } => validator.nested("medium", |v| v.medium(medium)),
+
+            JsonObject::JsonSettings { .. } => validator.settings(object),
+
+            _ =>
+            {
+                has_objects = true;
+                validator.prototypes = all_prototypes.clone();
+                validator.object(object);
+            }
+        }
+    }
+
+    validator.object = None;
+
+    if !has_camera
+    { validator.report("camera", "scene has no camera"); }
+
+    if !has_objects
+    { validator.report("objects", "scene has no renderable objects"); }
+
+    return validator.issues;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const CAMERA : &str = r#"{ "type" : "camera", "look_from" : [0, 0, -5], "look_at" : [0, 0, 0], "vup" : [0, 1, 0], "fov" : 60,
+        "resolution" : { "width" : 64, "height" : 48 } }"#;
+    const SPHERE : &str = r##"{ "type" : "sphere", "center" : [0, 0, 0], "radius" : 1, "material" : { "type" : "diffuse", "color" : "#FF0000" } }"##;
+
+    fn objects(json : &[&str]) -> Vec<JsonObject>
+    { return json.iter().map(|object| serde_json::from_str(object).unwrap()).collect(); }
+
+    fn materials(json : &str) -> MaterialLibrary
+    { return serde_json::from_str(json).unwrap(); }
+
+    // Issues as (object, field) pairs, which is what the tests care about
+    fn issues(objects : &[JsonObject], materials : &MaterialLibrary) -> Vec<(Option<usize>, String)>
+    { return validate_scene(objects, materials, &mut SceneAssets::default()).into_iter().map(|issue| (issue.object, issue.field)).collect(); }
+
+    #[test]
+    fn valid_scene_has_no_issues()
+    { assert_eq!(issues(&objects(&[CAMERA, SPHERE]), &MaterialLibrary::new()), vec![]); }
+
+    #[test]
+    fn empty_scene()
+    {
+        let found = issues(&[], &MaterialLibrary::new());
+
+        assert_eq!(found, vec![(None, "camera".to_string()), (None, "objects".to_string())]);
+    }
+
+    #[test]
+    fn missing_camera()
+    { assert_eq!(issues(&objects(&[SPHERE]), &MaterialLibrary::new()), vec![(None, "camera".to_string())]); }
+
+    #[test]
+    fn bad_colour()
+    {
+        let sphere = r##"{ "type" : "sphere", "center" : [0, 0, 0], "radius" : 1, "material" : { "type" : "diffuse", "color" : "#12345G" } }"##;
+
+        assert_eq!(issues(&objects(&[CAMERA, sphere]), &MaterialLibrary::new()), vec![(Some(1), "material.color".to_string())]);
+    }
+
+    #[test]
+    fn triangle_index_out_of_range()
+    {
+        let mesh = r##"{ "type" : "mesh", "vertices" : [[0, 0, 0], [1, 0, 0], [0, 1, 0]], "triangles" : [[0, 1, 3]],
+            "material" : { "type" : "diffuse", "color" : "#FFFFFF" } }"##;
+
+        let found = issues(&objects(&[CAMERA, mesh]), &MaterialLibrary::new());
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Some(1));
+        assert!(found[0].1.starts_with("triangles"), "{:?}", found);
+    }
+
+    #[test]
+    fn material_cycle()
+    {
+        let library = materials(r##"{ "a" : { "inherits" : "b" }, "b" : { "inherits" : "a", "type" : "diffuse", "color" : "#FFFFFF" } }"##);
+        let sphere = r#"{ "type" : "sphere", "center" : [0, 0, 0], "radius" : 1, "material" : "a" }"#;
+
+        let found = issues(&objects(&[CAMERA, sphere]), &library);
+
+        assert!(found.contains(&(None, "materials.a.inherits".to_string())), "{:?}", found);
+        assert!(found.contains(&(None, "materials.b.inherits".to_string())), "{:?}", found);
+        assert!(found.contains(&(Some(1), "material".to_string())), "{:?}", found);
+    }
+
+    #[test]
+    fn dispersion_models()
+    {
+        let glass = |ior : &str| materials(&format!(r##"{{ "glass" : {{ "type" : "refractive", "color" : "#FFFFFF", "ior" : {} }} }}"##, ior));
+        let sphere = r#"{ "type" : "sphere", "center" : [0, 0, 0], "radius" : 1, "material" : "glass" }"#;
+        let found = |ior : &str| validate_scene(&objects(&[CAMERA, sphere]), &glass(ior), &mut SceneAssets::default()).into_iter()
+            .map(|issue| (issue.field, issue.reason)).collect::<Vec<_>>();
+
+        // Crown glass in both models
+        assert_eq!(found(r#"{ "model" : "cauchy", "a" : 1.5046, "b" : 0.0042 }"#), vec![]);
+        assert_eq!(found(r#"{ "model" : "sellmeier", "b" : [1.0396, 0.2318, 1.0105], "c" : [0.0060, 0.0200, 103.56] }"#), vec![]);
+
+        let below_one = vec![("materials.glass.ior".to_string(), "is below 1 at 360 nm".to_string())];
+        assert_eq!(found(r#"{ "model" : "cauchy", "a" : 0.9, "b" : 0.01 }"#), below_one);
+        assert_eq!(found(r#"{ "model" : "sellmeier", "b" : [-0.5, 0, 0], "c" : [0, 0, 0] }"#), below_one);
+
+        // A resonance at 500 nm
+        let pole = found(r#"{ "model" : "sellmeier", "b" : [1, 0, 0], "c" : [0.25, 0, 0] }"#);
+        assert_eq!(pole.len(), 1);
+        assert_eq!(pole[0].0, "materials.glass.ior.c");
+    }
+
+    #[test]
+    fn unknown_prototype()
+    {
+        let instance = r#"{ "type" : "instance", "prototype" : "missing" }"#;
+
+        assert_eq!(issues(&objects(&[CAMERA, instance]), &MaterialLibrary::new()), vec![(Some(1), "prototype".to_string())]);
+    }
+
+    #[test]
+    fn prototype_used_before_its_definition_inside_another_prototype()
+    {
+        let first = r#"{ "type" : "prototype", "name" : "first", "object" : { "type" : "instance", "prototype" : "second" } }"#;
+        let second = format!(r#"{{ "type" : "prototype", "name" : "second", "object" : {} }}"#, SPHERE);
+
+        let found = issues(&objects(&[CAMERA, first, &second, SPHERE]), &MaterialLibrary::new());
+
+        assert_eq!(found, vec![(Some(1), "object.prototype".to_string())]);
+    }
+
+    #[test]
+    fn csg_operands_enclose_a_volume()
+    {
+        let disk = r##"{ "type" : "disk", "center" : [0, 0, 0], "normal" : [0, 1, 0], "radius" : 1, "material" : { "type" : "diffuse", "color" : "#FF0000" } }"##;
+        let quad = r##"{ "type" : "prototype", "name" : "quad", "object" : { "type" : "quad", "corner" : [0, 0, 0], "u" : [1, 0, 0],
+            "v" : [0, 0, 1], "material" : { "type" : "diffuse", "color" : "#FF0000" } } }"##;
+        let ball = format!(r#"{{ "type" : "prototype", "name" : "ball", "object" : {} }}"#, SPHERE);
+        let csg = |left : &str, right : &str| format!(r#"{{ "type" : "csg", "operation" : "difference", "left" : {}, "right" : {} }}"#, left, right);
+        let instance = |name : &str| format!(r#"{{ "type" : "instance", "prototype" : "{}" }}"#, name);
+
+        // Instances may come before their prototype is defined
+        let solid = csg(SPHERE, &instance("ball"));
+        assert_eq!(issues(&objects(&[CAMERA, &solid, &ball]), &MaterialLibrary::new()), vec![]);
+
+        let flat = csg(disk, &instance("quad"));
+        let found = issues(&objects(&[CAMERA, &flat, quad]), &MaterialLibrary::new());
+
+        assert_eq!(found, vec![(Some(1), "left.type".to_string()), (Some(1), "right.type".to_string())]);
+    }
+
+    #[test]
+    fn caustics_only_with_rgb_path_tracing()
+    {
+        let bdpt = r#"{ "type" : "settings", "integrator" : "bdpt", "caustic_photons" : 1000 }"#;
+        let spectral = r#"{ "type" : "settings", "spectral" : true, "caustic_photons" : 1000 }"#;
+        let path = r#"{ "type" : "settings", "integrator" : "path", "caustic_photons" : 1000 }"#;
+
+        assert_eq!(issues(&objects(&[CAMERA, bdpt, SPHERE]), &MaterialLibrary::new()), vec![(Some(1), "caustic_photons".to_string())]);
+        assert_eq!(issues(&objects(&[CAMERA, spectral, SPHERE]), &MaterialLibrary::new()), vec![(Some(1), "caustic_photons".to_string())]);
+        assert_eq!(issues(&objects(&[CAMERA, path, SPHERE]), &MaterialLibrary::new()), vec![]);
+    }
+
+    #[test]
+    fn unreadable_voxel_grid()
+    {
+        let atmosphere = r#"{ "type" : "atmosphere", "medium" : { "absorption" : [0.1, 0.1, 0.1], "scattering" : [0.1, 0.1, 0.1],
+            "density" : { "file" : "does/not/exist.vgrd", "min" : [-1, -1, -1], "max" : [1, 1, 1] } } }"#;
+
+        assert_eq!(issues(&objects(&[CAMERA, atmosphere, SPHERE]), &MaterialLibrary::new()), vec![(Some(1), "medium.density.file".to_string())]);
+    }
+}
@@ -0,0 +1,520 @@
+use std::sync::Arc;
+use crate::aabb::Aabb;
+use crate::material::Material;
+use crate::object::{Body, Hit};
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+const EPSILON : f64 = 0.000001;
+
+// Distance below which the march counts as having reached the surface
+const HIT_EPSILON : f64 = 0.0001;
+
+// Distance a ray starting on the surface travels before hits count again
+const LEAVE_DISTANCE : f64 = 0.001;
+
+const NORMAL_EPSILON : f64 = 0.0001;
+
+const MAX_STEPS : u32 = 512;
+
+// Escape radius of the fractal iteration, the fractal lies within it
+const MANDELBULB_BAILOUT : f64 = 2.0;
+
+// Distance function built from primitives and operators
+pub enum SdfNode
+{
+    Sphere { center : Vector, radius : f64 },
+    // Edges and corners are rounded off by rounding
+    Cuboid { center : Vector, half_size : Vector, rounding : f64 },
+    // Lies in the xz plane
+    Torus { center : Vector, major_radius : f64, minor_radius : f64 },
+    // Capped, along the y axis
+    Cylinder { center : Vector, radius : f64, half_height : f64 },
+    Mandelbulb { center : Vector, scale : f64, power : f64, iterations : u32 },
+    Union(Box<SdfNode>, Box<SdfNode>),
+    Intersection(Box<SdfNode>, Box<SdfNode>),
+    // Removes the right node from the left one
+    Difference(Box<SdfNode>, Box<SdfNode>),
+    // Blends the surfaces where they are closer than smoothness
+    SmoothUnion { left : Box<SdfNode>, right : Box<SdfNode>, smoothness : f64 },
+    // Infinite copies centred on multiples of period, axes with a period of 0 are not repeated
+    Repeat { child : Box<SdfNode>, period : Vector },
+    // Rotates the xz plane by rate radians per unit along the y axis
+    Twist { child : Box<SdfNode>, rate : f64 },
+    // Moves the child, e.g. to place operators that act around the origin
+    Translate { child : Box<SdfNode>, offset : Vector }
+}
+
+fn length(vec : Vector) -> f64
+{ return vec.dot_product(vec).sqrt(); }
+
+fn repeat_axis(value : f64, period : f64) -> f64
+{
+    if period <= 0.0
+    { return value; }
+
+    return value - period * (value / period).round();
+}
+
+fn mandelbulb_distance(point : Vector, power : f64, iterations : u32) -> f64
+{
+    let mut z = point;
+    let mut derivative = 1.0;
+    let mut radius = length(z);
+
+    for _ in 0..iterations
+    {
+        if radius > MANDELBULB_BAILOUT
+        { break; }
+
+        // Raise to the power in spherical coordinates and add the starting point
+        let theta = (z.z / radius).acos() * power;
+        let phi = z.y.atan2(z.x) * power;
+        let scaled_radius = radius.powf(power);
+
+        derivative = power * radius.powf(power - 1.0) * derivative + 1.0;
+
+        z = Vector { x : theta.sin() * phi.cos(), y : theta.sin() * phi.sin(), z : theta.cos() } * scaled_radius + point;
+        radius = length(z);
+    }
+
+    if radius <= 0.0
+    { return 0.0; }
+
+    return 0.5 * radius.ln() * radius / derivative;
+}
+
+impl SdfNode
+{
+    // Signed distance, negative inside
+    pub fn distance(&self, point : Vector) -> f64
+    {
+        match self
+        {
+            SdfNode::Sphere { center, radius } => length(point - *center) - radius,
+
+            SdfNode::Cuboid { center, half_size, rounding } =>
+            {
+                let p = point - *center;
+                let q = Vector
+                {
+                    x : p.x.abs() - (half_size.x - rounding),
+                    y : p.y.abs() - (half_size.y - rounding),
+                    z : p.z.abs() - (half_size.z - rounding)
+                };
+
+                let outside = length(Vector { x : q.x.max(0.0), y : q.y.max(0.0), z : q.z.max(0.0) });
+                let inside = q.x.max(q.y).max(q.z).min(0.0);
+
+                outside + inside - rounding
+            }
+
+            SdfNode::Torus { center, major_radius, minor_radius } =>
+            {
+                let p = point - *center;
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            }
+
+            SdfNode::Cylinder { center, radius, half_height } =>
+            {
+                let p = point - *center;
+                let radial = (p.x * p.x + p.z * p.z).sqrt() - radius;
+                let axial = p.y.abs() - half_height;
+
+                let outside = (radial.max(0.0).powi(2) + axial.max(0.0).powi(2)).sqrt();
+
+                outside + radial.max(axial).min(0.0)
+            }
+
+            SdfNode::Mandelbulb { center, scale, power, iterations } =>
+                mandelbulb_distance((point - *center) * (1.0 / scale), *power, *iterations) * scale,
+
+            SdfNode::Union(left, right) => left.distance(point).min(right.distance(point)),
+
+            SdfNode::Intersection(left, right) => left.distance(point).max(right.distance(point)),
+
+            SdfNode::Difference(left, right) => left.distance(point).max(-right.distance(point)),
+
+            SdfNode::SmoothUnion { left, right, smoothness } =>
+            {
+                let a = left.distance(point);
+                let b = right.distance(point);
+
+                if *smoothness <= 0.0
+                { return a.min(b); }
+
+                // Polynomial smooth minimum
+                let h = (0.5 + 0.5 * (b - a) / smoothness).clamp(0.0, 1.0);
+
+                b + (a - b) * h - smoothness * h * (1.0 - h)
+            }
+
+            SdfNode::Repeat { child, period } =>
+            {
+                let cell_point = Vector
+                {
+                    x : repeat_axis(point.x, period.x),
+                    y : repeat_axis(point.y, period.y),
+                    z : repeat_axis(point.z, period.z)
+                };
+
+                child.distance(cell_point)
+            }
+
+            SdfNode::Twist { child, rate } =>
+            {
+                let angle = -rate * point.y;
+                let (sin, cos) = angle.sin_cos();
+
+                child.distance(Vector { x : cos * point.x - sin * point.z, y : point.y, z : sin * point.x + cos * point.z })
+            }
+
+            SdfNode::Translate { child, offset } => child.distance(point - *offset)
+        }
+    }
+
+    // None if the surface is unbounded
+    pub fn bounds(&self) -> Option<Aabb>
+    {
+        let around = |center : Vector, extent : Vector| Some(Aabb::new(center - extent, center + extent));
+
+        match self
+        {
+            SdfNode::Sphere { center, radius } =>
+                around(*center, Vector { x : *radius, y : *radius, z : *radius }),
+
+            SdfNode::Cuboid { center, half_size, .. } => around(*center, *half_size),
+
+            SdfNode::Torus { center, major_radius, minor_radius } =>
+            {
+                let outer = major_radius + minor_radius;
+
+                around(*center, Vector { x : outer, y : *minor_radius, z : outer })
+            }
+
+            SdfNode::Cylinder { center, radius, half_height } =>
+                around(*center, Vector { x : *radius, y : *half_height, z : *radius }),
+
+            SdfNode::Mandelbulb { center, scale, .. } =>
+            {
+                let extent = MANDELBULB_BAILOUT * scale;
+
+                around(*center, Vector { x : extent, y : extent, z : extent })
+            }
+
+            SdfNode::Union(left, right) => match (left.bounds(), right.bounds())
+            {
+                (Some(l), Some(r)) => Some(l.union(&r)),
+                _ => None
+            },
+
+            SdfNode::Intersection(left, right) => match (left.bounds(), right.bounds())
+            {
+                (Some(l), Some(r)) =>
+                {
+                    let min = Vector { x : l.min.x.max(r.min.x), y : l.min.y.max(r.min.y), z : l.min.z.max(r.min.z) };
+                    let max = Vector { x : l.max.x.min(r.max.x), y : l.max.y.min(r.max.y), z : l.max.z.min(r.max.z) };
+
+                    Some(Aabb::new(min, max))
+                },
+                (Some(l), None) => Some(l),
+                (None, r) => r
+            },
+
+            SdfNode::Difference(left, _) => left.bounds(),
+
+            // The blend lowers the distance by at most a quarter of the smoothness
+            SdfNode::SmoothUnion { left, right, smoothness } => match (left.bounds(), right.bounds())
+            {
+                (Some(l), Some(r)) =>
+                {
+                    let margin = 0.25 * smoothness.max(0.0);
+                    let grown = Vector { x : margin, y : margin, z : margin };
+
+                    Some(Aabb::new(l.union(&r).min - grown, l.union(&r).max + grown))
+                },
+                _ => None
+            },
+
+            SdfNode::Repeat { .. } => None,
+
+            // Any rotation around the y axis stays within the cylinder around the child's bounds
+            SdfNode::Twist { child, .. } =>
+            {
+                let b = child.bounds()?;
+                let radius = b.min.x.abs().max(b.max.x.abs()).hypot(b.min.z.abs().max(b.max.z.abs()));
+
+                Some(Aabb::new(Vector { x : -radius, y : b.min.y, z : -radius }, Vector { x : radius, y : b.max.y, z : radius }))
+            }
+
+            SdfNode::Translate { child, offset } => child.bounds().map(|b| Aabb::new(b.min + *offset, b.max + *offset))
+        }
+    }
+}
+
+// Surface of a distance function found by sphere tracing within its bounds
+#[derive(Clone)]
+pub struct Sdf
+{
+    root : Arc<SdfNode>,
+    bounds : Aabb,
+    // Fraction of the distance taken per step, below 1 for operators such as twist that overestimate distances
+    step_scale : f64,
+    material : Material
+}
+
+impl Sdf
+{
+    pub fn new(sdf_root : SdfNode, sdf_bounds : Aabb, scale : f64, mat : Material) -> Self
+    {
+        Sdf
+        {
+            root : Arc::new(sdf_root),
+            bounds : sdf_bounds,
+            step_scale : scale,
+            material : mat
+        }
+    }
+
+    // Normalized gradient of the distance by central differences
+    fn gradient(&self, vec : Vector) -> Vector
+    {
+        let dx = Vector { x : NORMAL_EPSILON, y : 0.0, z : 0.0 };
+        let dy = Vector { x : 0.0, y : NORMAL_EPSILON, z : 0.0 };
+        let dz = Vector { x : 0.0, y : 0.0, z : NORMAL_EPSILON };
+
+        let mut gradient = Vector
+        {
+            x : self.root.distance(vec + dx) - self.root.distance(vec - dx),
+            y : self.root.distance(vec + dy) - self.root.distance(vec - dy),
+            z : self.root.distance(vec + dz) - self.root.distance(vec - dz)
+        };
+
+        return *gradient.normalize();
+    }
+}
+
+impl Body for Sdf
+{
+    fn intersect(&self, ray : Ray) -> Option<Hit>
+    {
+        let (t_near, t_far) = self.bounds.clip(ray, f64::INFINITY)?;
+
+        let mut t = t_near;
+        let start_distance = self.root.distance(ray.origin + ray.dest * t);
+
+        // Marching from inside the surface follows the negated distance
+        let mut sign = if start_distance < 0.0 { -1.0 } else { 1.0 };
+        let mut min_hit = EPSILON;
+
+        // A ray leaving the surface it starts on must not hit it straight away, one entering the bounds on it may
+        if t_near == 0.0 && start_distance.abs() < 2.0 * HIT_EPSILON
+        {
+            let normal = self.gradient(ray.origin + ray.dest * t);
+
+            sign = if normal.dot_product(ray.dest) >= 0.0 { 1.0 } else { -1.0 };
+            min_hit = t + LEAVE_DISTANCE;
+        }
+
+        for _ in 0..MAX_STEPS
+        {
+            if t > t_far
+            { break; }
+
+            let distance = sign * self.root.distance(ray.origin + ray.dest * t);
+
+            if distance < HIT_EPSILON && t >= min_hit
+            { return Some(Hit::new(t)); }
+
+            t += (distance.abs() * self.step_scale).max(HIT_EPSILON);
+        }
+
+        return None;
+    }
+
+    fn normal(&self, vec : Vector, _hit : &Hit) -> Vector
+    { return self.gradient(vec); }
+
+    // Position projected onto the bounds' xz extent
+    fn uv(&self, vec : Vector, _hit : &Hit) -> (f64, f64)
+    {
+        let size = self.bounds.max - self.bounds.min;
+
+        return ((vec.x - self.bounds.min.x) / size.x, (vec.z - self.bounds.min.z) / size.z);
+    }
+
+    fn bounds(&self) -> Option<Aabb>
+    { return Some(self.bounds); }
+
+    fn get_material(&self) -> Material {
+        return self.material.clone();
+    }
+
+    fn clone_self(&self) -> Box<dyn Body + Send + Sync> { return Box::new(self.clone()) }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::material::MatType;
+    use super::*;
+
+    fn vector(x : f64, y : f64, z : f64) -> Vector
+    { return Vector { x, y, z }; }
+
+    fn white() -> Material
+    { return Material::new(0xFFFFFF, MatType::Diffuse, 0.0); }
+
+    fn sphere(center : Vector, radius : f64) -> Box<SdfNode>
+    { return Box::new(SdfNode::Sphere { center, radius }); }
+
+    fn assert_close(actual : f64, expected : f64, tolerance : f64)
+    { assert!((actual - expected).abs() < tolerance, "{} is not {}", actual, expected); }
+
+    fn assert_bounds(bounds : Option<Aabb>, min : Vector, max : Vector)
+    {
+        let bounds = bounds.unwrap();
+
+        for (actual, expected) in [(bounds.min, min), (bounds.max, max)]
+        {
+            assert_close(actual.x, expected.x, 1e-12);
+            assert_close(actual.y, expected.y, 1e-12);
+            assert_close(actual.z, expected.z, 1e-12);
+        }
+    }
+
+    #[test]
+    fn primitive_distances()
+    {
+        let ball = SdfNode::Sphere { center : vector(1.0, 0.0, 0.0), radius : 1.0 };
+
+        assert_close(ball.distance(vector(4.0, 0.0, 0.0)), 2.0, 1e-12);
+        assert_close(ball.distance(vector(1.0, 0.0, 0.0)), -1.0, 1e-12);
+
+        let cuboid = SdfNode::Cuboid { center : vector(0.0, 0.0, 0.0), half_size : vector(1.0, 1.0, 1.0), rounding : 0.2 };
+
+        assert_close(cuboid.distance(vector(2.0, 0.0, 0.0)), 1.0, 1e-12);
+        assert_close(cuboid.distance(vector(0.0, 0.0, 0.0)), -1.0, 1e-12);
+        // Corners are rounded, so they are further away than the sharp corner would be
+        assert_close(cuboid.distance(vector(2.0, 2.0, 2.0)), 1.2 * 3.0_f64.sqrt() - 0.2, 1e-12);
+
+        let torus = SdfNode::Torus { center : vector(0.0, 0.0, 0.0), major_radius : 2.0, minor_radius : 0.5 };
+
+        assert_close(torus.distance(vector(0.0, 0.0, 0.0)), 1.5, 1e-12);
+        assert_close(torus.distance(vector(0.0, 0.0, 2.0)), -0.5, 1e-12);
+        assert_close(torus.distance(vector(2.0, 1.0, 0.0)), 0.5, 1e-12);
+
+        let cylinder = SdfNode::Cylinder { center : vector(0.0, 0.0, 0.0), radius : 1.0, half_height : 2.0 };
+
+        assert_close(cylinder.distance(vector(3.0, 0.0, 0.0)), 2.0, 1e-12);
+        assert_close(cylinder.distance(vector(0.0, 5.0, 0.0)), 3.0, 1e-12);
+        assert_close(cylinder.distance(vector(2.0, 3.0, 0.0)), 2.0_f64.sqrt(), 1e-12);
+        assert_close(cylinder.distance(vector(0.0, 0.0, 0.0)), -1.0, 1e-12);
+    }
+
+    #[test]
+    fn operator_distances()
+    {
+        let point = vector(0.0, 0.0, 0.0);
+        let union = SdfNode::Union(sphere(vector(-1.0, 0.0, 0.0), 0.5), sphere(vector(2.0, 0.0, 0.0), 0.5));
+
+        assert_close(union.distance(point), 0.5, 1e-12);
+
+        let difference = SdfNode::Difference(sphere(point, 1.0), sphere(vector(1.0, 0.0, 0.0), 0.5));
+
+        // Inside the removed sphere is outside the result
+        assert_close(difference.distance(vector(1.0, 0.0, 0.0)), 0.5, 1e-12);
+        assert_close(difference.distance(point), -0.5, 1e-12);
+
+        // Where both surfaces are equally close the blend lowers the distance by a quarter of the smoothness
+        let smooth = SdfNode::SmoothUnion { left : sphere(vector(-1.0, 0.0, 0.0), 0.5), right : sphere(vector(1.0, 0.0, 0.0), 0.5), smoothness : 0.4 };
+
+        assert_close(smooth.distance(point), 0.5 - 0.1, 1e-12);
+        assert_close(smooth.distance(vector(-3.0, 0.0, 0.0)), 1.5, 1e-12);
+
+        let repeat = SdfNode::Repeat { child : sphere(point, 0.5), period : vector(10.0, 0.0, 0.0) };
+
+        assert_close(repeat.distance(vector(30.2, 0.0, 0.0)), -0.3, 1e-12);
+        assert_close(repeat.distance(vector(0.0, 3.0, 0.0)), 2.5, 1e-12);
+
+        // A quarter turn at height 1 brings the point on the z axis onto the x axis
+        let twist = SdfNode::Twist { child : sphere(vector(2.0, 1.0, 0.0), 0.5), rate : std::f64::consts::FRAC_PI_2 };
+
+        assert_close(twist.distance(vector(0.0, 1.0, 2.0)), -0.5, 1e-9);
+        assert_close(twist.distance(vector(2.0, 1.0, 0.0)), 2.0_f64.sqrt() * 2.0 - 0.5, 1e-9);
+
+        let moved = SdfNode::Translate { child : sphere(point, 1.0), offset : vector(0.0, 0.0, 3.0) };
+
+        assert_close(moved.distance(vector(0.0, 0.0, 3.0)), -1.0, 1e-12);
+    }
+
+    #[test]
+    fn node_bounds()
+    {
+        let origin = vector(0.0, 0.0, 0.0);
+        let torus = SdfNode::Torus { center : origin, major_radius : 2.0, minor_radius : 0.5 };
+
+        assert_bounds(torus.bounds(), vector(-2.5, -0.5, -2.5), vector(2.5, 0.5, 2.5));
+
+        let intersection = SdfNode::Intersection(sphere(origin, 1.0), sphere(vector(1.0, 0.0, 0.0), 1.0));
+
+        assert_bounds(intersection.bounds(), vector(0.0, -1.0, -1.0), vector(1.0, 1.0, 1.0));
+        assert!(SdfNode::Repeat { child : sphere(origin, 1.0), period : vector(4.0, 0.0, 0.0) }.bounds().is_none());
+
+        // Twisting sweeps the child around the y axis
+        let cuboid = SdfNode::Cuboid { center : vector(2.0, 0.0, 0.0), half_size : vector(1.0, 1.0, 1.0), rounding : 0.0 };
+        let twist = SdfNode::Twist { child : Box::new(cuboid), rate : 1.0 };
+        let radius = 10.0_f64.sqrt();
+
+        assert_bounds(twist.bounds(), vector(-radius, -1.0, -radius), vector(radius, 1.0, radius));
+
+        // Every point of the twisted surface lies within the bounds
+        for step in 0..=100
+        {
+            let angle = step as f64 * std::f64::consts::PI / 50.0;
+
+            for height in [-1.0, 0.0, 1.0]
+            {
+                let corner = vector(radius * angle.cos(), height, radius * angle.sin());
+
+                assert!(twist.distance(corner * 1.001) >= 0.0);
+            }
+        }
+
+        // The blend grows the surface by a quarter of the smoothness where the spheres touch
+        let smooth = SdfNode::SmoothUnion { left : sphere(vector(-1.0, 0.0, 0.0), 1.0), right : sphere(vector(1.0, 0.0, 0.0), 1.0), smoothness : 0.4 };
+
+        assert_bounds(smooth.bounds(), vector(-2.1, -1.1, -1.1), vector(2.1, 1.1, 1.1));
+        assert!(smooth.distance(vector(0.0, 0.3, 0.0)) < 0.0);
+        assert!(smooth.distance(vector(0.0, 1.1, 0.0)) >= 0.0);
+    }
+
+    #[test]
+    fn ray_starting_on_the_surface()
+    {
+        let root = SdfNode::Sphere { center : vector(0.0, 0.0, 0.0), radius : 1.0 };
+        let bounds = root.bounds().unwrap();
+        let sdf = Sdf::new(root, bounds, 1.0, white());
+        let on_surface = vector(1.0, 0.0, 0.0);
+
+        // Leaving the surface it starts on the ray does not hit it again
+        assert!(sdf.intersect(Ray { origin : on_surface, dest : vector(1.0, 0.0, 0.0) }).is_none());
+
+        // Entering it, the ray passes through and hits the far side
+        let hit = sdf.intersect(Ray { origin : on_surface, dest : vector(-1.0, 0.0, 0.0) }).unwrap();
+
+        assert_close(hit.distance, 2.0, 1e-3);
+
+        let from_inside = sdf.intersect(Ray { origin : vector(0.0, 0.0, 0.0), dest : vector(0.0, 1.0, 0.0) }).unwrap();
+
+        assert_close(from_inside.distance, 1.0, 1e-3);
+
+        // The surface touches the bounds where this ray enters them
+        let from_outside = sdf.intersect(Ray { origin : vector(0.0, 0.0, -3.0), dest : vector(0.0, 0.0, 1.0) }).unwrap();
+
+        assert_close(from_outside.distance, 2.0, 1e-3);
+        assert!(sdf.normal(vector(0.0, 0.0, -1.0), &from_outside).z < -0.999);
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::OnceLock;
+use crate::color::Color;
+
+// Range wavelengths are sampled from, in nanometres
+pub(crate) const LAMBDA_MIN : f64 = 360.0;
+pub(crate) const LAMBDA_MAX : f64 = 830.0;
+
+// Range covered by the RGB to spectrum basis, values outside of it are clamped
+const BASIS_MIN : f64 = 380.0;
+const BASIS_MAX : f64 = 720.0;
+
+// Wavelength of the sodium D line that refractive indices are usually quoted at
+pub const SODIUM_D_LINE : f64 = 589.3;
+
+// Number of wavelengths carried by a path, one per colour channel
+const PATH_WAVELENGTHS : usize = 3;
+
+// Basis spectra from Smits, "An RGB to Spectrum Conversion for Reflectances", sampled at 10 bins over the range
+const SMITS_WHITE : [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN : [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA : [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW : [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED : [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN : [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE : [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// Linearly interpolates a basis spectrum whose bins are centred evenly across the range
+fn basis_value(basis : &[f64; 10], lambda : f64) -> f64
+{
+    let bin_width = (BASIS_MAX - BASIS_MIN) / basis.len() as f64;
+    let pos = ((lambda - BASIS_MIN) / bin_width - 0.5).clamp(0.0, (basis.len() - 1) as f64);
+    let lower = pos.floor() as usize;
+    let upper = (lower + 1).min(basis.len() - 1);
+    let frac = pos - lower as f64;
+
+    return basis[lower] + (basis[upper] - basis[lower]) * frac;
+}
+
+// Value at lambda of a smooth spectrum whose colour is the given RGB triple
+pub fn rgb_to_spectrum(color : Color, lambda : f64) -> f64
+{
+    let basis = |spectrum : &[f64; 10]| basis_value(spectrum, lambda);
+    let (r, g, b) = (color.r, color.g, color.b);
+
+    if r <= g && r <= b
+    {
+        if g <= b
+        { return r * basis(&SMITS_WHITE) + (g - r) * basis(&SMITS_CYAN) + (b - g) * basis(&SMITS_BLUE); }
+
+        return r * basis(&SMITS_WHITE) + (b - r) * basis(&SMITS_CYAN) + (g - b) * basis(&SMITS_GREEN);
+    }
+
+    if g <= r && g <= b
+    {
+        if r <= b
+        { return g * basis(&SMITS_WHITE) + (r - g) * basis(&SMITS_MAGENTA) + (b - r) * basis(&SMITS_BLUE); }
+
+        return g * basis(&SMITS_WHITE) + (b - g) * basis(&SMITS_MAGENTA) + (r - b) * basis(&SMITS_RED);
+    }
+
+    if r <= g
+    { return b * basis(&SMITS_WHITE) + (r - b) * basis(&SMITS_YELLOW) + (g - r) * basis(&SMITS_GREEN); }
+
+    return b * basis(&SMITS_WHITE) + (g - b) * basis(&SMITS_YELLOW) + (r - g) * basis(&SMITS_RED);
+}
+
+// Piecewise gaussian with different widths left and right of the mean
+fn lobe(lambda : f64, mean : f64, left_width : f64, right_width : f64) -> f64
+{
+    let width = if lambda < mean { left_width } else { right_width };
+    let t = (lambda - mean) / width;
+
+    return (-0.5 * t * t).exp();
+}
+
+// CIE 1931 colour matching functions by the multi lobe fit of Wyman, Sloan and Shirley
+fn color_matching(lambda : f64) -> [f64; 3]
+{
+    let x = 1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8);
+
+    return [x, y, z];
+}
+
+// Density roughly following the sum of the colour matching functions, from pbrt-v4
+fn visible_pdf(lambda : f64) -> f64
+{
+    if ! (LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda)
+    { return 0.0; }
+
+    return 0.0039398042 / (0.0072 * (lambda - 538.0)).cosh().powi(2);
+}
+
+fn sample_visible(u : f64) -> f64
+{ return 538.0 - 138.888889 * (0.85691062 - 1.82750197 * u).atanh(); }
+
+fn xyz_to_linear_srgb(xyz : [f64; 3]) -> [f64; 3]
+{
+    let [x, y, z] = xyz;
+
+    return [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z
+    ];
+}
+
+// Linear sRGB of a constant spectrum of 1, used to map it to white
+fn white_point() -> [f64; 3]
+{
+    static WHITE : OnceLock<[f64; 3]> = OnceLock::new();
+
+    return *WHITE.get_or_init(||
+    {
+        let steps = 1000;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+        let mut xyz = [0.0; 3];
+
+        for i in 0..steps
+        {
+            let cmf = color_matching(LAMBDA_MIN + (i as f64 + 0.5) * step);
+
+            for channel in 0..3
+            { xyz[channel] += cmf[channel] * step; }
+        }
+
+        xyz_to_linear_srgb(xyz)
+    });
+}
+
+// Hero wavelength and the wavelengths stratified against it, traced along a single path
+#[derive(Clone, Copy)]
+pub struct Wavelengths
+{
+    pub lambda : [f64; PATH_WAVELENGTHS],
+    pdf : [f64; PATH_WAVELENGTHS],
+    // Set once the path is only valid for the hero wavelength
+    hero_only : bool
+}
+
+impl Wavelengths
+{
+    pub fn sample(u : f64) -> Self
+    {
+        let mut lambda = [0.0; PATH_WAVELENGTHS];
+        let mut pdf = [0.0; PATH_WAVELENGTHS];
+
+        for i in 0..PATH_WAVELENGTHS
+        {
+            let offset = (u + i as f64 / PATH_WAVELENGTHS as f64) % 1.0;
+
+            lambda[i] = sample_visible(offset);
+            pdf[i] = visible_pdf(lambda[i]);
+        }
+
+        Wavelengths { lambda, pdf, hero_only : false }
+    }
+
+    pub fn hero(&self) -> f64
+    { return self.lambda[0]; }
+
+    // Values at the path's wavelengths of the spectrum upsampled from an RGB colour, stored in place of its channels
+    pub fn upsample(&self, color : Color) -> Color
+    {
+        return Color::new_rgb(
+            rgb_to_spectrum(color, self.lambda[0]),
+            rgb_to_spectrum(color, self.lambda[1]),
+            rgb_to_spectrum(color, self.lambda[2]));
+    }
+
+    // Drops all but the hero wavelength once the path depends on the wavelength, e.g. after dispersion
+    pub fn keep_hero(&mut self, values : Color) -> Color
+    {
+        if self.hero_only
+        { return values; }
+
+        self.hero_only = true;
+
+        // The hero now stands in for all wavelengths of the path
+        return Color::new_rgb(values.r * PATH_WAVELENGTHS as f64, 0.0, 0.0);
+    }
+
+    // Converts radiance at the path's wavelengths to linear sRGB, a constant spectrum of 1 becomes white
+    pub fn to_rgb(self, values : Color) -> Color
+    {
+        let samples = [values.r, values.g, values.b];
+        let mut xyz = [0.0; 3];
+
+        for ((lambda, pdf), value) in self.lambda.iter().zip(self.pdf).zip(samples)
+        {
+            if pdf <= 0.0
+            { continue; }
+
+            let cmf = color_matching(*lambda);
+            let weight = value / (pdf * PATH_WAVELENGTHS as f64);
+
+            for channel in 0..3
+            { xyz[channel] += cmf[channel] * weight; }
+        }
+
+        let rgb = xyz_to_linear_srgb(xyz);
+        let white = white_point();
+
+        return Color::new_rgb(rgb[0] / white[0], rgb[1] / white[1], rgb[2] / white[2]);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use gltf::image::Format;
+use crate::color::{srgb_to_linear, Color};
+
+// Image mapped onto a surface with texel values in linear space
+pub struct Texture
+{
+    pub width : usize,
+    pub height : usize,
+    texels : Vec<Color>
+}
+
+// Textures loaded for one scene, shared between every material that references the same file
+#[derive(Default)]
+pub struct TextureCache
+{
+    textures : HashMap<String, Arc<Texture>>
+}
+
+impl Texture
+{
+    fn from_rgba8(width : usize, height : usize, pixels : &[u8]) -> Self
+    {
+        let texels = pixels.chunks_exact(4)
+            .map(|p| Color::new_rgb(srgb_to_linear(p[0] as f64 / 255.0), srgb_to_linear(p[1] as f64 / 255.0), srgb_to_linear(p[2] as f64 / 255.0)))
+            .collect();
+
+        Texture
+        {
+            width,
+            height,
+            texels
+        }
+    }
+
+    // Decoded glTF images come in whichever channel layout the file used
+    fn from_gltf(image : &gltf::image::Data) -> Result<Self, String>
+    {
+        let (channels, bytes) = match image.format
+        {
+            Format::R8 => (1, 1),
+            Format::R8G8 => (2, 1),
+            Format::R8G8B8 => (3, 1),
+            Format::R8G8B8A8 => (4, 1),
+            Format::R16 => (1, 2),
+            Format::R16G16 => (2, 2),
+            Format::R16G16B16 => (3, 2),
+            Format::R16G16B16A16 => (4, 2),
+            format => return Err(format!("unsupported image format {:?}", format))
+        };
+
+        // Grey images repeat their first channel, only the high byte of 16 bit channels is kept
+        let rgba : Vec<u8> = image.pixels.chunks_exact(channels * bytes)
+            .flat_map(|p|
+            {
+                let channel = |i : usize| p[i.min(if channels < 3 { 0 } else { 2 }) * bytes + bytes - 1];
+
+                [channel(0), channel(1), channel(2), 255]
+            })
+            .collect();
+
+        return Ok(Texture::from_rgba8(image.width as usize, image.height as usize, &rgba));
+    }
+
+    // Bilinearly filtered and repeated outside of [0, 1], v = 0 is the top row of the image
+    pub fn sample(&self, u : f64, v : f64) -> Color
+    {
+        let x = u.rem_euclid(1.0) * self.width as f64 - 0.5;
+        let y = v.rem_euclid(1.0) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let texel = |i : f64, j : f64| -> Color
+        {
+            let col = (i as i64).rem_euclid(self.width as i64) as usize;
+            let row = (j as i64).rem_euclid(self.height as i64) as usize;
+
+            return self.texels[row * self.width + col];
+        };
+
+        let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1.0, y0) * fx;
+        let bottom = texel(x0, y0 + 1.0) * (1.0 - fx) + texel(x0 + 1.0, y0 + 1.0) * fx;
+
+        return top * (1.0 - fy) + bottom * fy;
+    }
+
+}
+
+impl TextureCache
+{
+    // Loads an image file, or the Nth image of a glTF model given as "model.glb#N", unless an earlier call did
+    pub fn load(&mut self, reference : &str) -> Result<Arc<Texture>, String>
+    {
+        if let Some(texture) = self.get(reference)
+        { return Ok(texture); }
+
+        match model_image(reference)
+        {
+            Some((model, index)) =>
+            {
+                let images = load_gltf_images(Path::new(model))?;
+
+                for (i, image) in images.into_iter().enumerate()
+                { self.textures.insert(format!("{}#{}", model, i), Arc::new(image)); }
+
+                return self.get(reference).ok_or(format!("{} has no image {}", model, index));
+            }
+
+            None =>
+            {
+                let image = ::image::open(reference).map_err(|err| err.to_string())?.to_rgba8();
+                let texture = Arc::new(Texture::from_rgba8(image.width() as usize, image.height() as usize, image.as_raw()));
+
+                self.textures.insert(reference.to_string(), texture.clone());
+
+                return Ok(texture);
+            }
+        }
+    }
+
+    // Texture an earlier call to load returned
+    pub fn get(&self, reference : &str) -> Option<Arc<Texture>>
+    {
+        // Model images are stored under their index as written by load, "model.glb#01" is image 1
+        let key = match model_image(reference)
+        {
+            Some((model, index)) => format!("{}#{}", model, index),
+            None => reference.to_string()
+        };
+
+        return self.textures.get(&key).cloned();
+    }
+}
+
+// Model file and image index of a reference to an image inside a glTF model, None for other image files,
+// whose names may contain '#' as well
+pub fn model_image(reference : &str) -> Option<(&str, usize)>
+{
+    let (model, index) = reference.rsplit_once('#')?;
+    let extension = Path::new(model).extension()?.to_string_lossy().to_lowercase();
+
+    if extension != "gltf" && extension != "glb"
+    { return None; }
+
+    return Some((model, index.parse().ok()?));
+}
+
+fn load_gltf_images(path : &Path) -> Result<Vec<Texture>, String>
+{
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path).map_err(|err| err.to_string())?;
+    let base = path.parent();
+    let buffers = gltf::import_buffers(&document, base, blob).map_err(|err| err.to_string())?;
+    let images = gltf::import_images(&document, base, &buffers).map_err(|err| err.to_string())?;
+
+    return images.iter().map(Texture::from_gltf).collect();
+}
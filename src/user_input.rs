@@ -1,13 +1,25 @@
 use std::process::Command;
+use std::error::Error;
 use std::io;
 use std::fs;
-use crate::json_utils::{get_scene_from_json, get_scenes_folder};
-use crate::scene::Scene;
-use crate::render::render;
-use crate::image::Image;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use path_tracer::{get_scene_from_json, render_with_progress, validate_settings, CancelToken, ConsoleProgress, RenderSettings, Scene,
+    SceneError, SceneFormat};
+use crate::cli_args::CliArgs;
 
 const ILLEGAL_SYMBOLS: &[char] = &['\\', '/', ':', '*', '?', '"', '<', '>', '|', '.'];
 
+// Used unless given on the command line, relative to the working directory
+const DEFAULT_SCENES_DIR : &str = "scenes";
+const DEFAULT_RENDERS_DIR : &str = "renders";
+
+// Cancelled by Ctrl-C while a render is running, Ctrl-C at any other time quits
+static INTERRUPT : OnceLock<CancelToken> = OnceLock::new();
+static RENDERING : AtomicBool = AtomicBool::new(false);
+
 fn get_gui_script_path() -> String
 {
     let mut path_to_exe = match  std::env::current_exe()
@@ -22,25 +34,22 @@ fn get_gui_script_path() -> String
     path_to_exe.pop();
     let path_string = path_to_exe.to_str().unwrap();
 
-    let script_path : String;
-    if cfg!(target_os = "windows")
-    { script_path = format!("{}{}", path_string, "\\scene_editor\\scene_editor.py"); }
-    else
-    { script_path = format!("{}{}", path_string, "/scene_editor/scene_editor.py"); };
-    
+    let script_path =
+        if cfg!(target_os = "windows")
+        { format!("{}{}", path_string, "\\scene_editor\\scene_editor.py") }
+        else
+        { format!("{}{}", path_string, "/scene_editor/scene_editor.py") };
 
     return script_path;
 }
 
 fn run_gui_script()
 {
-    let interpreter = 
-    
-    if cfg!(target_os = "windows")
-    { "python.exe" }
-    
-    else
-    { "python" };
+    let interpreter =
+        if cfg!(target_os = "windows")
+        { "python.exe" }
+        else
+        { "python" };
 
     let script_path = get_gui_script_path();
 
@@ -50,18 +59,31 @@ fn run_gui_script()
     { println!("Opening scene editor failed.") }
 }
 
-pub fn get_scenes() -> Vec<String>
+pub fn get_scenes(scenes_dir : &Path) -> Vec<String>
 {
-    let scenes_path = get_scenes_folder();
     let mut found_scenes : Vec<String >= Vec::new();
 
-    let scenes : Vec<_> = fs::read_dir(scenes_path).expect("Reading from scenes folder failed.")
+    let entries = match fs::read_dir(scenes_dir)
+    {
+        Ok(entries) => entries,
+        Err(err) =>
+        {
+            println!("Reading from scenes folder {} failed: {}", scenes_dir.display(), err);
+
+            return found_scenes;
+        }
+    };
+
+    let scenes : Vec<_> = entries
     .filter_map(|entry|
         {
             let entry = entry.expect("Reading entry from scenes folder failed");
             let path = entry.path();
 
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "json")
+            // Mesh files have no camera and are only used through includes
+            let renderable = SceneFormat::from_path(&path).is_some_and(|format| !matches!(format, SceneFormat::Ply | SceneFormat::Stl));
+
+            if path.is_file() && renderable
             { Some(path.file_name().unwrap().to_string_lossy().into_owned()) }
             else { None }
         }).collect();
@@ -87,8 +109,7 @@ fn get_file_name() -> String
 
         if trimmed_input.chars().any(|c| ILLEGAL_SYMBOLS.contains(&c))
         { println!("Please refrain from using any illegal symbols"); }
-        
-        else 
+        else
         { correct = true }
     }
     
@@ -98,7 +119,7 @@ fn get_file_name() -> String
     return formatted_file_name
 }
 
-fn render_scene_dialog(scene : Scene)
+fn render_scene_dialog(scene : Scene, mut settings : RenderSettings, renders_dir : &Path)
 {
     let mut correct = false;
 
@@ -117,34 +138,160 @@ fn render_scene_dialog(scene : Scene)
 
         match parsed_input
         {
-            Ok(spp) => correct = render_scene(&scene, spp, file_name),
+            Ok(spp) =>
+            {
+                settings.spp = spp;
+                settings.time_limit = None;
+                settings.outputs = vec![renders_dir.join(file_name)];
+
+                if let Err(err) = render_scene(&scene, &settings)
+                { println!("{}", err); }
+
+                correct = true;
+            }
+
             Err(..) => println!("Incorrect scene choice input format.")
         };
     }
 }
 
-fn render_scene(scene : &Scene, spp : u32, file_name : String) -> bool
+// The handler is installed with the first render, so Ctrl-C keeps its usual meaning before that
+fn interrupt_token() -> CancelToken
 {
-    let width = scene.camera.img_width;
-    let height = scene.camera.img_height;
-    let image = Image::new(width, height);
-    let image_name = format!("{}", file_name);
+    return INTERRUPT.get_or_init(||
+    {
+        let token = CancelToken::new();
+        let handler_token = token.clone();
+
+        let installed = ctrlc::set_handler(move ||
+        {
+            if RENDERING.load(Ordering::Relaxed) && ! handler_token.is_cancelled()
+            {
+                handler_token.cancel();
+                println!("\nFinishing the current pass, press Ctrl-C again to quit without writing the image");
+            }
+            else
+            { std::process::exit(130); }
+        });
+
+        if let Err(err) = installed
+        { println!("Renders can't be stopped with Ctrl-C: {}", err); }
+
+        token
+    }).clone();
+}
+
+// Errs if the render could not be started or any of the outputs could not be written
+fn render_scene(scene : &Scene, settings : &RenderSettings) -> io::Result<()>
+{
+    let cancel = interrupt_token();
+    cancel.reset();
+
+    let benchmark = Instant::now();
+
+    RENDERING.store(true, Ordering::Relaxed);
+    let rendered = render_with_progress(scene, settings, &ConsoleProgress::new(), &cancel);
+    RENDERING.store(false, Ordering::Relaxed);
 
-    render(image,&image_name, scene, spp);
+    let image = rendered?;
 
-    return true;
+    let elapsed = benchmark.elapsed();
+
+    println!("Render took {} minutes and {} seconds", elapsed.as_secs() / 60, elapsed.as_secs() % 60);
+
+    if cancel.is_cancelled()
+    { println!("Render stopped early, writing the passes that finished"); }
+    else
+    { println!("Render finished"); }
+
+    // A failed output doesn't stop the others from being written
+    let mut failed = 0;
+
+    for output in &settings.outputs
+    {
+        match image.write(output)
+        {
+            Ok(()) => println!("Wrote {}", output.display()),
+            Err(err) =>
+            {
+                eprintln!("Writing {} failed: {}", output.display(), err);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0
+    { return Err(io::Error::other(format!("{} of {} outputs could not be written", failed, settings.outputs.len()))); }
+
+    return Ok(());
 }
 
-fn scene_choice_correct(scene_num : i32, scenes : &Vec<String>) -> bool
+fn scene_choice_correct(scene_num : i32, scenes : &[String]) -> bool
 { return ! (scene_num > scenes.len() as i32 || scene_num < 1); }
 
-fn load_and_render_scene()
+// Settings from the command line are checked against the scene again, they may not suit it
+fn apply_cli_overrides(scene : &Scene, settings : &mut RenderSettings, cli_args : &CliArgs) -> Result<(), SceneError>
+{
+    // Either one replaces what the scene file asks for
+    if let Some(spp) = cli_args.spp
+    {
+        settings.spp = spp;
+        settings.time_limit = None;
+    }
+
+    if let Some(seconds) = cli_args.time_limit
+    { settings.time_limit = Duration::try_from_secs_f64(seconds).ok(); }
+
+    if let Some(min) = cli_args.min_bounces
+    { settings.min_bounces = min; }
+
+    if let Some(max) = cli_args.max_bounces
+    { settings.max_bounces = max; }
+
+    if cli_args.spectral
+    { settings.spectral = true; }
+
+    if let Some(integrator) = cli_args.integrator
+    { settings.integrator = integrator; }
+
+    if let Some(sampler) = cli_args.sampler
+    { settings.sampler = sampler; }
+
+    if let Some(filter) = cli_args.filter
+    { settings.filter = filter; }
+
+    if cli_args.seed.is_some()
+    { settings.seed = cli_args.seed; }
+
+    if let Some(threads) = cli_args.threads
+    { settings.threads = threads; }
+
+    if cli_args.crop.is_some()
+    { settings.crop = cli_args.crop; }
+
+    if ! cli_args.outputs.is_empty()
+    { settings.outputs = cli_args.outputs.clone(); }
+
+    if let Some(radius) = cli_args.ao_radius
+    { settings.ao_radius = radius; }
+
+    let issues = validate_settings(scene, settings);
+
+    if ! issues.is_empty()
+    { return Err(SceneError::Invalid(issues)); }
+
+    return Ok(());
+}
+
+fn load_and_render_scene(cli_args : &CliArgs)
 {
     let mut end = false;
-    let scenes = get_scenes();
+    let scenes_dir = cli_args.scenes_dir.clone().unwrap_or(PathBuf::from(DEFAULT_SCENES_DIR));
+    let renders_dir = cli_args.renders_dir.clone().unwrap_or(PathBuf::from(DEFAULT_RENDERS_DIR));
+    let scenes = get_scenes(&scenes_dir);
     let mut choice_correct = false;
 
-    if scenes.len() <= 0
+    if scenes.is_empty()
     {
         println!("No scenes present in scenes folder");
 
@@ -155,8 +302,8 @@ fn load_and_render_scene()
     {
         println!("\nAvailible scenes:");
         
-        for i in 0..scenes.len()
-        { println!("{} {}", i + 1, scenes[i]); }
+        for (i, scene) in scenes.iter().enumerate()
+        { println!("{} {}", i + 1, scene); }
 
         println!("\nChoose scene: ");
 
@@ -178,20 +325,45 @@ fn load_and_render_scene()
             let choice = parsed_input.unwrap() - 1;
             let scene_name = &scenes[choice as usize];
 
-            let scene = get_scene_from_json(scene_name);
-            render_scene_dialog(scene);
+            let loaded = get_scene_from_json(&scenes_dir.join(scene_name), &cli_args.asset_paths)
+                .and_then(|(scene, mut settings)| apply_cli_overrides(&scene, &mut settings, cli_args).map(|()| (scene, settings)));
+
+            match loaded
+            {
+                Ok((scene, settings)) => render_scene_dialog(scene, settings, &renders_dir),
+                Err(err) => println!("{}", err)
+            }
 
             end = true;
         }
     }
 }
 
-fn execute_choice(choice : i32) -> bool
+// Renders the scene given on the command line without going through the menu
+pub fn render_scene_file(scene_path : &Path, cli_args : &CliArgs) -> Result<(), Box<dyn Error>>
+{
+    let (scene, mut settings) = get_scene_from_json(scene_path, &cli_args.asset_paths)?;
+    apply_cli_overrides(&scene, &mut settings, cli_args)?;
+
+    // Defaults to the scene's name in the working directory
+    if settings.outputs.is_empty()
+    {
+        let output = scene_path.with_extension("ppm").file_name().map(PathBuf::from).unwrap_or(PathBuf::from("render.ppm"));
+
+        settings.outputs.push(output);
+    }
+
+    render_scene(&scene, &settings)?;
+
+    return Ok(());
+}
+
+fn execute_choice(choice : i32, cli_args : &CliArgs) -> bool
 {
     match choice
     {
         1 => run_gui_script(),
-        2 => load_and_render_scene() ,
+        2 => load_and_render_scene(cli_args) ,
         3 => return true,
         _ => println!("Incorrect choice input.")
     }
@@ -199,7 +371,7 @@ fn execute_choice(choice : i32) -> bool
     return  false;
 }
 
-pub fn main_loop()
+pub fn main_loop(cli_args : CliArgs)
 {
     let mut end = false;
     
@@ -213,7 +385,7 @@ pub fn main_loop()
 
         match trimmed_input.parse::<i32>()
         {
-            Ok(choice) => end = execute_choice(choice),
+            Ok(choice) => end = execute_choice(choice, &cli_args),
             Err(..) => println!("Incorrect input format.")
         };
     }
@@ -1,13 +1,18 @@
-#[derive(Clone, Copy)]
+/// Point or direction in scene space, y points up in the shipped scenes.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
 pub struct Vector
 {
+    /// First coordinate.
     pub x : f64,
+    /// Second coordinate.
     pub y : f64,
+    /// Third coordinate.
     pub z : f64
 }
 
 impl Vector
 {
+    /// Zero vector.
     pub fn new() -> Self
     {
         Vector
@@ -18,7 +23,7 @@ impl Vector
         }
     }
 
-    pub fn set_vector(&mut self, x : f64, y : f64, z : f64) -> &mut Self
+    pub(crate) fn set_vector(&mut self, x : f64, y : f64, z : f64) -> &mut Self
     {
         self.x = x;
         self.y = y;
@@ -27,33 +32,23 @@ impl Vector
         self
     }
 
-    pub fn mul_by_vec(&self, vec2 : Vector) -> Vector
-    {
-        Vector
-        {
-            x : self.x * vec2.x,
-            y : self.y * vec2.y,
-            z : self.z * vec2.z
-        }
-    }
-
-    pub fn normalize(&mut self) -> &mut Self
+    pub(crate) fn normalize(&mut self) -> &mut Self
     {
         let vec_len : f64 = (self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
 
-        self.x = self.x * (1.0 / vec_len);
-        self.y = self.y * (1.0 / vec_len);
-        self.z = self.z * (1.0 / vec_len);
+        self.x *= 1.0 / vec_len;
+        self.y *= 1.0 / vec_len;
+        self.z *= 1.0 / vec_len;
 
         self
     }
 
-    pub fn dot_product(&self, vec2 : Vector) -> f64
+    pub(crate) fn dot_product(&self, vec2 : Vector) -> f64
     {
         self.x * vec2.x + self.y * vec2.y + self.z * vec2.z
     }
 
-    pub fn cross_product(&self, vec2 : Vector) -> Vector
+    pub(crate) fn cross_product(&self, vec2 : Vector) -> Vector
     {
         Vector
         {
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+use crate::aabb::Aabb;
+use crate::vector::Vector;
+
+// Grid files start with the magic bytes followed by the x, y and z resolution as little endian u32,
+// then hold one little endian f32 per voxel with x varying fastest and z slowest
+const MAGIC : &[u8; 4] = b"VGRD";
+const HEADER_SIZE : usize = 16;
+
+// Dense grid of densities stretched over a box in world space
+pub struct VoxelGrid
+{
+    resolution : [usize; 3],
+    data : Vec<f32>,
+    bounds : Aabb,
+    max_density : f64
+}
+
+// File and the bits of the bounds' corners a grid was loaded with
+type GridKey = (String, [u64; 6]);
+
+// Grids loaded for one scene, shared with every medium that uses the same file and bounds
+#[derive(Default)]
+pub struct GridCache
+{
+    grids : HashMap<GridKey, Arc<VoxelGrid>>
+}
+
+fn grid_key(path : &str, bounds : Aabb) -> GridKey
+{
+    let (min, max) = (bounds.min, bounds.max);
+
+    return (path.to_string(), [min.x, min.y, min.z, max.x, max.y, max.z].map(f64::to_bits));
+}
+
+fn read_u32(bytes : &[u8], offset : usize) -> usize
+{
+    return u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]) as usize;
+}
+
+impl VoxelGrid
+{
+    pub fn new(grid_resolution : [usize; 3], grid_data : Vec<f32>, grid_bounds : Aabb) -> Self
+    {
+        let max = grid_data.iter().fold(0.0_f32, |acc, d| acc.max(*d));
+
+        VoxelGrid
+        {
+            resolution : grid_resolution,
+            data : grid_data,
+            bounds : grid_bounds,
+            max_density : max as f64
+        }
+    }
+
+    // Resolution stored in the header, checked against the size of the whole file
+    fn read_header(header : &[u8], file_size : usize) -> Result<[usize; 3], Error>
+    {
+        if header.len() < HEADER_SIZE || &header[0..4] != MAGIC
+        { return Err(Error::new(ErrorKind::InvalidData, "Not a voxel grid file")); }
+
+        let resolution = [read_u32(header, 4), read_u32(header, 8), read_u32(header, 12)];
+        let count = resolution[0] * resolution[1] * resolution[2];
+
+        if count == 0 || file_size != HEADER_SIZE + 4 * count
+        { return Err(Error::new(ErrorKind::InvalidData, "Voxel grid size does not match its resolution")); }
+
+        return Ok(resolution);
+    }
+
+    pub fn load(path : &str, grid_bounds : Aabb) -> Result<Self, Error>
+    {
+        let bytes = fs::read(path)?;
+        let resolution = VoxelGrid::read_header(&bytes, bytes.len())?;
+
+        let data = bytes[HEADER_SIZE..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]).max(0.0))
+            .collect();
+
+        return Ok(VoxelGrid::new(resolution, data, grid_bounds));
+    }
+
+    pub fn max_density(&self) -> f64
+    { return self.max_density; }
+
+    pub fn bounds(&self) -> Aabb
+    { return self.bounds; }
+
+    fn voxel(&self, x : usize, y : usize, z : usize) -> f64
+    { return self.data[(z * self.resolution[1] + y) * self.resolution[0] + x] as f64; }
+
+    // Trilinearly interpolated density with voxel values at the cell centres, 0 outside of the bounds
+    pub fn density(&self, point : Vector) -> f64
+    {
+        if ! self.bounds.contains(point, 0.0)
+        { return 0.0; }
+
+        let size = self.bounds.max - self.bounds.min;
+        let rel = point - self.bounds.min;
+        let coords = [rel.x / size.x, rel.y / size.y, rel.z / size.z];
+
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        let mut frac = [0.0; 3];
+
+        for axis in 0..3
+        {
+            let cells = self.resolution[axis];
+            let pos = (coords[axis] * cells as f64 - 0.5).clamp(0.0, (cells - 1) as f64);
+
+            lower[axis] = pos.floor() as usize;
+            upper[axis] = (lower[axis] + 1).min(cells - 1);
+            frac[axis] = pos - lower[axis] as f64;
+        }
+
+        let lerp = |a : f64, b : f64, t : f64| a + (b - a) * t;
+
+        let x00 = lerp(self.voxel(lower[0], lower[1], lower[2]), self.voxel(upper[0], lower[1], lower[2]), frac[0]);
+        let x10 = lerp(self.voxel(lower[0], upper[1], lower[2]), self.voxel(upper[0], upper[1], lower[2]), frac[0]);
+        let x01 = lerp(self.voxel(lower[0], lower[1], upper[2]), self.voxel(upper[0], lower[1], upper[2]), frac[0]);
+        let x11 = lerp(self.voxel(lower[0], upper[1], upper[2]), self.voxel(upper[0], upper[1], upper[2]), frac[0]);
+
+        return lerp(lerp(x00, x10, frac[1]), lerp(x01, x11, frac[1]), frac[2]);
+    }
+}
+
+impl GridCache
+{
+    // Grid of the file stretched over the bounds, loaded unless an earlier call did
+    pub fn load(&mut self, path : &str, bounds : Aabb) -> Result<Arc<VoxelGrid>, Error>
+    {
+        let key = grid_key(path, bounds);
+
+        if let Some(grid) = self.grids.get(&key)
+        { return Ok(grid.clone()); }
+
+        let grid = Arc::new(VoxelGrid::load(path, bounds)?);
+        self.grids.insert(key, grid.clone());
+
+        return Ok(grid);
+    }
+
+    // Grid an earlier call to load returned
+    pub fn get(&self, path : &str, bounds : Aabb) -> Option<Arc<VoxelGrid>>
+    { return self.grids.get(&grid_key(path, bounds)).cloned(); }
+}